@@ -0,0 +1,689 @@
+//! Iterator adapters over streams of `(NaiveDateTime, Tick)`.
+//!
+//! The adapters are available on any such iterator, in particular on
+//! `Bi5Iter`, by importing the `TickIteratorExt` trait. As a directory is
+//! read as one continuous stream, adapters that compare with the previous
+//! tick do so across file boundaries.
+
+use chrono::NaiveDateTime;
+use crate::{Anomaly, Tick};
+
+/// Extension trait with tick specific iterator adapters
+///
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let moves = ticks.into_iter().price_changes_only().count();
+/// assert!(moves <= 10412);
+/// ```
+pub trait TickIteratorExt: Iterator<Item = (NaiveDateTime, Tick)> + Sized {
+
+    /// Drop ticks whose bid and ask both equal those of the last emitted tick
+    fn price_changes_only(self) -> PriceChanges<Self> {
+        PriceChanges { iter: self, last: None }
+    }
+
+    /// Drop ticks whose bidsize and asksize both equal those of the last
+    /// emitted tick, regardless of the prices
+    ///
+    /// Sizes are compared bitwise, so a `NaN` size equals itself.
+    fn size_changes_only(self) -> SizeChanges<Self> {
+        SizeChanges { iter: self, last: None }
+    }
+
+    /// Of each run of ticks with the same absolute time keep only the last
+    /// one, the prevailing quote at that millisecond
+    ///
+    /// Runs are grouped by the time, not by `millisecs`, so ticks of two
+    /// files sharing a boundary millisecond form one run. The adapter holds
+    /// back one tick to see where a run ends.
+    /// ```
+    /// use bi5::*;
+    /// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+    /// let ticks: Vec<_> = ticks.into_iter().last_per_ms().collect();
+    /// assert!(ticks.windows(2).all(|w| w[0].0 != w[1].0));
+    /// ```
+    fn last_per_ms(self) -> LastPerMs<Self> {
+        LastPerMs { iter: self, pending: None }
+    }
+
+    /// Keep the ticks in `[start, end)` of a time-sorted stream
+    ///
+    /// Ticks before `start` are skipped. At the first tick at or after `end`
+    /// the adapter ends and stops pulling from the inner iterator, so the
+    /// remaining files of a directory are not decompressed.
+    fn clamp_to_range(self, start: NaiveDateTime, end: NaiveDateTime) -> ClampToRange<Self> {
+        ClampToRange { iter: Some(self), start, end }
+    }
+
+    /// Convert to a stream of `TickDelta`s, each relative to the previous
+    /// tick (the first relative to an all-zero tick), see `undelta`
+    fn deltas(self) -> Deltas<Self> {
+        Deltas { iter: self, prev: ZERO_TICK }
+    }
+
+    /// Tag each tick with its `Anomaly`, `None` for a regular quote
+    ///
+    /// ```
+    /// use bi5::*;
+    /// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+    /// let crossed = ticks.into_iter().flag_anomalies()
+    ///     .filter(|(_, _, anomaly)| *anomaly == Some(Anomaly::Crossed))
+    ///     .count();
+    /// assert_eq!(crossed, 0);
+    /// ```
+    fn flag_anomalies(self) -> FlagAnomalies<Self> {
+        FlagAnomalies { iter: self }
+    }
+
+    /// Drop the ticks with a zero size, see `Tick::is_one_sided`. To flag
+    /// them instead, map with `tick.is_one_sided()`.
+    ///
+    /// ```
+    /// use bi5::*;
+    /// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+    /// assert!(ticks.into_iter().drop_one_sided().all(|(_, tick)| tick.bidsize > 0.0 && tick.asksize > 0.0));
+    /// ```
+    fn drop_one_sided(self) -> DropOneSided<Self> {
+        DropOneSided { iter: self }
+    }
+
+    /// Split each tick into a bid and an ask quote event with the tick's
+    /// time, `(t, side, price, size)`, the bid event first
+    ///
+    /// ```
+    /// use bi5::*;
+    /// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+    /// let events: Vec<_> = ticks.into_iter().split_sides().take(2).collect();
+    /// assert_eq!(events[0].1, Side::Bid);
+    /// assert_eq!((events[1].1, events[1].2), (Side::Ask, 133153));
+    /// assert_eq!(events[0].0, events[1].0);
+    /// ```
+    fn split_sides(self) -> SplitSides<Self> {
+        SplitSides { iter: self, ask: None }
+    }
+
+    /// Tag each tick with the `Direction` of its mid price relative to the
+    /// previous tick (the tick rule), the first tick is a zero-tick
+    ///
+    /// ```
+    /// use bi5::*;
+    /// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+    /// let ups = ticks.into_iter().with_tick_direction()
+    ///     .filter(|(_, _, direction)| *direction == Direction::Up)
+    ///     .count();
+    /// assert!(ups > 0);
+    /// ```
+    fn with_tick_direction(self) -> WithTickDirection<Self> {
+        WithTickDirection { iter: self, prev_mid: None }
+    }
+
+    /// Tag each tick with the signed changes `(dbid, dask)` of its bid and ask
+    /// in raw points since the previous tick, zero for the first tick
+    ///
+    /// The previous tick is the one before in the stream, also across file
+    /// boundaries, e.g. as basis of tick returns.
+    /// ```
+    /// use bi5::*;
+    /// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+    /// let deltas: Vec<_> = ticks.into_iter().with_price_deltas().take(2).map(|(_, _, dbid, dask)| (dbid, dask)).collect();
+    /// assert_eq!(deltas, vec![(0, 0), (11, -20)]);
+    /// ```
+    fn with_price_deltas(self) -> WithPriceDeltas<Self> {
+        WithPriceDeltas { iter: self, prev: None }
+    }
+
+    /// Swap bid and ask, and their sizes, of crossed ticks (`bid > ask`)
+    ///
+    /// This repairs feeds known to swap the sides. Crossed quotes also occur
+    /// in real markets, so only use it for such feeds. The number of swaps
+    /// so far is `NormalizeCrossed::swapped`.
+    fn normalize_crossed(self) -> NormalizeCrossed<Self> {
+        NormalizeCrossed { iter: self, swapped: 0 }
+    }
+
+    /// Tag each tick with the running sum of `asksize + bidsize` over the
+    /// stream up to and including the tick
+    ///
+    /// The sum is accumulated in `f64`, so it doesn't lose the small sizes
+    /// late in a long stream like an `f32` sum would.
+    /// ```
+    /// use bi5::*;
+    /// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+    /// let (_, _, volume) = ticks.into_iter().with_cumulative_volume().last().unwrap();
+    /// assert!(volume > 0.0);
+    /// ```
+    fn with_cumulative_volume(self) -> WithCumulativeVolume<Self> {
+        WithCumulativeVolume { iter: self, volume: 0.0 }
+    }
+
+    /// Number the ticks of the stream consecutively from `base`, e.g. as
+    /// primary key when loading them into a database
+    ///
+    /// Unlike `enumerate` the number comes first and is a `u64`, so it
+    /// doesn't depend on the platform.
+    /// ```
+    /// use bi5::*;
+    /// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+    /// let (seq, _, _) = ticks.into_iter().with_sequence(1000).last().unwrap();
+    /// assert_eq!(seq, 1000 + 10411);
+    /// ```
+    fn with_sequence(self, base: u64) -> WithSequence<Self> {
+        WithSequence { iter: self, next: base }
+    }
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> TickIteratorExt for I {}
+
+/// Iterator returned by `TickIteratorExt::price_changes_only`
+pub struct PriceChanges<I> {
+    iter: I,
+    last: Option<(u32, u32)>,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for PriceChanges<I> {
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        for (t, tick) in self.iter.by_ref() {
+            if self.last != Some((tick.bid, tick.ask)) {
+                self.last = Some((tick.bid, tick.ask));
+                return Some((t, tick));
+            }
+        }
+        None
+    }
+}
+
+/// Iterator returned by `TickIteratorExt::size_changes_only`
+pub struct SizeChanges<I> {
+    iter: I,
+    /// bits of the sizes of the last emitted tick
+    last: Option<(u32, u32)>,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for SizeChanges<I> {
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        for (t, tick) in self.iter.by_ref() {
+            let sizes = (tick.bidsize.to_bits(), tick.asksize.to_bits());
+            if self.last != Some(sizes) {
+                self.last = Some(sizes);
+                return Some((t, tick));
+            }
+        }
+        None
+    }
+}
+
+/// Iterator returned by `TickIteratorExt::last_per_ms`
+pub struct LastPerMs<I> {
+    iter: I,
+    /// the last tick read, not yet known to end its run
+    pending: Option<(NaiveDateTime, Tick)>,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for LastPerMs<I> {
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut last = self.pending.take().or_else(|| self.iter.next())?;
+        for item in self.iter.by_ref() {
+            if item.0 != last.0 {
+                self.pending = Some(item);
+                break;
+            }
+            last = item;
+        }
+        Some(last)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.iter.size_hint();
+        let pending = self.pending.is_some() as usize;
+        ((lower + pending).min(1), upper.and_then(|upper| upper.checked_add(pending)))
+    }
+}
+
+/// Iterator returned by `TickIteratorExt::clamp_to_range`
+pub struct ClampToRange<I> {
+    /// `None` once `end` was reached
+    iter: Option<I>,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for ClampToRange<I> {
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        let iter = self.iter.as_mut()?;
+        for (t, tick) in iter.by_ref() {
+            if t >= self.end {
+                break;
+            }
+            if t >= self.start {
+                return Some((t, tick));
+            }
+        }
+        self.iter = None;
+        None
+    }
+}
+
+/// Iterator returned by `TickIteratorExt::flag_anomalies`
+pub struct FlagAnomalies<I> {
+    iter: I,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for FlagAnomalies<I> {
+    type Item = (NaiveDateTime, Tick, Option<Anomaly>);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(t, tick)| (t, tick, tick.anomaly()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Iterator returned by `TickIteratorExt::drop_one_sided`
+pub struct DropOneSided<I> {
+    iter: I,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for DropOneSided<I> {
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.by_ref().find(|(_, tick)| !tick.is_one_sided())
+    }
+}
+
+/// Side of a quote event, see `TickIteratorExt::split_sides`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// `bid` and `bidsize`
+    Bid,
+    /// `ask` and `asksize`
+    Ask,
+}
+
+/// Iterator returned by `TickIteratorExt::split_sides`
+pub struct SplitSides<I> {
+    iter: I,
+    /// the ask event of the last tick, not yet emitted
+    ask: Option<(NaiveDateTime, Side, u32, f32)>,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for SplitSides<I> {
+    type Item = (NaiveDateTime, Side, u32, f32);
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(ask) = self.ask.take() {
+            return Some(ask);
+        }
+        let (t, tick) = self.iter.next()?;
+        self.ask = Some((t, Side::Ask, tick.ask, tick.asksize));
+        Some((t, Side::Bid, tick.bid, tick.bidsize))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = self.ask.is_some() as usize;
+        let (low, high) = self.iter.size_hint();
+        (low.saturating_mul(2).saturating_add(pending), high.and_then(|high| high.checked_mul(2)?.checked_add(pending)))
+    }
+}
+
+/// Mid price move of a tick, see `TickIteratorExt::with_tick_direction`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The mid price rose
+    Up,
+    /// The mid price fell
+    Down,
+    /// The mid price is unchanged
+    Zero,
+}
+
+/// Iterator returned by `TickIteratorExt::with_tick_direction`
+pub struct WithTickDirection<I> {
+    iter: I,
+    /// `bid + ask` of the previous tick, twice the mid price
+    prev_mid: Option<u64>,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for WithTickDirection<I> {
+    type Item = (NaiveDateTime, Tick, Direction);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (t, tick) = self.iter.next()?;
+        let mid = tick.bid as u64 + tick.ask as u64;
+        let direction = match self.prev_mid.replace(mid) {
+            Some(prev) if mid > prev => Direction::Up,
+            Some(prev) if mid < prev => Direction::Down,
+            _ => Direction::Zero,
+        };
+        Some((t, tick, direction))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Iterator returned by `TickIteratorExt::with_price_deltas`
+pub struct WithPriceDeltas<I> {
+    iter: I,
+    /// bid and ask of the previous tick
+    prev: Option<(u32, u32)>,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for WithPriceDeltas<I> {
+    type Item = (NaiveDateTime, Tick, i64, i64);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (t, tick) = self.iter.next()?;
+        let (bid, ask) = self.prev.replace((tick.bid, tick.ask)).unwrap_or((tick.bid, tick.ask));
+        Some((t, tick, tick.bid as i64 - bid as i64, tick.ask as i64 - ask as i64))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Iterator returned by `TickIteratorExt::normalize_crossed`
+pub struct NormalizeCrossed<I> {
+    iter: I,
+    swapped: usize,
+}
+
+impl<I> NormalizeCrossed<I> {
+    /// Number of crossed ticks swapped so far
+    pub fn swapped(&self) -> usize {
+        self.swapped
+    }
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for NormalizeCrossed<I> {
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (t, mut tick) = self.iter.next()?;
+        if tick.is_crossed() {
+            self.swapped += 1;
+            std::mem::swap(&mut tick.bid, &mut tick.ask);
+            std::mem::swap(&mut tick.bidsize, &mut tick.asksize);
+        }
+        Some((t, tick))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Iterator returned by `TickIteratorExt::with_cumulative_volume`
+pub struct WithCumulativeVolume<I> {
+    iter: I,
+    volume: f64,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for WithCumulativeVolume<I> {
+    type Item = (NaiveDateTime, Tick, f64);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (t, tick) = self.iter.next()?;
+        self.volume += tick.asksize as f64 + tick.bidsize as f64;
+        Some((t, tick, self.volume))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Iterator returned by `TickIteratorExt::with_sequence`
+pub struct WithSequence<I> {
+    iter: I,
+    next: u64,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for WithSequence<I> {
+    type Item = (u64, NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (t, tick) = self.iter.next()?;
+        let seq = self.next;
+        self.next += 1;
+        Some((seq, t, tick))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Difference of a tick to its predecessor, see `TickIteratorExt::deltas`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickDelta {
+    /// Difference of `millisecs`, negative at a file boundary
+    pub dmillis: i64,
+    /// Difference of `bid`
+    pub dbid: i64,
+    /// Difference of `ask`
+    pub dask: i64,
+    /// Difference of `bidsize`
+    pub dbidsize: f32,
+    /// Difference of `asksize`
+    pub dasksize: f32,
+}
+
+const ZERO_TICK: Tick = Tick { millisecs: 0, ask: 0, bid: 0, asksize: 0.0, bidsize: 0.0 };
+
+/// Apply `delta` to `prev`, wrapping like the `u32` fields
+fn apply(prev: &Tick, delta: &TickDelta) -> Tick {
+    Tick {
+        millisecs: (prev.millisecs as i64 + delta.dmillis) as u32,
+        ask: (prev.ask as i64 + delta.dask) as u32,
+        bid: (prev.bid as i64 + delta.dbid) as u32,
+        asksize: prev.asksize + delta.dasksize,
+        bidsize: prev.bidsize + delta.dbidsize,
+    }
+}
+
+/// Iterator returned by `TickIteratorExt::deltas`
+pub struct Deltas<I> {
+    iter: I,
+    /// the previous tick as `undelta` reconstructs it
+    prev: Tick,
+}
+
+impl<I: Iterator<Item = (NaiveDateTime, Tick)>> Iterator for Deltas<I> {
+    type Item = TickDelta;
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, tick) = self.iter.next()?;
+        let prev = &self.prev;
+        // sizes relative to the reconstruction, so float rounding doesn't accumulate
+        let delta = TickDelta {
+            dmillis: tick.millisecs as i64 - prev.millisecs as i64,
+            dbid: tick.bid as i64 - prev.bid as i64,
+            dask: tick.ask as i64 - prev.ask as i64,
+            dbidsize: tick.bidsize - prev.bidsize,
+            dasksize: tick.asksize - prev.asksize,
+        };
+        self.prev = apply(prev, &delta);
+        Some(delta)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Reconstruct the ticks from a stream of `TickDelta`s, the inverse of
+/// `TickIteratorExt::deltas`
+///
+/// Prices and times are restored exactly, sizes up to float rounding. The
+/// datetimes are not part of the deltas.
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let undone: Vec<Tick> = undelta(ticks.iter().copied().deltas()).collect();
+/// assert!(undone.iter().zip(&ticks).all(|(a, (_, b))| a.approx_eq(b, 1e-6)));
+/// ```
+pub fn undelta<I: IntoIterator<Item = TickDelta>>(iter: I) -> Undelta<I::IntoIter> {
+    Undelta { iter: iter.into_iter(), prev: ZERO_TICK }
+}
+
+/// Iterator returned by `undelta`
+pub struct Undelta<I> {
+    iter: I,
+    prev: Tick,
+}
+
+impl<I: Iterator<Item = TickDelta>> Iterator for Undelta<I> {
+    type Item = Tick;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.prev = apply(&self.prev, &self.iter.next()?);
+        Some(self.prev)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Columns of the rows of `to_rows`
+pub const ROW_COLUMNS: [&str; 5] = ["epoch_ms", "bid", "ask", "bidsize", "asksize"];
+
+/// Collect ticks into an N×5 row-major matrix with the columns `ROW_COLUMNS`:
+/// `[epoch_ms, bid, ask, bidsize, asksize]`
+///
+/// `epoch_ms` is the tick time in milliseconds since the Unix epoch, `bid`
+/// and `ask` are scaled by `point_value` (see `Price::as_f64`). The rows
+/// convert without copying into an `ndarray::Array2<f64>` with `Array2::from`
+/// or flatten into a buffer for other numeric libraries.
+/// ```
+/// use bi5::*;
+/// let epoch = chrono::NaiveDateTime::from_timestamp_opt(0, 0);
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), epoch).unwrap();
+/// let rows = to_rows(ticks.into_iter(), 1e5);
+/// assert_eq!(rows.len(), 10412);
+/// assert_eq!(rows[0], [1860002.0, 1.33117, 1.33153, 0.019999999552965164, 0.014999999664723873]);
+/// ```
+pub fn to_rows<I>(iter: I, point_value: f64) -> Vec<[f64; 5]>
+where I: IntoIterator<Item = (NaiveDateTime, Tick)> {
+    iter.into_iter().map(|(t, tick)| [
+        t.timestamp_millis() as f64,
+        tick.bid_price().as_f64(point_value),
+        tick.ask_price().as_f64(point_value),
+        tick.bidsize as f64,
+        tick.asksize as f64,
+    ]).collect()
+}
+
+/// Ticks at `(millisecs, bid, ask)` with sizes of 1, timed from the epoch
+#[cfg(test)]
+pub(crate) fn test_ticks(ticks: &[(u32, u32, u32)]) -> Vec<(NaiveDateTime, Tick)> {
+    ticks.iter().map(|&(millisecs, bid, ask)| {
+        let t = NaiveDateTime::from_timestamp_millis(millisecs as i64).unwrap();
+        (t, Tick { millisecs, ask, bid, asksize: 1.0, bidsize: 1.0 })
+    }).collect()
+}
+
+#[test]
+/// Test that size-only updates are dropped
+fn test_price_changes_only() {
+    let mut ticks = test_ticks(&[(0, 10, 12), (1, 10, 12), (2, 10, 13), (3, 10, 12), (4, 10, 12)]);
+    ticks[1].1.bidsize = 3.0;
+    let out: Vec<u32> = ticks.into_iter().price_changes_only().map(|(_, t)| t.millisecs).collect();
+    assert_eq!(out, vec![0, 2, 3]);
+}
+
+#[test]
+/// Test that price moves with unchanged sizes are dropped
+fn test_size_changes_only() {
+    let mut ticks = test_ticks(&[(0, 10, 12), (1, 11, 13), (2, 11, 13), (3, 12, 14), (4, 12, 14)]);
+    ticks[2].1.asksize = 2.0;
+    ticks[4].1.bidsize = f32::NAN;
+    let out: Vec<u32> = ticks.into_iter().size_changes_only().map(|(_, t)| t.millisecs).collect();
+    assert_eq!(out, vec![0, 2, 3, 4]);
+}
+
+#[test]
+/// Test that the last tick of each run is kept, also across a file boundary
+fn test_last_per_ms() {
+    let mut ticks = test_ticks(&[(0, 10, 12), (0, 11, 13), (1, 12, 14), (2, 13, 15), (2, 14, 16), (2, 15, 17), (3, 16, 18)]);
+    // the first tick of the next file, at the same time as the previous tick
+    ticks.push((ticks[6].0, Tick { millisecs: 0, ..ticks[6].1 }));
+    ticks[7].1.bid = 20;
+    let out: Vec<(u32, u32)> = ticks.into_iter().last_per_ms().map(|(_, t)| (t.millisecs, t.bid)).collect();
+    assert_eq!(out, vec![(0, 11), (1, 12), (2, 15), (0, 20)]);
+}
+
+#[test]
+/// Test that the window is half-open and the inner iterator isn't pulled past `end`
+fn test_clamp_to_range() {
+    let ticks = test_ticks(&[(0, 1, 2), (1, 1, 2), (2, 1, 2), (3, 1, 2), (4, 1, 2)]);
+    let t = |ms| NaiveDateTime::from_timestamp_millis(ms).unwrap();
+    let mut pulled = 0;
+    let iter = ticks.into_iter().inspect(|_| pulled += 1);
+    let out: Vec<u32> = iter.clamp_to_range(t(1), t(3)).map(|(_, t)| t.millisecs).collect();
+    assert_eq!(out, vec![1, 2]);
+    assert_eq!(pulled, 4);
+}
+
+#[test]
+/// Test signed differences, including a file boundary where millisecs restart
+fn test_deltas() {
+    let ticks = test_ticks(&[(100, 10, 12), (3_599_000, 9, 13), (5, 11, 12)]);
+    let deltas: Vec<TickDelta> = ticks.clone().into_iter().deltas().collect();
+    assert_eq!((deltas[0].dmillis, deltas[0].dbid, deltas[0].dask), (100, 10, 12));
+    assert_eq!((deltas[1].dmillis, deltas[1].dbid, deltas[1].dask), (3_598_900, -1, 1));
+    assert_eq!((deltas[2].dmillis, deltas[2].dbid, deltas[2].dask), (-3_598_995, 2, -1));
+    assert_eq!((deltas[1].dbidsize, deltas[1].dasksize), (0.0, 0.0));
+    let restored: Vec<Tick> = undelta(deltas).collect();
+    assert_eq!(restored, ticks.into_iter().map(|(_, t)| t).collect::<Vec<_>>());
+}
+
+#[test]
+/// Test that each tick yields its bid then its ask event
+fn test_split_sides() {
+    let ticks = test_ticks(&[(0, 10, 12), (1, 11, 13)]);
+    let mut events = ticks.into_iter().split_sides();
+    assert_eq!(events.size_hint(), (4, Some(4)));
+    let sides: Vec<(u32, Side, u32)> = events.by_ref().take(3).map(|(t, side, price, _)| (t.timestamp_millis() as u32, side, price)).collect();
+    assert_eq!(sides, vec![(0, Side::Bid, 10), (0, Side::Ask, 12), (1, Side::Bid, 11)]);
+    assert_eq!(events.size_hint(), (1, Some(1)));
+    assert_eq!(events.next().map(|(_, side, price, _)| (side, price)), Some((Side::Ask, 13)));
+    assert_eq!(events.next(), None);
+}
+
+#[test]
+/// Test the tick rule on the mid price, including a move of half a point
+fn test_with_tick_direction() {
+    let ticks = test_ticks(&[(0, 10, 12), (1, 10, 13), (2, 10, 12), (3, 9, 14), (4, 9, 14)]);
+    let directions: Vec<Direction> = ticks.into_iter().with_tick_direction().map(|(_, _, d)| d).collect();
+    assert_eq!(directions, vec![Direction::Zero, Direction::Up, Direction::Down, Direction::Up, Direction::Zero]);
+}
+
+#[test]
+/// Test signed deltas, the zero first tick and a file boundary where millisecs restart
+fn test_with_price_deltas() {
+    let ticks = test_ticks(&[(100, 10, 12), (3_599_000, 9, 13), (5, 11, 12), (6, 11, 12)]);
+    let deltas: Vec<(i64, i64)> = ticks.into_iter().with_price_deltas().map(|(_, _, dbid, dask)| (dbid, dask)).collect();
+    assert_eq!(deltas, vec![(0, 0), (-1, 1), (2, -1), (0, 0)]);
+}
+
+#[test]
+/// Test that only crossed ticks are swapped and counted, sizes with their prices
+fn test_normalize_crossed() {
+    let mut ticks = test_ticks(&[(0, 10, 12), (1, 13, 12), (2, 12, 12)]);
+    ticks[1].1.bidsize = 2.0;
+    let mut normalized = ticks.into_iter().normalize_crossed();
+    let out: Vec<Tick> = normalized.by_ref().map(|(_, t)| t).collect();
+    assert_eq!(normalized.swapped(), 1);
+    assert_eq!(out.iter().map(|t| (t.bid, t.ask)).collect::<Vec<_>>(), vec![(10, 12), (12, 13), (12, 12)]);
+    assert_eq!((out[1].bidsize, out[1].asksize), (1.0, 2.0));
+}
+
+#[test]
+/// Test that the volume includes the current tick and keeps accumulating
+fn test_with_cumulative_volume() {
+    let mut ticks = test_ticks(&[(0, 10, 12), (3_599_000, 10, 12), (5, 10, 12)]);
+    ticks[1].1.asksize = 0.5;
+    let volumes: Vec<f64> = ticks.into_iter().with_cumulative_volume().map(|(_, _, v)| v).collect();
+    assert_eq!(volumes, vec![2.0, 3.5, 5.5]);
+}
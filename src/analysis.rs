@@ -0,0 +1,508 @@
+//! Analysis helpers over streams of `(NaiveDateTime, Tick)`.
+
+use std::{collections::{BTreeMap, HashMap, VecDeque}, iter::Peekable};
+use chrono::{NaiveDateTime, Duration};
+use crate::Tick;
+
+/// Iterator returned by `minmax_decimate`
+pub struct MinMaxDecimate<I> {
+    iter: I,
+    bucket_ms: i64,
+    /// index of the next tick pulled from `iter`
+    index: usize,
+    /// bucket currently being collected
+    current: Option<Bucket>,
+    /// ticks of a finished bucket that still have to be emitted
+    out: Vec<(NaiveDateTime, Tick)>,
+}
+
+struct Bucket {
+    key: i64,
+    min: (usize, NaiveDateTime, Tick),
+    max: (usize, NaiveDateTime, Tick),
+}
+
+/// Min-max decimation for plotting.
+///
+/// Within each time bucket only the ticks with the lowest and the highest bid
+/// are kept (in their original order), so spikes survive the decimation.
+/// Buckets are aligned to multiples of `bucket` since the Unix epoch.
+/// ## Arguments
+/// - `iter` - Stream of ticks, e.g. `Bi5Iter`
+/// - `bucket` - Bucket width, must be positive
+///
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let n = minmax_decimate(ticks.into_iter(), chrono::Duration::minutes(1)).count();
+/// assert!(n <= 2 * 60);
+/// ```
+pub fn minmax_decimate<I>(iter: I, bucket: Duration) -> MinMaxDecimate<I::IntoIter>
+where I: IntoIterator<Item = (NaiveDateTime, Tick)>
+{
+    assert!(bucket > Duration::zero(), "bucket must be positive");
+    MinMaxDecimate {
+        iter: iter.into_iter(),
+        bucket_ms: bucket.num_milliseconds().max(1),
+        index: 0,
+        current: None,
+        out: Vec::with_capacity(2),
+    }
+}
+
+impl<I> MinMaxDecimate<I> {
+    fn flush(&mut self, bucket: Bucket) {
+        let (min, max) = (bucket.min, bucket.max);
+        if min.0 == max.0 {
+            self.out.push((min.1, min.2));
+        } else if min.0 < max.0 {
+            self.out.push((max.1, max.2));
+            self.out.push((min.1, min.2));
+        } else {
+            self.out.push((min.1, min.2));
+            self.out.push((max.1, max.2));
+        }
+    }
+}
+
+impl<I> Iterator for MinMaxDecimate<I>
+where I: Iterator<Item = (NaiveDateTime, Tick)>
+{
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.out.pop() {
+                return Some(item);
+            }
+            match self.iter.next() {
+                Some((t, tick)) => {
+                    let key = t.timestamp_millis().div_euclid(self.bucket_ms);
+                    let index = self.index;
+                    self.index += 1;
+                    match &mut self.current {
+                        Some(bucket) if bucket.key == key => {
+                            if tick.bid < bucket.min.2.bid { bucket.min = (index, t, tick); }
+                            if tick.bid > bucket.max.2.bid { bucket.max = (index, t, tick); }
+                        }
+                        _ => {
+                            let new = Bucket { key, min: (index, t, tick), max: (index, t, tick) };
+                            if let Some(done) = self.current.replace(new) {
+                                self.flush(done);
+                            }
+                        }
+                    }
+                }
+                None => {
+                    let done = self.current.take()?;
+                    self.flush(done);
+                }
+            }
+        }
+    }
+}
+
+/// Histogram of the spread `ask - bid` in points, in one pass over `iter`
+///
+/// Keys are the lower edges of buckets of width `bucket_points`, values the
+/// number of ticks in the bucket. Crossed quotes count as spread 0.
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let hist = spread_histogram(ticks.into_iter(), 10);
+/// assert_eq!(hist.values().sum::<u64>(), 10412);
+/// ```
+pub fn spread_histogram<I>(iter: I, bucket_points: u32) -> BTreeMap<u32, u64>
+where I: IntoIterator<Item = (NaiveDateTime, Tick)>
+{
+    assert!(bucket_points > 0, "bucket_points must be positive");
+    let mut hist = BTreeMap::new();
+    for (_, tick) in iter {
+        let spread = tick.ask.saturating_sub(tick.bid);
+        *hist.entry(spread - spread % bucket_points).or_insert(0) += 1;
+    }
+    hist
+}
+
+/// The `p`-th percentile (`0.0..=100.0`) of the spread in points, using the
+/// nearest-rank method, or `None` for an empty stream
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let median = spread_percentile(ticks.into_iter(), 50.0);
+/// assert!(median.is_some());
+/// ```
+pub fn spread_percentile<I>(iter: I, p: f64) -> Option<u32>
+where I: IntoIterator<Item = (NaiveDateTime, Tick)>
+{
+    assert!((0.0..=100.0).contains(&p), "p must be in 0..=100");
+    let hist = spread_histogram(iter, 1);
+    let n: u64 = hist.values().sum();
+    let rank = ((p / 100.0 * n as f64).ceil() as u64).max(1);
+    let mut seen = 0;
+    hist.into_iter().find(|&(_, count)| { seen += count; seen >= rank }).map(|(spread, _)| spread)
+}
+
+/// Struct-of-arrays price series, see `to_price_series`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceSeries {
+    /// Tick times
+    pub times: Vec<NaiveDateTime>,
+    /// Bid prices scaled by the point value
+    pub bid: Vec<f64>,
+    /// Ask prices scaled by the point value
+    pub ask: Vec<f64>,
+}
+
+/// Collect a tick stream into a `PriceSeries` in one pass, prices are
+/// scaled by `point_value` (see `Price::as_f64`)
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let series = to_price_series(ticks.into_iter(), 1e5);
+/// assert_eq!((series.times.len(), series.bid[0], series.ask[0]), (10412, 1.33117, 1.33153));
+/// ```
+pub fn to_price_series<I>(iter: I, point_value: f64) -> PriceSeries
+where I: IntoIterator<Item = (NaiveDateTime, Tick)>
+{
+    let iter = iter.into_iter();
+    let n = iter.size_hint().0;
+    let mut series = PriceSeries {
+        times: Vec::with_capacity(n),
+        bid: Vec::with_capacity(n),
+        ask: Vec::with_capacity(n),
+    };
+    for (t, tick) in iter {
+        series.times.push(t);
+        series.bid.push(tick.bid_price().as_f64(point_value));
+        series.ask.push(tick.ask_price().as_f64(point_value));
+    }
+    series
+}
+
+/// Number of ticks per interval of a time-sorted stream
+///
+/// Intervals are aligned to multiples of `interval` since the Unix epoch and
+/// keyed by their start. With `fill_empty` intervals without ticks between
+/// the first and the last one are included with a count of 0.
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let counts = count_by_interval(ticks.into_iter(), chrono::Duration::minutes(1), true);
+/// assert_eq!(counts.len(), 29);
+/// assert_eq!(counts.iter().map(|(_, n)| n).sum::<u64>(), 10412);
+/// ```
+pub fn count_by_interval<I>(iter: I, interval: Duration, fill_empty: bool) -> Vec<(NaiveDateTime, u64)>
+where I: IntoIterator<Item = (NaiveDateTime, Tick)>
+{
+    assert!(interval > Duration::zero(), "interval must be positive");
+    let interval_ms = interval.num_milliseconds().max(1);
+    let mut counts: Vec<(i64, u64)> = Vec::new();
+    for (t, _) in iter {
+        let key = t.timestamp_millis().div_euclid(interval_ms);
+        match counts.last_mut() {
+            Some((last, n)) if *last == key => *n += 1,
+            Some((last, _)) if fill_empty && *last < key => {
+                let last = *last;
+                counts.extend((last + 1..key).map(|k| (k, 0)));
+                counts.push((key, 1));
+            }
+            _ => counts.push((key, 1)),
+        }
+    }
+    counts.into_iter()
+        .map(|(key, n)| (NaiveDateTime::from_timestamp_millis(key * interval_ms).unwrap(), n))
+        .collect()
+}
+
+/// Iterator returned by `align`
+pub struct Align<I: Iterator> {
+    sources: Vec<(String, Peekable<I>)>,
+    interval_ms: i64,
+    /// next grid point in Unix milliseconds
+    next: i64,
+    /// latest tick time seen in any source
+    latest: i64,
+    last: HashMap<String, Tick>,
+}
+
+/// Samples several time-sorted streams, e.g. of different instruments, on a
+/// common clock
+///
+/// Grid points are multiples of `interval` since the Unix epoch, from the
+/// first at or after the earliest tick to the last at or before the latest
+/// tick of all sources. Each grid point carries the last tick at or before it
+/// of each source, keyed by the source's name. During the warm-up sources
+/// without a tick yet are missing from the map.
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let eurusd = ticks.clone().into_iter();
+/// let gbpusd = ticks.into_iter();
+/// let sources = vec![("EURUSD".to_string(), eurusd), ("GBPUSD".to_string(), gbpusd)];
+/// let (t, ticks) = align(sources, chrono::Duration::minutes(1)).next().unwrap();
+/// assert_eq!(t.to_string(), "0000-01-01 00:32:00");
+/// assert_eq!(ticks["EURUSD"], ticks["GBPUSD"]);
+/// ```
+pub fn align<I>(sources: Vec<(String, I)>, interval: Duration) -> Align<I::IntoIter>
+where I: IntoIterator<Item = (NaiveDateTime, Tick)>
+{
+    assert!(interval > Duration::zero(), "interval must be positive");
+    let interval_ms = interval.num_milliseconds().max(1);
+    let mut sources: Vec<(String, Peekable<I::IntoIter>)> = sources.into_iter()
+        .map(|(name, iter)| (name, iter.into_iter().peekable()))
+        .collect();
+    let first = sources.iter_mut()
+        .filter_map(|(_, iter)| iter.peek().map(|(t, _)| t.timestamp_millis()))
+        .min();
+    // without ticks the first grid point lies beyond `latest` and nothing is emitted
+    let next = first.map_or(0, |t| -(-t).div_euclid(interval_ms) * interval_ms);
+    Align { sources, interval_ms, next, latest: i64::MIN, last: HashMap::new() }
+}
+
+impl<I> Iterator for Align<I>
+where I: Iterator<Item = (NaiveDateTime, Tick)>
+{
+    type Item = (NaiveDateTime, HashMap<String, Tick>);
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut pending = false;
+        for (name, iter) in self.sources.iter_mut() {
+            while let Some((t, tick)) = iter.next_if(|(t, _)| t.timestamp_millis() <= self.next) {
+                self.latest = self.latest.max(t.timestamp_millis());
+                self.last.insert(name.clone(), tick);
+            }
+            pending |= iter.peek().is_some();
+        }
+        if !pending && self.next > self.latest {
+            return None;
+        }
+        // from_timestamp_millis mishandles whole seconds before the epoch
+        let t = NaiveDateTime::from_timestamp_opt(0, 0)? + Duration::milliseconds(self.next);
+        self.next += self.interval_ms;
+        Some((t, self.last.clone()))
+    }
+}
+
+/// Mid prices `(bid + ask) / 2` of a time-sorted stream sampled on a uniform
+/// grid, e.g. for FFT and other signal processing
+///
+/// Grid points are multiples of `interval` since the Unix epoch, from the
+/// first at or after the first tick to the last at or before the last tick,
+/// like `align`. Each value is the mid of the last tick at or before its grid
+/// point scaled by `point_value` (see `Price::as_f64`), so gaps are forward
+/// filled. Returns the time of the first value and the values; without ticks
+/// the start is the Unix epoch and the values are empty.
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let (start, mids) = resampled_mid_series(ticks.into_iter(), chrono::Duration::seconds(1), 1e5);
+/// assert_eq!((start.to_string().as_str(), mids.len()), ("0000-01-01 00:31:01", 1739));
+/// ```
+pub fn resampled_mid_series<I>(iter: I, interval: Duration, point_value: f64) -> (NaiveDateTime, Vec<f64>)
+where I: IntoIterator<Item = (NaiveDateTime, Tick)>
+{
+    assert!(interval > Duration::zero(), "interval must be positive");
+    let interval_ms = interval.num_milliseconds().max(1);
+    let mut mids = Vec::new();
+    // first grid point, next grid point, last tick time and mid
+    let mut grid: Option<(i64, i64, i64, f64)> = None;
+    for (t, tick) in iter {
+        let t = t.timestamp_millis();
+        let mid = (tick.bid_price().as_f64(point_value) + tick.ask_price().as_f64(point_value)) / 2.0;
+        let (_, next, latest, last) = grid.get_or_insert_with(|| {
+            let first = -(-t).div_euclid(interval_ms) * interval_ms;
+            (first, first, t, mid)
+        });
+        while *next < t {
+            mids.push(*last);
+            *next += interval_ms;
+        }
+        (*latest, *last) = (t, mid);
+    }
+    let Some((first, next, latest, last)) = grid else {
+        return (NaiveDateTime::from_timestamp_opt(0, 0).unwrap(), mids);
+    };
+    if next == latest {
+        mids.push(last);
+    }
+    // from_timestamp_millis mishandles whole seconds before the epoch
+    (NaiveDateTime::from_timestamp_opt(0, 0).unwrap() + Duration::milliseconds(first), mids)
+}
+
+/// Iterator returned by `rolling`
+pub struct Rolling<I, F> {
+    iter: I,
+    window: Duration,
+    f: F,
+    emit_partial: bool,
+    first: Option<NaiveDateTime>,
+    /// times and ticks currently in the window
+    times: VecDeque<NaiveDateTime>,
+    ticks: VecDeque<Tick>,
+}
+
+/// Rolling statistic over a sliding time window
+///
+/// For each tick at time `t` yields `(t, f(window))`, where the window holds
+/// the ticks in `(t - window, t]`. While the stream covers less than
+/// `window` (the warm-up) values are only emitted if `emit_partial` is set.
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let max_spread = |ticks: &[Tick]| ticks.iter().map(|t| t.ask as f64 - t.bid as f64).fold(0.0, f64::max);
+/// let (_, spread) = rolling(ticks.into_iter(), chrono::Duration::seconds(10), max_spread, false).next().unwrap();
+/// assert!(spread > 0.0);
+/// ```
+pub fn rolling<I, F>(iter: I, window: Duration, f: F, emit_partial: bool) -> Rolling<I::IntoIter, F>
+where I: IntoIterator<Item = (NaiveDateTime, Tick)>, F: FnMut(&[Tick]) -> f64
+{
+    assert!(window > Duration::zero(), "window must be positive");
+    Rolling {
+        iter: iter.into_iter(),
+        window,
+        f,
+        emit_partial,
+        first: None,
+        times: VecDeque::new(),
+        ticks: VecDeque::new(),
+    }
+}
+
+impl<I, F> Iterator for Rolling<I, F>
+where I: Iterator<Item = (NaiveDateTime, Tick)>, F: FnMut(&[Tick]) -> f64
+{
+    type Item = (NaiveDateTime, f64);
+    fn next(&mut self) -> Option<Self::Item> {
+        for (t, tick) in self.iter.by_ref() {
+            let first = *self.first.get_or_insert(t);
+            self.times.push_back(t);
+            self.ticks.push_back(tick);
+            while self.times.front().is_some_and(|&front| front <= t - self.window) {
+                self.times.pop_front();
+                self.ticks.pop_front();
+            }
+            if self.emit_partial || t - first >= self.window {
+                return Some((t, (self.f)(self.ticks.make_contiguous())));
+            }
+        }
+        None
+    }
+}
+
+/// Indices of the ticks of a file whose `millisecs` equal those of the
+/// previous tick
+///
+/// Several ticks in the same millisecond are legitimate. With
+/// `identical_only` only ticks equal to the previous one in all fields are
+/// reported, these are likely decode or re-download artifacts.
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = from_compressed_bytes(include_bytes!("../test/test.bi5")).unwrap();
+/// assert!(duplicate_timestamps(&ticks, true).is_empty());
+/// ```
+pub fn duplicate_timestamps(ticks: &[Tick], identical_only: bool) -> Vec<usize> {
+    ticks.windows(2).enumerate()
+        .filter(|(_, w)| w[0].millisecs == w[1].millisecs && (!identical_only || w[0] == w[1]))
+        .map(|(i, _)| i + 1)
+        .collect()
+}
+
+#[cfg(test)]
+use crate::adapters::test_ticks;
+
+#[test]
+/// Test that the extremes of each bucket are kept in order
+fn test_minmax_decimate() {
+    let ticks = test_ticks(&[
+        (0, 10, 12), (100, 15, 16), (200, 5, 6), (300, 9, 10),
+        (1000, 7, 8),
+        (2000, 3, 4), (2500, 3, 4),
+    ]);
+    let out: Vec<u32> = minmax_decimate(ticks, Duration::seconds(1)).map(|(_, t)| t.millisecs).collect();
+    assert_eq!(out, vec![100, 200, 1000, 2000]);
+    assert_eq!(minmax_decimate(vec![], Duration::seconds(1)).count(), 0);
+}
+
+#[test]
+/// Test bucketing and nearest-rank percentiles
+fn test_spread_histogram() {
+    let ticks = test_ticks(&[(0, 10, 12), (1, 10, 13), (2, 10, 21), (3, 10, 9)]);
+    let hist = spread_histogram(ticks.clone(), 5);
+    assert_eq!(hist.into_iter().collect::<Vec<_>>(), vec![(0, 3), (10, 1)]);
+    assert_eq!(spread_percentile(ticks.clone(), 0.0), Some(0));
+    assert_eq!(spread_percentile(ticks.clone(), 50.0), Some(2));
+    assert_eq!(spread_percentile(ticks, 100.0), Some(11));
+    assert_eq!(spread_percentile(vec![], 50.0), None);
+}
+
+#[test]
+/// Test the window contents with and without the warm-up
+fn test_rolling() {
+    let ticks = test_ticks(&[(0, 1, 2), (400, 2, 3), (1000, 3, 4), (1500, 4, 5)]);
+    let sum = |ticks: &[Tick]| ticks.iter().map(|t| t.bid as f64).sum();
+    let out: Vec<(i64, f64)> = rolling(ticks.clone(), Duration::seconds(1), sum, true)
+        .map(|(t, x)| (t.timestamp_millis(), x)).collect();
+    assert_eq!(out, vec![(0, 1.0), (400, 3.0), (1000, 5.0), (1500, 7.0)]);
+    let out: Vec<(i64, f64)> = rolling(ticks, Duration::seconds(1), sum, false)
+        .map(|(t, x)| (t.timestamp_millis(), x)).collect();
+    assert_eq!(out, vec![(1000, 5.0), (1500, 7.0)]);
+}
+
+#[test]
+/// Test counting with and without empty intervals
+fn test_count_by_interval() {
+    let ticks = test_ticks(&[(0, 1, 2), (400, 1, 2), (2500, 1, 2)]);
+    let count = |fill| -> Vec<(i64, u64)> {
+        count_by_interval(ticks.clone(), Duration::seconds(1), fill)
+            .into_iter().map(|(t, n)| (t.timestamp_millis(), n)).collect()
+    };
+    assert_eq!(count(false), vec![(0, 2), (2000, 1)]);
+    assert_eq!(count(true), vec![(0, 2), (1000, 0), (2000, 1)]);
+}
+
+#[test]
+/// Test the warm-up and that the last tick of each source is carried forward
+fn test_align() {
+    let a = test_ticks(&[(500, 1, 2), (1200, 2, 3), (4000, 3, 4)]);
+    let b = test_ticks(&[(2100, 10, 11)]);
+    let out: Vec<(i64, Option<u32>, Option<u32>)> = align(vec![("a".to_string(), a), ("b".to_string(), b)], Duration::seconds(1))
+        .map(|(t, ticks)| (t.timestamp_millis(), ticks.get("a").map(|t| t.bid), ticks.get("b").map(|t| t.bid)))
+        .collect();
+    assert_eq!(out, vec![
+        (1000, Some(1), None),
+        (2000, Some(2), None),
+        (3000, Some(2), Some(10)),
+        (4000, Some(3), Some(10)),
+    ]);
+    assert_eq!(align(Vec::<(String, Vec<(NaiveDateTime, Tick)>)>::new(), Duration::seconds(1)).count(), 0);
+}
+
+#[test]
+/// Test the forward fill across a gap, a tick on a grid point and the empty stream
+fn test_resampled_mid_series() {
+    let ticks = test_ticks(&[(500, 1, 3), (1200, 3, 5), (4000, 7, 9), (4100, 9, 11)]);
+    let (start, mids) = resampled_mid_series(ticks, Duration::seconds(1), 1.0);
+    assert_eq!(start.timestamp_millis(), 1000);
+    assert_eq!(mids, vec![2.0, 4.0, 4.0, 8.0]);
+    let (start, mids) = resampled_mid_series(test_ticks(&[(2000, 1, 3)]), Duration::seconds(1), 1.0);
+    assert_eq!((start.timestamp_millis(), mids), (2000, vec![2.0]));
+    assert!(resampled_mid_series(Vec::new(), Duration::seconds(1), 1.0).1.is_empty());
+}
+
+#[test]
+/// Test that same-millisecond ticks are told apart from exact duplicates
+fn test_duplicate_timestamps() {
+    let ticks: Vec<Tick> = test_ticks(&[(0, 1, 2), (5, 1, 2), (5, 1, 3), (5, 1, 3), (6, 1, 3)])
+        .into_iter().map(|(_, tick)| tick).collect();
+    assert_eq!(duplicate_timestamps(&ticks, false), vec![2, 3]);
+    assert_eq!(duplicate_timestamps(&ticks, true), vec![3]);
+    assert!(duplicate_timestamps(&[], false).is_empty());
+}
@@ -0,0 +1,60 @@
+//! Health check of all files of a directory, see `Bi5::audit`.
+
+use std::path::PathBuf;
+use chrono::NaiveDateTime;
+
+/// Result of checking one file, see `AuditReport`
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct FileAudit {
+    pub path: PathBuf,
+    /// File start from the path
+    pub date_time: NaiveDateTime,
+    /// Why the file couldn't be decoded, the other fields are empty then
+    pub error: Option<String>,
+    pub ticks: usize,
+    /// `millisecs` never decrease, beyond `Bi5Builder::tolerance`
+    pub monotonic: bool,
+    /// Ticks beyond the hour of the file, by more than the tolerance
+    pub out_of_hour: usize,
+    /// Ticks with `bid > ask`
+    pub crossed: usize,
+    /// Ticks with `bid == ask`
+    pub locked: usize,
+    /// Earliest and latest tick time, `None` without ticks
+    pub time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+}
+
+impl FileAudit {
+    /// True if the file decoded and its times are in order and within the
+    /// hour. Crossed and locked quotes occur in real markets, so they don't
+    /// make a problem.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none() && self.monotonic && self.out_of_hour == 0
+    }
+}
+
+/// Per-file results of `Bi5::audit` in chronological order
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct AuditReport {
+    pub files: Vec<FileAudit>,
+}
+
+impl AuditReport {
+    /// Total number of ticks
+    pub fn ticks(&self) -> usize {
+        self.files.iter().map(|file| file.ticks).sum()
+    }
+
+    /// The files that failed to decode or whose times are off
+    pub fn problems(&self) -> impl Iterator<Item = &FileAudit> {
+        self.files.iter().filter(|file| !file.is_ok())
+    }
+
+    /// Earliest and latest tick time of all files
+    pub fn time_range(&self) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        self.files.iter().filter_map(|file| file.time_range)
+            .reduce(|(min, max), (first, last)| (min.min(first), max.max(last)))
+    }
+}
@@ -0,0 +1,216 @@
+//! Information-driven bars over streams of `(NaiveDateTime, Tick)`, see `volume_bars`.
+
+use chrono::{Duration, NaiveDateTime};
+use crate::{Direction, Tick, TickIteratorExt};
+
+/// OHLC bar of the mid price `(bid + ask) / 2` in points
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct Bar {
+    /// Time of the first tick
+    pub start: NaiveDateTime,
+    /// Time of the last tick
+    pub end: NaiveDateTime,
+    /// Mid of the first tick
+    pub open: f64,
+    /// Highest mid
+    pub high: f64,
+    /// Lowest mid
+    pub low: f64,
+    /// Mid of the last tick
+    pub close: f64,
+    /// Sum of `asksize + bidsize`, accumulated in `f64`
+    pub volume: f64,
+    /// Number of ticks
+    pub ticks: usize,
+}
+
+impl Bar {
+    /// A bar of the single tick
+    fn new(t: NaiveDateTime, tick: &Tick) -> Self {
+        let mid = mid(tick);
+        Bar { start: t, end: t, open: mid, high: mid, low: mid, close: mid, volume: volume(tick), ticks: 1 }
+    }
+
+    /// Extend the bar by the tick
+    fn push(&mut self, t: NaiveDateTime, tick: &Tick) {
+        let mid = mid(tick);
+        self.end = t;
+        self.high = self.high.max(mid);
+        self.low = self.low.min(mid);
+        self.close = mid;
+        self.volume += volume(tick);
+        self.ticks += 1;
+    }
+}
+
+fn mid(tick: &Tick) -> f64 {
+    (tick.bid as f64 + tick.ask as f64) / 2.0
+}
+
+fn volume(tick: &Tick) -> f64 {
+    tick.asksize as f64 + tick.bidsize as f64
+}
+
+/// Aggregate a time-sorted stream into bars of `interval`
+///
+/// Intervals are aligned to multiples of `interval` since the Unix epoch
+/// like `count_by_interval`, intervals without ticks have no bar. Unlike the
+/// other bars the last one is kept, it ends with the stream.
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let bars = time_bars(ticks.into_iter(), chrono::Duration::minutes(1));
+/// assert_eq!(bars.len(), 29);
+/// assert_eq!(bars.iter().map(|bar| bar.ticks).sum::<usize>(), 10412);
+/// ```
+pub fn time_bars<I>(iter: I, interval: Duration) -> Vec<Bar>
+where I: IntoIterator<Item = (NaiveDateTime, Tick)>
+{
+    assert!(interval > Duration::zero(), "interval must be positive");
+    let interval_ms = interval.num_milliseconds().max(1);
+    let mut bars: Vec<Bar> = Vec::new();
+    let mut key = None;
+    for (t, tick) in iter {
+        let k = t.timestamp_millis().div_euclid(interval_ms);
+        match bars.last_mut() {
+            Some(bar) if key == Some(k) => bar.push(t, &tick),
+            _ => {
+                bars.push(Bar::new(t, &tick));
+                key = Some(k);
+            }
+        }
+    }
+    bars
+}
+
+/// Aggregate ticks into bars of `volume_per_bar` (`asksize + bidsize`)
+///
+/// A bar is closed by the tick with which its volume reaches
+/// `volume_per_bar`, the excess isn't carried over. So a single tick with
+/// more volume closes a bar right away, on its own if the bar was empty. The
+/// ticks after the last full bar are dropped.
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let bars = volume_bars(ticks.into_iter(), 1.0);
+/// assert!(!bars.is_empty());
+/// assert!(bars.iter().all(|bar| bar.volume >= 1.0 && bar.low <= bar.high));
+/// ```
+pub fn volume_bars<I>(iter: I, volume_per_bar: f64) -> Vec<Bar>
+where I: IntoIterator<Item = (NaiveDateTime, Tick)>
+{
+    assert!(volume_per_bar > 0.0, "volume_per_bar must be positive");
+    let mut bars = Vec::new();
+    let mut bar: Option<Bar> = None;
+    for (t, tick) in iter {
+        match bar.as_mut() {
+            Some(bar) => bar.push(t, &tick),
+            None => bar = Some(Bar::new(t, &tick)),
+        }
+        if bar.is_some_and(|bar| bar.volume >= volume_per_bar) {
+            bars.extend(bar.take());
+        }
+    }
+    bars
+}
+
+/// Aggregate ticks into tick imbalance bars
+///
+/// Each tick contributes its volume `asksize + bidsize` signed by the tick
+/// rule (see `TickIteratorExt::with_tick_direction`), a zero-tick keeps the
+/// sign of the previous move. A bar is closed by the tick with which the
+/// absolute sum reaches `threshold`, then the sum restarts at zero. The
+/// ticks after the last full bar are dropped.
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+/// let bars = imbalance_bars(ticks.into_iter(), 1.0);
+/// assert!(!bars.is_empty());
+/// ```
+pub fn imbalance_bars<I>(iter: I, threshold: f64) -> Vec<Bar>
+where I: IntoIterator<Item = (NaiveDateTime, Tick)>
+{
+    assert!(threshold > 0.0, "threshold must be positive");
+    let mut bars = Vec::new();
+    let mut bar: Option<Bar> = None;
+    let (mut sign, mut imbalance) = (0.0, 0.0);
+    for (t, tick, direction) in iter.into_iter().with_tick_direction() {
+        match direction {
+            Direction::Up => sign = 1.0,
+            Direction::Down => sign = -1.0,
+            Direction::Zero => {}
+        }
+        imbalance += sign * volume(&tick);
+        match bar.as_mut() {
+            Some(bar) => bar.push(t, &tick),
+            None => bar = Some(Bar::new(t, &tick)),
+        }
+        if f64::abs(imbalance) >= threshold {
+            bars.extend(bar.take());
+            imbalance = 0.0;
+        }
+    }
+    bars
+}
+
+#[cfg(test)]
+use crate::adapters::test_ticks;
+
+#[test]
+/// Test the interval alignment, skipped empty intervals and the kept last bar
+fn test_time_bars() {
+    let ticks = test_ticks(&[(500, 10, 12), (999, 14, 16), (1000, 8, 10), (3500, 10, 11)]);
+    let bars = time_bars(ticks, Duration::seconds(1));
+    assert_eq!(bars.iter().map(|bar| bar.ticks).collect::<Vec<_>>(), vec![2, 1, 1]);
+    assert_eq!((bars[0].open, bars[0].high, bars[0].close), (11.0, 15.0, 15.0));
+    assert_eq!(bars[2].start.timestamp_millis(), 3500);
+    assert!(time_bars(Vec::new(), Duration::seconds(1)).is_empty());
+}
+
+#[test]
+/// Test bar closing, OHLC of the mid, a tick above the threshold and the dropped tail
+fn test_volume_bars() {
+    let mut ticks = test_ticks(&[(0, 10, 12), (1, 14, 16), (2, 8, 10), (3, 10, 11), (4, 10, 12)]);
+    ticks[2].1.asksize = 0.5;
+    ticks[3].1.asksize = 4.0;
+    let bars = volume_bars(ticks, 4.5);
+    assert_eq!(bars.len(), 2);
+    assert_eq!((bars[0].open, bars[0].high, bars[0].low, bars[0].close), (11.0, 15.0, 9.0, 9.0));
+    assert_eq!((bars[0].volume, bars[0].ticks), (5.5, 3));
+    assert_eq!((bars[1].open, bars[1].volume, bars[1].ticks), (10.5, 5.0, 1));
+    assert_eq!(bars[1].start, bars[1].end);
+}
+
+#[test]
+/// Test that zero-ticks keep the sign and opposite flow delays the bar
+fn test_imbalance_bars() {
+    let ticks = test_ticks(&[
+        (0, 10, 12), (1, 11, 13), (2, 11, 13),
+        (3, 10, 12), (4, 11, 13), (5, 12, 14), (6, 13, 15),
+        (7, 12, 14),
+    ]);
+    // flow: 0, +2, +2 (zero-tick), | -2, +2, +2, +2, | -2
+    let bars = imbalance_bars(ticks, 4.0);
+    assert_eq!(bars.iter().map(|bar| bar.ticks).collect::<Vec<_>>(), vec![3, 4]);
+    assert_eq!((bars[1].open, bars[1].close), (11.0, 14.0));
+}
+
+#[test]
+/// Test that volumes of a long stream of small sizes don't drift like an `f32` sum
+fn test_volume_precision() {
+    let n = 1_000_000;
+    let mut ticks = test_ticks(&(0..n).map(|i| (i % 3_600_000, 10, 12)).collect::<Vec<_>>());
+    for (_, tick) in &mut ticks { (tick.asksize, tick.bidsize) = (0.1, 0.0); }
+    let ticks = || ticks.iter().copied();
+    let f32_sum: f32 = ticks().map(|(_, tick)| tick.asksize + tick.bidsize).sum();
+    assert!((f32_sum as f64 - 100_000.0).abs() > 100.0, "{}", f32_sum);
+    let (_, _, cumulative) = ticks().with_cumulative_volume().last().unwrap();
+    assert!((cumulative - 100_000.0).abs() < 0.01, "{}", cumulative);
+    let bars = volume_bars(ticks(), 50_000.0);
+    assert_eq!(bars.iter().map(|bar| bar.ticks).collect::<Vec<_>>(), vec![500_000, 500_000]);
+    assert!((bars[0].volume - 50_000.0).abs() < 0.01, "{}", bars[0].volume);
+}
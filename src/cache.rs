@@ -0,0 +1,95 @@
+//! In-memory cache of decompressed files, see `DecodeCache`.
+
+use std::{
+    collections::VecDeque,
+    fs::Metadata,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// A file version: path, modification time and length
+type Key = (PathBuf, SystemTime, u64);
+
+/// Shared LRU cache of decompressed buffers, see `Bi5::with_cache`
+///
+/// Entries are keyed by path, modification time and length, so a file that
+/// is rewritten is decompressed again. Clones share the cache, e.g. between
+/// all `Bi5`s of an interactive tool. At most `capacity` buffers are kept,
+/// the least recently used one is evicted first.
+/// ```
+/// use bi5::*;
+/// let cache = DecodeCache::new(16);
+/// let bi5 = Bi5::new("test/test.bi5", None).with_cache(cache.clone());
+/// assert_eq!(bi5.iter().unwrap().count(), 10412);
+/// // served from the cache
+/// assert_eq!(bi5.iter().unwrap().count(), 10412);
+/// assert_eq!(cache.len(), 1);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DecodeCache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    capacity: usize,
+    /// most recently used last
+    entries: VecDeque<(Key, Vec<u8>)>,
+}
+
+impl DecodeCache {
+    /// Cache of at most `capacity` buffers
+    pub fn new(capacity: usize) -> Self {
+        DecodeCache { inner: Arc::new(Mutex::new(Inner { capacity, entries: VecDeque::new() })) }
+    }
+
+    /// Number of cached buffers
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// True if nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop all buffers
+    pub fn clear(&self) {
+        self.lock().entries.clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        // a panic while holding the lock can't leave the entries inconsistent
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Key of the file with `metadata`, `None` for files that can't be
+    /// cached, e.g. FIFOs
+    pub(crate) fn key(path: &Path, metadata: &Metadata) -> Option<Key> {
+        if !metadata.is_file() {
+            return None;
+        }
+        Some((path.to_path_buf(), metadata.modified().ok()?, metadata.len()))
+    }
+
+    /// A copy of the cached buffer, marked as most recently used
+    pub(crate) fn get(&self, key: &Key) -> Option<Vec<u8>> {
+        let mut inner = self.lock();
+        let i = inner.entries.iter().position(|(k, _)| k == key)?;
+        let entry = inner.entries.remove(i)?;
+        let buf = entry.1.clone();
+        inner.entries.push_back(entry);
+        Some(buf)
+    }
+
+    /// Add a buffer, evicting the least recently used ones beyond the capacity
+    pub(crate) fn insert(&self, key: Key, buf: Vec<u8>) {
+        let mut inner = self.lock();
+        inner.entries.retain(|(k, _)| *k != key);
+        inner.entries.push_back((key, buf));
+        while inner.entries.len() > inner.capacity {
+            inner.entries.pop_front();
+        }
+    }
+}
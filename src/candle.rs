@@ -0,0 +1,226 @@
+//! Dukascopy candle files.
+//!
+//! Besides ticks Dukascopy also distributes minute, hour and day candles in
+//! LZMA compressed files. Each record is 24 bytes: the offset in seconds from
+//! the file start, open, close, low, high (raw `u32` prices) and the volume.
+
+use std::{
+    io::Cursor,
+    mem::size_of,
+    path::Path,
+};
+use binread::BinRead;
+use chrono::{NaiveDateTime, Duration};
+use anyhow::{anyhow, Error};
+use crate::{Side, Tick};
+#[cfg(any(feature = "fs", test))]
+use crate::decode::decompress;
+
+/// `Candle` is the record of a Dukascopy candle file.
+#[derive(BinRead, Debug, Clone, Copy, PartialEq)]
+pub struct Candle {
+    /// Seconds since file start
+    #[br(big)]
+    pub secs: u32,
+    /// Open price
+    #[br(big)]
+    pub open: u32,
+    /// Close price
+    #[br(big)]
+    pub close: u32,
+    /// Low price
+    #[br(big)]
+    pub low: u32,
+    /// High price
+    #[br(big)]
+    pub high: u32,
+    /// Volume
+    #[br(big)]
+    pub volume: f32,
+}
+
+impl Candle {
+    /// Absolute time of the candle given the datetime of the file start
+    pub fn date_time(&self, file_start: NaiveDateTime) -> NaiveDateTime {
+        file_start + Duration::seconds(self.secs as i64)
+    }
+}
+
+/// Record layout of a decompressed bi5 buffer, see `detect_layout`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// 20 byte `Tick` records
+    Ticks,
+    /// 24 byte `Candle` records
+    Candles,
+}
+
+/// Interval of the candles of a file, see `CandleName`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeframe {
+    /// `min_<n>`
+    Minutes(u32),
+    /// `hour_<n>`
+    Hours(u32),
+    /// `day_<n>`
+    Days(u32),
+}
+
+impl Timeframe {
+    /// Length of one candle
+    pub fn duration(&self) -> Duration {
+        match *self {
+            Timeframe::Minutes(n) => Duration::minutes(n as i64),
+            Timeframe::Hours(n) => Duration::hours(n as i64),
+            Timeframe::Days(n) => Duration::days(n as i64),
+        }
+    }
+}
+
+/// Side and interval of a candle file named like `BID_candles_min_1.bi5`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CandleName {
+    pub side: Side,
+    pub timeframe: Timeframe,
+}
+
+/// Parse side and timeframe from the file name of a candle file
+///
+/// The stem must be `<BID|ASK>_candles_<min|hour|day>_<n>`, case-insensitive,
+/// with `n > 0`. The directories and the extension aren't looked at, so this
+/// also works for paths inside archives. Returns `None` for other names,
+/// e.g. tick files.
+/// ```
+/// use bi5::*;
+/// let name = parse_candle_name("EURUSD/2022/11/16/BID_candles_min_1.bi5".as_ref()).unwrap();
+/// assert_eq!(name, CandleName { side: Side::Bid, timeframe: Timeframe::Minutes(1) });
+/// assert_eq!(name.timeframe.duration(), chrono::Duration::minutes(1));
+/// assert_eq!(parse_candle_name("EURUSD/2022/11/16/13h_ticks.bi5".as_ref()), None);
+/// ```
+pub fn parse_candle_name(path: &Path) -> Option<CandleName> {
+    let stem = path.file_stem()?.to_str()?.to_ascii_lowercase();
+    let mut parts = stem.split('_');
+    let side = match parts.next()? {
+        "bid" => Side::Bid,
+        "ask" => Side::Ask,
+        _ => return None,
+    };
+    if parts.next()? != "candles" {
+        return None;
+    }
+    let unit = parts.next()?;
+    let n = parts.next()?.parse::<u32>().ok().filter(|&n| n > 0)?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let timeframe = match unit {
+        "min" => Timeframe::Minutes(n),
+        "hour" => Timeframe::Hours(n),
+        "day" => Timeframe::Days(n),
+        _ => return None,
+    };
+    Some(CandleName { side, timeframe })
+}
+
+/// Guess the record layout of a decompressed buffer
+///
+/// The length decides if it is a multiple of only one of the record sizes.
+/// If it is a multiple of both (i.e. of 120 bytes) the first records are
+/// checked for being plausible candles: `low <= open, close <= high` and
+/// start offsets that are whole minutes.
+/// Returns `None` if the buffer fits neither layout.
+pub fn detect_layout(buf: &[u8]) -> Option<Layout> {
+    let is_ticks = buf.len().is_multiple_of(size_of::<Tick>());
+    let is_candles = buf.len().is_multiple_of(size_of::<Candle>());
+    match (is_ticks, is_candles) {
+        (true, false) => Some(Layout::Ticks),
+        (false, true) => Some(Layout::Candles),
+        (false, false) => None,
+        (true, true) => {
+            let mut cursor = Cursor::new(buf);
+            let plausible = (0..(buf.len() / size_of::<Candle>()).min(5)).all(|_| {
+                match Candle::read(&mut cursor) {
+                    Ok(c) => c.secs % 60 == 0 && c.low <= c.open.min(c.close) && c.high >= c.open.max(c.close),
+                    Err(_) => false,
+                }
+            });
+            if plausible && !buf.is_empty() { Some(Layout::Candles) } else { Some(Layout::Ticks) }
+        }
+    }
+}
+
+/// Parse an already decompressed buffer into candles
+/// ## Arguments
+/// - `buf` - Decompressed bytes, a sequence of 24 byte big-endian records
+/// ## Returns
+/// `Vec` of `Candle`s or `Error` if the length is not a multiple of 24
+pub fn parse_candles(buf: &[u8]) -> Result<Vec<Candle>, Error> {
+    if !buf.len().is_multiple_of(size_of::<Candle>()) {
+        return Err(anyhow!(
+            "Decompressed buffer length {} is not a multiple of {}",
+            buf.len(),
+            size_of::<Candle>()
+        ));
+    }
+    let mut cursor = Cursor::new(buf);
+    let mut candles = Vec::with_capacity(buf.len() / size_of::<Candle>());
+    for _ in 0..buf.len() / size_of::<Candle>() {
+        candles.push(Candle::read(&mut cursor)?);
+    }
+    Ok(candles)
+}
+
+/// Decompress and parse a bi5 candle file
+/// ## Arguments
+/// - `path` - Path to bi5 candle file
+/// ## Returns
+/// `Vec` of `Candle`s or `Error`
+#[cfg(feature = "fs")]
+pub fn read_candle_bi5_file<P: AsRef<Path>>(path: P) -> Result<Vec<Candle>, Error> {
+    let file = std::fs::File::open(path)?;
+    parse_candles(&decompress(file)?)
+}
+
+#[cfg(test)]
+fn candle_bytes(candles: &[[u32; 5]]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for c in candles {
+        for x in c { buf.extend_from_slice(&x.to_be_bytes()); }
+        buf.extend_from_slice(&1.5f32.to_be_bytes());
+    }
+    buf
+}
+
+#[test]
+/// Test parsing of candle records
+fn test_parse_candles() {
+    let buf = candle_bytes(&[[0, 100, 110, 90, 120], [60, 110, 105, 100, 115]]);
+    let candles = parse_candles(&buf).unwrap();
+    assert_eq!(candles.len(), 2);
+    assert_eq!(candles[1], Candle { secs: 60, open: 110, close: 105, low: 100, high: 115, volume: 1.5 });
+    assert!(parse_candles(&buf[1..]).is_err());
+}
+
+#[test]
+/// Test layout detection by length and by plausibility
+fn test_detect_layout() {
+    assert_eq!(detect_layout(&[0u8; 20]), Some(Layout::Ticks));
+    assert_eq!(detect_layout(&[0u8; 24]), Some(Layout::Candles));
+    assert_eq!(detect_layout(&[0u8; 25]), None);
+    let candles = candle_bytes(&[[0, 100, 110, 90, 120]; 5]);
+    assert_eq!(detect_layout(&candles), Some(Layout::Candles));
+    let ticks = decompress(&include_bytes!("../test/test.bi5")[..]).unwrap();
+    assert_eq!(detect_layout(&ticks[..120]), Some(Layout::Ticks));
+}
+
+#[test]
+/// Test side, units, case and names that aren't candle files
+fn test_parse_candle_name() {
+    let parse = |s: &str| parse_candle_name(Path::new(s));
+    assert_eq!(parse("ASK_candles_hour_4.bi5"), Some(CandleName { side: Side::Ask, timeframe: Timeframe::Hours(4) }));
+    assert_eq!(parse("x/bid_CANDLES_day_1"), Some(CandleName { side: Side::Bid, timeframe: Timeframe::Days(1) }));
+    for name in ["BID_candles_min_0.bi5", "BID_candles_week_1.bi5", "BID_candles_min.bi5",
+                 "MID_candles_min_1.bi5", "BID_candles_min_1_x.bi5", "BID_ticks_min_1.bi5", "13h_ticks.bi5"] {
+        assert_eq!(parse(name), None, "{}", name);
+    }
+}
@@ -0,0 +1,152 @@
+//! A compact storage format for ticks, see `write_compact`.
+//!
+//! This is not bi5. A file starts with the magic `BI5C` and a version byte,
+//! followed by the tick count as varint. Each tick stores the differences of
+//! `millisecs`, `ask` and `bid` to the previous tick (the first to zero) as
+//! zigzag varints, then `asksize` and `bidsize` as little-endian `f32`.
+//! Slowly changing quotes need one byte per price. The result is well below
+//! the 20 bytes per tick of decompressed bi5 and decodes without LZMA, but
+//! it isn't necessarily smaller than the LZMA compressed bi5 file.
+
+use std::{fs, path::Path};
+use anyhow::Error;
+use crate::{Bi5Error, Tick};
+
+const MAGIC: &[u8; 4] = b"BI5C";
+const VERSION: u8 = 1;
+
+fn push_varint(buf: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        buf.push(x as u8 | 0x80);
+        x >>= 7;
+    }
+    buf.push(x as u8);
+}
+
+fn push_delta(buf: &mut Vec<u8>, cur: u32, prev: u32) {
+    let delta = cur as i64 - prev as i64;
+    push_varint(buf, ((delta << 1) ^ (delta >> 63)) as u64);
+}
+
+/// Reads the compact encoding of a tick sequence
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+fn truncated() -> Bi5Error {
+    Bi5Error::Parse("truncated compact file".to_string())
+}
+
+impl Reader<'_> {
+    fn varint(&mut self) -> Result<u64, Bi5Error> {
+        let mut x = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = *self.buf.get(self.pos).ok_or_else(truncated)?;
+            self.pos += 1;
+            x |= ((b & 0x7f) as u64) << shift;
+            if b < 0x80 {
+                return Ok(x);
+            }
+        }
+        Err(Bi5Error::Parse("varint too long".to_string()))
+    }
+
+    fn delta(&mut self, prev: u32) -> Result<u32, Bi5Error> {
+        let z = self.varint()?;
+        let delta = (z >> 1) as i64 ^ -((z & 1) as i64);
+        u32::try_from(prev as i64 + delta).map_err(|_| Bi5Error::Parse("delta out of range".to_string()))
+    }
+
+    fn f32(&mut self) -> Result<f32, Bi5Error> {
+        let bytes = self.buf.get(self.pos..self.pos + 4).ok_or_else(truncated)?;
+        self.pos += 4;
+        Ok(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// Encode `ticks` in the compact format
+fn encode(ticks: &[Tick]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(16 + ticks.len() * 12);
+    buf.extend_from_slice(MAGIC);
+    buf.push(VERSION);
+    push_varint(&mut buf, ticks.len() as u64);
+    let mut prev = Tick { millisecs: 0, ask: 0, bid: 0, asksize: 0.0, bidsize: 0.0 };
+    for tick in ticks {
+        push_delta(&mut buf, tick.millisecs, prev.millisecs);
+        push_delta(&mut buf, tick.ask, prev.ask);
+        push_delta(&mut buf, tick.bid, prev.bid);
+        buf.extend_from_slice(&tick.asksize.to_le_bytes());
+        buf.extend_from_slice(&tick.bidsize.to_le_bytes());
+        prev = *tick;
+    }
+    buf
+}
+
+/// Decode a buffer written by `encode`
+fn decode(buf: &[u8]) -> Result<Vec<Tick>, Bi5Error> {
+    if buf.get(..4) != Some(MAGIC) {
+        return Err(Bi5Error::Parse("not a compact tick file".to_string()));
+    }
+    if buf.get(4) != Some(&VERSION) {
+        return Err(Bi5Error::Parse(format!("unsupported compact version {:?}", buf.get(4))));
+    }
+    let mut reader = Reader { buf, pos: 5 };
+    let len = reader.varint()? as usize;
+    // every tick takes at least 11 bytes, don't trust the count for the allocation
+    let mut ticks = Vec::with_capacity(len.min(buf.len() / 11));
+    let mut prev = Tick { millisecs: 0, ask: 0, bid: 0, asksize: 0.0, bidsize: 0.0 };
+    for _ in 0..len {
+        let tick = Tick {
+            millisecs: reader.delta(prev.millisecs)?,
+            ask: reader.delta(prev.ask)?,
+            bid: reader.delta(prev.bid)?,
+            asksize: reader.f32()?,
+            bidsize: reader.f32()?,
+        };
+        ticks.push(tick);
+        prev = tick;
+    }
+    if reader.pos != buf.len() {
+        return Err(Bi5Error::Parse("trailing bytes after the last tick".to_string()));
+    }
+    Ok(ticks)
+}
+
+/// Write `ticks` to `path` in the compact delta + varint format
+///
+/// This is an alternative storage format, not bi5, and only `read_compact`
+/// reads it. Only the tick fields are stored, not the file start.
+/// ```
+/// use bi5::*;
+/// let ticks = read_bi5_file("test/test.bi5", None).unwrap();
+/// let path = std::env::temp_dir().join(format!("bi5-doc-{}.bi5c", std::process::id()));
+/// write_compact(&path, &ticks).unwrap();
+/// assert_eq!(read_compact(&path).unwrap(), ticks);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn write_compact<P: AsRef<Path>>(path: P, ticks: &[Tick]) -> Result<(), Error> {
+    fs::write(path, encode(ticks))?;
+    Ok(())
+}
+
+/// Read ticks written by `write_compact`
+pub fn read_compact<P: AsRef<Path>>(path: P) -> Result<Vec<Tick>, Error> {
+    Ok(decode(&fs::read(path)?)?)
+}
+
+#[test]
+/// Round trip of falling prices and times, smaller than decompressed bi5, and corrupt input
+fn test_compact() {
+    let mut ticks = crate::read_bi5_file("test/test.bi5", None).unwrap();
+    ticks.push(Tick { millisecs: 0, ask: u32::MAX, bid: 0, asksize: f32::NAN, bidsize: -1.0 });
+    let buf = encode(&ticks);
+    assert!(buf.len() < ticks.len() * 14);
+    let decoded = decode(&buf).unwrap();
+    assert_eq!(decoded[..ticks.len() - 1], ticks[..ticks.len() - 1]);
+    assert_eq!(decoded.last().unwrap().ask, u32::MAX);
+    assert!(decoded.last().unwrap().asksize.is_nan());
+    assert!(decode(&buf[..buf.len() - 1]).is_err());
+    assert!(decode(b"BI5C\x02").is_err());
+    assert_eq!(decode(&encode(&[])).unwrap(), vec![]);
+}
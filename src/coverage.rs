@@ -0,0 +1,75 @@
+//! Coverage of a directory tree, see `Bi5::missing_hours`.
+
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Timelike, Weekday};
+
+const WEEK_MINUTES: u32 = 7 * 24 * 60;
+
+/// Options of `Bi5::missing_hours`
+///
+/// FX markets close for the weekend, so by default the hours from Friday
+/// 22:00 to Sunday 22:00 UTC are expected to be empty when `skip_weekends`
+/// is set. The bounds are rounded down to the hour.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoverageOptions {
+    /// Don't report hours between `weekly_close` and `weekly_open`
+    pub skip_weekends: bool,
+    /// Start of the weekly close in UTC
+    pub weekly_close: (Weekday, NaiveTime),
+    /// End of the weekly close in UTC, the first hour expected to have data
+    pub weekly_open: (Weekday, NaiveTime),
+}
+
+impl Default for CoverageOptions {
+    fn default() -> Self {
+        CoverageOptions {
+            skip_weekends: false,
+            weekly_close: (Weekday::Fri, NaiveTime::from_hms_opt(22, 0, 0).unwrap()),
+            weekly_open: (Weekday::Sun, NaiveTime::from_hms_opt(22, 0, 0).unwrap()),
+        }
+    }
+}
+
+/// Minutes since Monday 00:00, rounded down to the hour
+fn week_minute(weekday: Weekday, time: NaiveTime) -> u32 {
+    weekday.num_days_from_monday() * 24 * 60 + time.hour() * 60
+}
+
+impl CoverageOptions {
+    /// True if the hour starting at `t` lies in the weekly close
+    pub fn is_weekend(&self, t: NaiveDateTime) -> bool {
+        let close = week_minute(self.weekly_close.0, self.weekly_close.1);
+        let open = week_minute(self.weekly_open.0, self.weekly_open.1);
+        let x = week_minute(t.weekday(), t.time()) % WEEK_MINUTES;
+        if close <= open {
+            close <= x && x < open
+        } else {
+            // the close wraps around Monday 00:00
+            x >= close || x < open
+        }
+    }
+
+    /// True if a missing hour starting at `t` should be reported
+    pub(crate) fn expects_data(&self, t: NaiveDateTime) -> bool {
+        !(self.skip_weekends && self.is_weekend(t))
+    }
+}
+
+#[test]
+/// Test the Friday close and Sunday open boundaries, and a close wrapping the week
+fn test_is_weekend() {
+    let t = |d, h| chrono::NaiveDate::from_ymd_opt(2022, 12, d).unwrap().and_hms_opt(h, 0, 0).unwrap();
+    let options = CoverageOptions::default();
+    // 2022-12-16 is a Friday
+    assert!(!options.is_weekend(t(16, 21)));
+    assert!(options.is_weekend(t(16, 22)));
+    assert!(options.is_weekend(t(17, 12)));
+    assert!(options.is_weekend(t(18, 21)));
+    assert!(!options.is_weekend(t(18, 22)));
+    let wrapping = CoverageOptions {
+        weekly_close: (Weekday::Sun, NaiveTime::from_hms_opt(20, 0, 0).unwrap()),
+        weekly_open: (Weekday::Mon, NaiveTime::from_hms_opt(2, 0, 0).unwrap()),
+        ..options
+    };
+    assert!(wrapping.is_weekend(t(18, 23)) && wrapping.is_weekend(t(19, 1)));
+    assert!(!wrapping.is_weekend(t(19, 2)) && !wrapping.is_weekend(t(16, 23)));
+}
@@ -0,0 +1,141 @@
+//! Reading back the CSV written by catbi5, see `read_csv_ticks`, and
+//! `TickRecord` rows for other CSV writers.
+
+use std::io::{BufRead, BufReader, Lines, Read};
+use chrono::{NaiveDateTime, Timelike};
+use anyhow::{anyhow, Error};
+use crate::Tick;
+
+/// Parse the CSV of catbi5 back into ticks
+///
+/// The columns are `t, bid, ask, bidsize, asksize`, optionally preceded by
+/// the symbol column of `--with-symbol`, which is skipped. Fields are
+/// separated by tabs, the default of `--sep`, or by commas if a line has no
+/// tab. `t` is formatted like `NaiveDateTime`'s `Display` or
+/// `--time-format iso`. `Tick::millisecs` is the offset from the start of
+/// the hour of `t`. Empty lines are skipped, a line that doesn't parse yields
+/// an error with its line number.
+/// ```
+/// use bi5::*;
+/// let csv = "t\tbid\task\tbidsize\tasksize\n2022-12-16 14:31:00.002\t133117\t133153\t0.020\t0.015\n";
+/// let ticks: Vec<_> = read_csv_ticks(csv.as_bytes(), true).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(ticks[0].0.to_string(), "2022-12-16 14:31:00.002");
+/// assert_eq!((ticks[0].1.millisecs, ticks[0].1.bid, ticks[0].1.asksize), (1860002, 133117, 0.015));
+/// ```
+pub fn read_csv_ticks<R: Read>(reader: R, has_header: bool) -> CsvTicks<R> {
+    let mut lines = BufReader::new(reader).lines();
+    let mut line = 0;
+    if has_header {
+        // a read error of the header shows up again with the next line
+        lines.next();
+        line += 1;
+    }
+    CsvTicks { lines, line }
+}
+
+/// Iterator returned by `read_csv_ticks`
+pub struct CsvTicks<R> {
+    lines: Lines<BufReader<R>>,
+    /// number of the last line read, 1-based
+    line: usize,
+}
+
+/// Parse one line of values
+fn parse_line(s: &str) -> Result<(NaiveDateTime, Tick), Error> {
+    let sep = if s.contains('\t') { '\t' } else { ',' };
+    let fields: Vec<&str> = s.split(sep).map(str::trim).collect();
+    let fields = match fields.len() {
+        5 => &fields[..],
+        6 => &fields[1..],
+        n => return Err(anyhow!("expected 5 or 6 fields, found {}", n)),
+    };
+    let t = NaiveDateTime::parse_from_str(fields[0], "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(fields[0], "%Y-%m-%dT%H:%M:%S%.f"))
+        .map_err(|e| anyhow!("invalid time {:?}: {}", fields[0], e))?;
+    let millisecs = (t.minute() * 60 + t.second()) * 1000 + t.nanosecond() / 1_000_000;
+    Ok((t, Tick {
+        millisecs,
+        bid: fields[1].parse().map_err(|e| anyhow!("invalid bid {:?}: {}", fields[1], e))?,
+        ask: fields[2].parse().map_err(|e| anyhow!("invalid ask {:?}: {}", fields[2], e))?,
+        bidsize: fields[3].parse().map_err(|e| anyhow!("invalid bidsize {:?}: {}", fields[3], e))?,
+        asksize: fields[4].parse().map_err(|e| anyhow!("invalid asksize {:?}: {}", fields[4], e))?,
+    }))
+}
+
+impl<R: Read> Iterator for CsvTicks<R> {
+    type Item = Result<(NaiveDateTime, Tick), Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = self.lines.next()?;
+            self.line += 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(anyhow!("line {}: {}", self.line, e))),
+            };
+            if !line.trim().is_empty() {
+                return Some(parse_line(&line).map_err(|e| anyhow!("line {}: {}", self.line, e)));
+            }
+        }
+    }
+}
+
+/// A tick with its absolute time as named fields, for writing with other
+/// CSV writers and their own options
+///
+/// `fields()` returns the values in the order of `TickRecord::HEADER`, so
+/// they can be passed to e.g. `write_record` of the `csv` crate. Sizes are
+/// not rounded like by catbi5, so `read_csv_ticks` reads them back exactly.
+/// ```
+/// use bi5::*;
+/// let (t, tick) = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap()[0];
+/// let record = TickRecord::from((t, tick));
+/// assert_eq!(TickRecord::HEADER, ["t", "bid", "ask", "bidsize", "asksize"]);
+/// assert_eq!(record.fields().join(","), "0000-01-01 00:31:00.002,133117,133153,0.02,0.015");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickRecord {
+    /// Absolute tick time
+    pub t: NaiveDateTime,
+    pub bid: u32,
+    pub ask: u32,
+    pub bidsize: f32,
+    pub asksize: f32,
+}
+
+impl TickRecord {
+    /// Names of the fields, the header of catbi5
+    pub const HEADER: [&'static str; 5] = ["t", "bid", "ask", "bidsize", "asksize"];
+
+    /// The values in the order of `HEADER`
+    pub fn fields(&self) -> [String; 5] {
+        [self.t.to_string(), self.bid.to_string(), self.ask.to_string(), self.bidsize.to_string(), self.asksize.to_string()]
+    }
+}
+
+impl From<(NaiveDateTime, Tick)> for TickRecord {
+    fn from((t, tick): (NaiveDateTime, Tick)) -> Self {
+        TickRecord { t, bid: tick.bid, ask: tick.ask, bidsize: tick.bidsize, asksize: tick.asksize }
+    }
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test the round trip of a file through `TickRecord`, commas, the symbol column and the line number of errors
+fn test_read_csv_ticks() {
+    let start = NaiveDateTime::from_timestamp_opt(1671199200, 0).unwrap();
+    let ticks: Vec<_> = crate::Bi5::new("test/test.bi5", Some(start)).iter().unwrap().collect();
+    let mut csv = TickRecord::HEADER.join("\t") + "\n";
+    for &tick in &ticks {
+        csv += &(TickRecord::from(tick).fields().join("\t") + "\n");
+    }
+    let read: Vec<_> = read_csv_ticks(csv.as_bytes(), true).collect::<Result<_, _>>().unwrap();
+    assert_eq!(read, ticks);
+
+    let csv = "EURUSD,2022-12-16T14:59:59.899,1,2,0.5,0.25\n\n2022-12-16 14:00:00,1,2,x,0.25\n";
+    let mut read = read_csv_ticks(csv.as_bytes(), false);
+    let (t, tick) = read.next().unwrap().unwrap();
+    assert_eq!((t, tick.millisecs, tick.bidsize), (start + chrono::Duration::milliseconds(3599899), 3599899, 0.5));
+    let e = read.next().unwrap().unwrap_err().to_string();
+    assert!(e.starts_with("line 3: invalid bidsize"), "{}", e);
+    assert!(read.next().is_none());
+}
@@ -0,0 +1,682 @@
+//! Decoding of bi5 byte buffers.
+//!
+//! Nothing in here touches the filesystem, so this part of the crate also
+//! builds without the `fs` feature (e.g. for `wasm32-unknown-unknown`).
+
+use std::{
+    io::{self, Cursor, Read, BufRead, BufReader, Write},
+    mem::size_of,
+};
+use binread::BinRead;
+use lzma_rs::{lzma_decompress, xz_decompress};
+use anyhow::Error;
+use chrono::NaiveDateTime;
+use crate::{Tick, Bi5Error};
+
+/// Returns an `Error` if `buf` does not hold a whole number of ticks,
+/// `Bi5Error::DoubleCompressed` if it starts with a compression header
+pub(crate) fn check_len(buf: &[u8]) -> Result<(), Bi5Error> {
+    if !buf.len().is_multiple_of(size_of::<Tick>()) {
+        if let Some(format) = compressed_format(buf) {
+            return Err(Bi5Error::DoubleCompressed { len: buf.len(), format });
+        }
+        return Err(Bi5Error::Length { len: buf.len(), record: size_of::<Tick>() });
+    }
+    Ok(())
+}
+
+/// Drop a trailing partial tick from `buf`, returns the number of dropped bytes
+#[cfg(feature = "fs")]
+pub(crate) fn truncate_partial(buf: &mut Vec<u8>) -> usize {
+    let dropped = buf.len() % size_of::<Tick>();
+    buf.truncate(buf.len() - dropped);
+    dropped
+}
+
+/// Drop the ticks of `buf` whose `millisecs` exceed `max_millisecs`, returns
+/// the number of dropped ticks. `buf` must hold a whole number of ticks.
+#[cfg(feature = "fs")]
+pub(crate) fn drop_implausible(buf: &mut Vec<u8>, max_millisecs: u32) -> usize {
+    let len = buf.len();
+    let mut kept = 0;
+    for start in (0..len).step_by(size_of::<Tick>()) {
+        let millisecs = u32::from_be_bytes([buf[start], buf[start + 1], buf[start + 2], buf[start + 3]]);
+        if millisecs <= max_millisecs {
+            buf.copy_within(start..start + size_of::<Tick>(), kept);
+            kept += size_of::<Tick>();
+        }
+    }
+    buf.truncate(kept);
+    (len - kept) / size_of::<Tick>()
+}
+
+/// Returns `Bi5Error::MillisecsOutOfRange` for the first tick of `buf` whose
+/// `millisecs` exceed `max_millisecs`
+#[cfg(feature = "fs")]
+pub(crate) fn check_max_millisecs(buf: &[u8], max_millisecs: u32) -> Result<(), Bi5Error> {
+    for (index, record) in buf.chunks_exact(size_of::<Tick>()).enumerate() {
+        let millisecs = u32::from_be_bytes([record[0], record[1], record[2], record[3]]);
+        if millisecs > max_millisecs {
+            return Err(Bi5Error::MillisecsOutOfRange { index, millisecs, max: max_millisecs });
+        }
+    }
+    Ok(())
+}
+
+/// Backward step of `millisecs` beyond which `millisecs_resets` sees a new hour
+const RESET_STEP: u32 = 60_000;
+
+/// Positions of the ticks whose `millisecs` are more than a minute below
+/// those of the previous tick
+///
+/// Such a reset is the sign of a file concatenated from several hours
+/// without rebasing the times, each reset starts the next hour. Clock jitter
+/// steps back by far less. The byte offset of a reset is its position times
+/// 20.
+/// ```
+/// use bi5::*;
+/// let mut ticks = parse_ticks(&decompress_bi5(std::fs::File::open("test/test.bi5").unwrap()).unwrap()).unwrap();
+/// assert!(millisecs_resets(&ticks).is_empty());
+/// let n = ticks.len();
+/// ticks.extend_from_within(..n);
+/// assert_eq!(millisecs_resets(&ticks), vec![n]);
+/// ```
+pub fn millisecs_resets(ticks: &[Tick]) -> Vec<usize> {
+    (1..ticks.len())
+        .filter(|&i| ticks[i].millisecs < ticks[i - 1].millisecs.saturating_sub(RESET_STEP))
+        .collect()
+}
+
+/// Add an hour to the `millisecs` of the ticks of `buf` after each reset, see
+/// `millisecs_resets`, returns the number of resets. `buf` must hold a whole
+/// number of ticks.
+#[cfg(feature = "fs")]
+pub(crate) fn split_resets(buf: &mut [u8]) -> usize {
+    let (mut resets, mut prev) = (0u32, 0u32);
+    for record in buf.chunks_exact_mut(size_of::<Tick>()) {
+        let millisecs = u32::from_be_bytes([record[0], record[1], record[2], record[3]]);
+        if millisecs < prev.saturating_sub(RESET_STEP) {
+            resets += 1;
+        }
+        prev = millisecs;
+        let rebased = millisecs.saturating_add(resets * crate::HOUR_MILLISECS);
+        record[..4].copy_from_slice(&rebased.to_be_bytes());
+    }
+    resets as usize
+}
+
+/// Number of leading ticks inspected by `check_endianness`
+#[cfg(feature = "fs")]
+const ENDIANNESS_SAMPLE: usize = 8;
+
+/// Returns `Bi5Error::Endianness` if the leading ticks of `buf` look like
+/// little-endian records: their `millisecs` are implausible read as big-endian
+/// (decreasing or beyond `MAX_PLAUSIBLE_MILLISECS`) but fine byte-swapped
+#[cfg(feature = "fs")]
+pub(crate) fn check_endianness(buf: &[u8]) -> Result<(), Bi5Error> {
+    let plausible = |millisecs: &[u32]| {
+        millisecs.windows(2).all(|w| w[0] <= w[1])
+            && millisecs.iter().all(|&ms| ms <= crate::MAX_PLAUSIBLE_MILLISECS)
+    };
+    let sample: Vec<[u8; 4]> = buf.chunks_exact(size_of::<Tick>())
+        .take(ENDIANNESS_SAMPLE)
+        .map(|record| [record[0], record[1], record[2], record[3]])
+        .collect();
+    let be: Vec<u32> = sample.iter().map(|&b| u32::from_be_bytes(b)).collect();
+    let le: Vec<u32> = sample.iter().map(|&b| u32::from_le_bytes(b)).collect();
+    if !plausible(&be) && plausible(&le) {
+        return Err(Bi5Error::Endianness);
+    }
+    Ok(())
+}
+
+/// Compression format of a bi5 file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    /// Detect from the header: xz if it starts with the xz magic, else LZMA
+    #[default]
+    Auto,
+    /// Raw LZMA ("LZMA alone"), as used by Dukascopy
+    Lzma,
+    /// xz container, e.g. re-packaged files
+    Xz,
+}
+
+/// Magic bytes at the start of an xz stream
+const XZ_MAGIC: [u8; 6] = [0xFD, b'7', b'z', b'X', b'Z', 0x00];
+
+/// Magic bytes at the start of a gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The format of the compression header `buf` starts with, `"LZMA"`, `"xz"`
+/// or `"gzip"`. LZMA has no magic, its header is recognized by the usual
+/// properties byte `0x5d` and a power of two dictionary size. Tick records
+/// can't start like this, their `millisecs` would be far beyond an hour.
+pub(crate) fn compressed_format(buf: &[u8]) -> Option<&'static str> {
+    if buf.starts_with(&XZ_MAGIC) {
+        Some("xz")
+    } else if buf.starts_with(&GZIP_MAGIC) {
+        Some("gzip")
+    } else if buf.len() >= 13 && buf[0] == 0x5d && u32::from_le_bytes(buf[1..5].try_into().unwrap()).is_power_of_two() {
+        Some("LZMA")
+    } else {
+        None
+    }
+}
+
+/// The unpacked size stored in an LZMA header at bytes 5..13, `None` if the
+/// header is too short or marks the size as unknown (all ones)
+pub(crate) fn lzma_unpacked_size(header: &[u8]) -> Option<u64> {
+    let size = u64::from_le_bytes(header.get(5..13)?.try_into().unwrap());
+    (size != u64::MAX).then_some(size)
+}
+
+/// Upper bound of the pre-allocation from the LZMA header, a corrupt size
+/// field must not reserve an absurd amount of memory up front
+const MAX_PREALLOCATION: u64 = 1 << 26;
+
+/// Decompress an LZMA or xz stream, an empty stream gives an empty buffer
+pub(crate) fn decompress<R: Read>(reader: R) -> Result<Vec<u8>, Bi5Error> {
+    decompress_with(reader, 0, Codec::Auto, DEFAULT_READ_BUFFER)
+}
+
+/// Default size of the read buffer, the one of `BufReader::new`
+pub(crate) const DEFAULT_READ_BUFFER: usize = 8 * 1024;
+
+/// The read buffer must hold the headers inspected up front
+const MIN_READ_BUFFER: usize = 64;
+
+/// Like `decompress` but pre-allocates `capacity` bytes for the output, reads
+/// through a buffer of `read_buffer` bytes (at least 64) and uses `codec` to
+/// decode
+pub(crate) fn decompress_with<R: Read>(reader: R, capacity: usize, codec: Codec, read_buffer: usize) -> Result<Vec<u8>, Bi5Error> {
+    let mut reader = BufReader::with_capacity(read_buffer.max(MIN_READ_BUFFER), reader);
+    let mut buf: Vec<u8> = Vec::with_capacity(capacity);  // buffer to decode into
+    let header = reader.fill_buf()?;
+    if header.is_empty() {
+        return Ok(buf);
+    }
+    if codec == Codec::Auto && header.starts_with(&GZIP_MAGIC) {
+        return Err(Bi5Error::Decompress("gzip stream, bi5 files are LZMA compressed; gunzip the file first".to_string()));
+    }
+    let codec = match codec {
+        Codec::Auto if header.starts_with(&XZ_MAGIC) => Codec::Xz,
+        Codec::Auto => Codec::Lzma,
+        codec => codec,
+    };
+    // Dukascopy files store the unpacked size, which sizes the buffer and
+    // tells a truncated stream from a corrupt one
+    let expected = if codec == Codec::Lzma { lzma_unpacked_size(header) } else { None };
+    if let Some(size) = expected {
+        if size > MAX_DECOMPRESSED as u64 {
+            return Err(too_large(size));
+        }
+        buf.reserve((size.min(MAX_PREALLOCATION) as usize).saturating_sub(capacity));
+    }
+    let mut out = LimitedWriter { buf: &mut buf, exceeded: false };
+    let result = match codec {
+        Codec::Xz => xz_decompress(&mut reader, &mut out)
+            .map_err(|e| Bi5Error::Decompress(format!("xz decoding failed: {}", e))),
+        _ => lzma_decompress(&mut reader, &mut out)
+            .map_err(|e| Bi5Error::Decompress(format!("LZMA decoding failed: {}", e))),
+    };
+    if out.exceeded {
+        return Err(too_large(out.buf.len() as u64));
+    }
+    match expected {
+        Some(size) if result.is_err() && (out.buf.len() as u64) < size => {
+            return Err(Bi5Error::Decompress(format!(
+                "LZMA stream truncated, decompressed {} of the {} bytes given in the header", out.buf.len(), size
+            )));
+        }
+        Some(size) if result.is_ok() && out.buf.len() as u64 != size => {
+            return Err(Bi5Error::Decompress(format!(
+                "Decompressed size {} doesn't match the {} bytes given in the header", out.buf.len(), size
+            )));
+        }
+        _ => result?,
+    }
+    Ok(buf)
+}
+
+/// The first `len` bytes of an LZMA or xz stream, fewer if the stream is
+/// shorter. Decoding stops once they are written, so this is cheap for large
+/// files.
+#[cfg(any(feature = "fs", test))]
+pub(crate) fn decompress_prefix<R: Read>(reader: R, len: usize) -> Result<Vec<u8>, Bi5Error> {
+    let mut reader = BufReader::new(reader);
+    let mut out = PrefixWriter { buf: Vec::with_capacity(len), len };
+    let header = reader.fill_buf()?;
+    if header.is_empty() {
+        return Ok(out.buf);
+    }
+    let result = if header.starts_with(&XZ_MAGIC) {
+        xz_decompress(&mut reader, &mut out).map_err(|e| Bi5Error::Decompress(format!("xz decoding failed: {}", e)))
+    } else {
+        lzma_decompress(&mut reader, &mut out).map_err(|e| Bi5Error::Decompress(format!("LZMA decoding failed: {}", e)))
+    };
+    // the error of a full prefix is the one `PrefixWriter` raised to stop
+    if out.buf.len() < len {
+        result?;
+    }
+    Ok(out.buf)
+}
+
+/// Keeps the first `len` bytes and then fails to stop the decoder
+#[cfg(any(feature = "fs", test))]
+struct PrefixWriter {
+    buf: Vec<u8>,
+    len: usize,
+}
+
+#[cfg(any(feature = "fs", test))]
+impl Write for PrefixWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(self.len - self.buf.len());
+        if n == 0 && !data.is_empty() {
+            return Err(io::Error::other("prefix complete"));
+        }
+        self.buf.extend_from_slice(&data[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Limit of the decompressed size, keeps the buffer and the tick offsets
+/// addressable on 32-bit targets
+const MAX_DECOMPRESSED: usize = usize::MAX / 2;
+
+fn too_large(size: u64) -> Bi5Error {
+    Bi5Error::Decompress(format!(
+        "Decompressed size {} exceeds the limit of {} bytes on this target", size, MAX_DECOMPRESSED
+    ))
+}
+
+/// Writes into a `Vec` and fails instead of growing it beyond `MAX_DECOMPRESSED`
+struct LimitedWriter<'a> {
+    buf: &'a mut Vec<u8>,
+    exceeded: bool,
+}
+
+impl Write for LimitedWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.len() > MAX_DECOMPRESSED - self.buf.len() {
+            self.exceeded = true;
+            return Err(io::Error::other("decompressed size limit exceeded"));
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Decompress a bi5 stream into a validated buffer of tick records
+/// ## Arguments
+/// - `reader` - LZMA (or xz) compressed bi5 content, an empty stream gives an empty buffer
+/// ## Returns
+/// Decompressed bytes whose length is a multiple of 20, or `Error`
+///
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let file = std::fs::File::open("test/test.bi5").unwrap();
+/// let buf = decompress_bi5(file).expect("Decode failed");
+/// assert_eq!(parse_ticks(&buf).unwrap().len(), 10412);
+/// ```
+pub fn decompress_bi5<R: Read>(reader: R) -> Result<Vec<u8>, Error> {
+    let buf = decompress(reader)?;
+    check_len(&buf)?;
+    Ok(buf)
+}
+
+/// Parse an already decompressed buffer into ticks
+/// ## Arguments
+/// - `buf` - Decompressed bytes, a sequence of 20 byte big-endian records
+/// ## Returns
+/// `Vec` of `Tick`s or `Error` if the length is not a multiple of 20
+pub fn parse_ticks(buf: &[u8]) -> Result<Vec<Tick>, Error> {
+    check_len(buf)?;
+    let mut cursor = Cursor::new(buf);
+    let mut ticks = Vec::with_capacity(buf.len() / size_of::<Tick>());
+    for _ in 0..buf.len() / size_of::<Tick>() {
+        ticks.push(Tick::read(&mut cursor)?);
+    }
+    Ok(ticks)
+}
+
+/// Parse a single 20 byte big-endian record
+///
+/// ```
+/// use bi5::*;
+/// let buf = decompress_bi5(std::fs::File::open("test/test.bi5").unwrap()).unwrap();
+/// let tick = Tick::try_from(&buf[..20]).unwrap();
+/// assert_eq!(tick.millisecs, 1860002);
+/// ```
+impl TryFrom<&[u8]> for Tick {
+    type Error = Bi5Error;
+    fn try_from(buf: &[u8]) -> Result<Self, Self::Error> {
+        if buf.len() != size_of::<Tick>() {
+            return Err(Bi5Error::Parse(format!(
+                "Tick record must be {} bytes, got {}", size_of::<Tick>(), buf.len()
+            )));
+        }
+        Ok(Tick::read(&mut Cursor::new(buf))?)
+    }
+}
+
+/// Decompress and parse the content of a bi5 file held in memory
+/// ## Arguments
+/// - `bytes` - LZMA compressed bi5 content, an empty slice yields no ticks
+/// ## Returns
+/// `Vec` of `Tick`s or `Error`
+///
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let bytes = std::fs::read("test/test.bi5").unwrap();
+/// let ticks = from_compressed_bytes(&bytes).expect("Decode failed");
+/// assert_eq!(ticks.len(), 10412);
+/// ```
+pub fn from_compressed_bytes(bytes: &[u8]) -> Result<Vec<Tick>, Error> {
+    parse_ticks(&decompress_bi5(bytes)?)
+}
+
+/// Decompress and parse bi5 content from any reader, e.g. an HTTP response
+/// ## Arguments
+/// - `reader` - LZMA (or xz) compressed bi5 content, an empty stream yields no ticks
+/// - `date_time` - Start of the file, see `Tick::time`
+/// ## Returns
+/// `Vec` of `(NaiveDateTime, Tick)` like `read_bi5_file_dated`, or `Error`
+///
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let start = chrono::NaiveDateTime::from_timestamp_opt(1671199200, 0).unwrap();
+/// let bytes = std::fs::read("test/test.bi5").unwrap();
+/// let ticks = read_bi5_reader(&bytes[..], start).expect("Decode failed");
+/// assert_eq!(ticks.first().unwrap().0.to_string(), "2022-12-16 14:31:00.002");
+/// ```
+pub fn read_bi5_reader<R: Read>(reader: R, date_time: NaiveDateTime) -> Result<Vec<(NaiveDateTime, Tick)>, Error> {
+    Ok(parse_ticks(&decompress_bi5(reader)?)?.into_iter().map(|tick| (tick.time(date_time), tick)).collect())
+}
+
+/// Decompress and parse a bi5 file held in memory, keeping the absolute time
+/// of each tick
+/// ## Arguments
+/// - `bytes` - LZMA (or xz) compressed bi5 content, an empty slice yields no ticks
+/// - `date_time` - Optional start of the file, 0000-01-01 if `None` as for `Bi5::new`
+/// ## Returns
+/// `Vec` of `(NaiveDateTime, Tick)` like `read_bi5_file_dated`, or `Error`
+///
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).expect("Decode failed");
+/// assert_eq!(ticks.first().unwrap().0.to_string(), "0000-01-01 00:31:00.002");
+/// ```
+pub fn decode_bytes(bytes: &[u8], date_time: Option<NaiveDateTime>) -> Result<Vec<(NaiveDateTime, Tick)>, Error> {
+    read_bi5_reader(bytes, date_time.unwrap_or(crate::zero_timestamp()))
+}
+
+/// Compressed bytes `TickStream` feeds to the decoder at a time
+const STREAM_CHUNK: usize = 16 * 1024;
+
+/// Iterator decoding the ticks of an LZMA stream incrementally, see
+/// `Bi5::iter_stream`
+///
+/// The stream is read in chunks and each tick is parsed as soon as its 20
+/// bytes are decoded, so only the decoder state is held in memory rather than
+/// the whole decompressed file. Leftover bytes that are no whole tick are an
+/// `Err` at the end of the stream, after the ticks before them. An empty
+/// stream yields no ticks. xz streams aren't supported, the iteration ends
+/// after the first error.
+/// ```
+/// use bi5::*;
+/// let file = std::fs::File::open("test/test.bi5").unwrap();
+/// let ticks: Vec<_> = TickStream::new(file, Default::default()).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(ticks.len(), 10412);
+/// ```
+pub struct TickStream<R> {
+    reader: R,
+    /// `None` once the stream is finished or failed
+    decoder: Option<lzma_rs::decompress::Stream<Vec<u8>>>,
+    /// decoded bytes from `pos` on are not parsed yet
+    buf: Vec<u8>,
+    pos: usize,
+    /// decoded bytes so far
+    len: usize,
+    /// some compressed input was read
+    started: bool,
+    date_time: NaiveDateTime,
+}
+
+impl<R: Read> TickStream<R> {
+    /// Ticks of the LZMA stream `reader` of a file starting at `date_time`
+    pub fn new(reader: R, date_time: NaiveDateTime) -> Self {
+        let decoder = Some(lzma_rs::decompress::Stream::new(Vec::new()));
+        TickStream { reader, decoder, buf: Vec::new(), pos: 0, len: 0, started: false, date_time }
+    }
+
+    /// Feed the next chunk to the decoder, finishing it at the end of the
+    /// stream
+    fn fill(&mut self) -> Result<(), Bi5Error> {
+        let Some(decoder) = self.decoder.as_mut() else { return Ok(()) };
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+        let mut chunk = [0u8; STREAM_CHUNK];
+        let n = loop {
+            match self.reader.read(&mut chunk) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result?,
+            }
+        };
+        if n > 0 {
+            if !self.started && (chunk[..n].starts_with(&XZ_MAGIC) || chunk[..n].starts_with(&GZIP_MAGIC)) {
+                return Err(Bi5Error::Decompress("only LZMA streams can be decoded incrementally".to_string()));
+            }
+            self.started = true;
+            decoder.write_all(&chunk[..n])
+                .map_err(|e| Bi5Error::Decompress(format!("LZMA decoding failed: {}", e)))?;
+            let out = decoder.get_output_mut().expect("the decoder isn't finished");
+            self.len += out.len();
+            self.buf.append(out);
+        } else if let Some(decoder) = self.decoder.take() {
+            if self.started {
+                let mut out = decoder.finish()
+                    .map_err(|e| Bi5Error::Decompress(format!("LZMA decoding failed: {}", e)))?;
+                self.len += out.len();
+                self.buf.append(&mut out);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for TickStream<R> {
+    type Item = Result<(NaiveDateTime, Tick), Bi5Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.buf.get(self.pos..self.pos + size_of::<Tick>()) {
+                self.pos += size_of::<Tick>();
+                return Some(Tick::try_from(record).map(|tick| (tick.time(self.date_time), tick)));
+            }
+            if self.decoder.is_none() {
+                if self.pos < self.buf.len() {
+                    self.pos = self.buf.len();
+                    return Some(Err(Bi5Error::Length { len: self.len, record: size_of::<Tick>() }));
+                }
+                return None;
+            }
+            if let Err(e) = self.fill() {
+                self.decoder = None;
+                self.buf.clear();
+                self.pos = 0;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+impl<R: Read> std::iter::FusedIterator for TickStream<R> {}
+
+#[test]
+/// Test that streamed ticks match the buffered ones, and the errors of trailing bytes and truncation
+fn test_tick_stream() {
+    let bytes = include_bytes!("../test/test.bi5");
+    let streamed: Vec<_> = TickStream::new(Cursor::new(&bytes[..]), NaiveDateTime::default()).collect::<Result<_, _>>().unwrap();
+    assert_eq!(streamed, read_bi5_reader(&bytes[..], NaiveDateTime::default()).unwrap());
+    assert_eq!(TickStream::new(io::empty(), NaiveDateTime::default()).count(), 0);
+
+    let mut compressed = Vec::new();
+    lzma_rs::lzma_compress(&mut &[0u8; 30][..], &mut compressed).unwrap();
+    let mut stream = TickStream::new(&compressed[..], NaiveDateTime::default());
+    assert!(matches!(stream.next(), Some(Ok(_))));
+    assert!(matches!(stream.next(), Some(Err(Bi5Error::Length { len: 30, record: 20 }))));
+    assert!(stream.next().is_none());
+
+    let truncated = TickStream::new(&bytes[..1000], NaiveDateTime::default()).collect::<Vec<_>>();
+    assert!(truncated.last().unwrap().is_err());
+    assert!(truncated.len() < 10412);
+}
+
+#[test]
+/// Smoke test of the filesystem-free decode path (this is what runs under wasm)
+fn test_from_compressed_bytes() {
+    let ticks = from_compressed_bytes(include_bytes!("../test/test.bi5")).unwrap();
+    assert_eq!(ticks.len(), 10412);
+    assert_eq!(
+        ticks.first(),
+        Some(&Tick { millisecs: 1860002, bid: 133117, ask: 133153, bidsize: 0.02, asksize: 0.015 })
+    );
+    assert_eq!(from_compressed_bytes(&[]).unwrap(), vec![]);
+    assert_eq!(read_bi5_reader(io::empty(), NaiveDateTime::default()).unwrap(), vec![]);
+    assert!(read_bi5_reader(&include_bytes!("../test/test.bi5")[..1000], NaiveDateTime::default()).is_err());
+}
+
+#[test]
+/// Test that decompress_bi5 validates the length and accepts empty input
+fn test_decompress_bi5() {
+    assert_eq!(decompress_bi5(&include_bytes!("../test/test.bi5")[..]).unwrap().len(), 10412 * 20);
+    assert!(decompress_bi5(&[][..]).unwrap().is_empty());
+    assert!(decompress_bi5(&[0x5d, 0, 0][..]).is_err());
+}
+
+#[test]
+/// Test that xz streams are detected, and only decoded with the right codec
+fn test_codec() {
+    let raw = decompress(&include_bytes!("../test/test.bi5")[..]).unwrap();
+    let mut xz = Vec::new();
+    lzma_rs::xz_compress(&mut Cursor::new(&raw[..2000]), &mut xz).unwrap();
+    assert_eq!(decompress_with(&xz[..], 0, Codec::Auto, DEFAULT_READ_BUFFER).unwrap(), &raw[..2000]);
+    assert_eq!(decompress_with(&xz[..], 0, Codec::Xz, DEFAULT_READ_BUFFER).unwrap(), &raw[..2000]);
+    assert!(decompress_with(&xz[..], 0, Codec::Lzma, DEFAULT_READ_BUFFER).is_err());
+    let lzma = include_bytes!("../test/test.bi5");
+    assert!(decompress_with(&lzma[..], 0, Codec::Xz, DEFAULT_READ_BUFFER).is_err());
+}
+
+#[test]
+/// Test that a second compression layer and gzip input give actionable errors
+fn test_double_compressed() {
+    let bytes = include_bytes!("../test/test.bi5");
+    let mut twice = Vec::new();
+    lzma_rs::lzma_compress(&mut &bytes[..], &mut twice).unwrap();
+    match decompress_bi5(&twice[..]) {
+        Err(e) => assert!(e.to_string().contains("compressed twice"), "{}", e),
+        Ok(buf) => panic!("{} bytes", buf.len()),
+    }
+    assert!(matches!(check_len(&bytes[..]), Err(Bi5Error::DoubleCompressed { len: 36827, format: "LZMA" })));
+    assert!(matches!(check_len(&[0x1f, 0x8b, 8]), Err(Bi5Error::DoubleCompressed { format: "gzip", .. })));
+    assert!(matches!(check_len(&[0u8; 21]), Err(Bi5Error::Length { .. })));
+    let e = decompress(&[0x1f, 0x8b, 8, 0][..]).unwrap_err().to_string();
+    assert!(e.contains("gunzip"), "{}", e);
+}
+
+#[test]
+/// Test that a buffer with a partial tick is rejected
+fn test_parse_ticks_bad_len() {
+    assert!(parse_ticks(&[0u8; 21]).is_err());
+    assert_eq!(parse_ticks(&[0u8; 40]).unwrap().len(), 2);
+}
+
+#[test]
+/// Slices must be exactly one record long
+fn test_tick_try_from() {
+    let buf = decompress_bi5(&include_bytes!("../test/test.bi5")[..]).unwrap();
+    assert_eq!(Tick::try_from(&buf[20..40]).unwrap(), parse_ticks(&buf).unwrap()[1]);
+    for len in [0, 19, 21, 40] {
+        assert!(matches!(Tick::try_from(&buf[..len]), Err(Bi5Error::Parse(_))));
+    }
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that resets are found and split into hours, and jitter is not a reset
+fn test_split_resets() {
+    let record = |millisecs: u32| {
+        let mut record = vec![0u8; size_of::<Tick>()];
+        record[..4].copy_from_slice(&millisecs.to_be_bytes());
+        record
+    };
+    let mut buf: Vec<u8> = [3_000_000, 2_999_000, 10, 3_500_000, 5].into_iter().flat_map(record).collect();
+    let resets = millisecs_resets(&parse_ticks(&buf).unwrap());
+    assert_eq!(resets, vec![2, 4]);
+    assert_eq!(split_resets(&mut buf), 2);
+    let millisecs: Vec<u32> = parse_ticks(&buf).unwrap().iter().map(|tick| tick.millisecs).collect();
+    assert_eq!(millisecs, vec![3_000_000, 2_999_000, 3_600_010, 7_100_000, 7_200_005]);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that byte-swapped records are detected and regular ones pass
+fn test_check_endianness() {
+    let buf = decompress_bi5(&include_bytes!("../test/test.bi5")[..]).unwrap();
+    assert!(check_endianness(&buf).is_ok());
+    let mut swapped = buf.clone();
+    for record in swapped.chunks_exact_mut(4) {
+        record.reverse();
+    }
+    assert!(matches!(check_endianness(&swapped), Err(Bi5Error::Endianness)));
+    assert!(check_endianness(&[]).is_ok());
+}
+
+#[test]
+/// A header announcing more than the addressable size is rejected up front
+fn test_decompress_too_large() {
+    let mut header = vec![0x5d, 0, 0, 0x40, 0];
+    header.extend_from_slice(&(u64::MAX - 1).to_le_bytes());
+    match decompress(&header[..]) {
+        Err(Bi5Error::Decompress(msg)) => assert!(msg.contains("exceeds the limit"), "{}", msg),
+        other => panic!("{:?}", other.map(|buf| buf.len())),
+    }
+}
+
+#[test]
+/// Test that the header size pre-sizes the buffer and reports truncation
+fn test_lzma_unpacked_size() {
+    let bytes = include_bytes!("../test/test.bi5");
+    assert_eq!(lzma_unpacked_size(bytes), Some(10412 * 20));
+    assert_eq!(lzma_unpacked_size(&bytes[..12]), None);
+    assert!(decompress(&bytes[..]).unwrap().capacity() >= 10412 * 20);
+    match decompress(&bytes[..bytes.len() / 2]) {
+        Err(Bi5Error::Decompress(msg)) => assert!(msg.contains("truncated"), "{}", msg),
+        other => panic!("{:?}", other.map(|buf| buf.len())),
+    }
+}
+
+#[test]
+/// Test that the prefix matches the full decode, also for streams shorter than the prefix
+fn test_decompress_prefix() {
+    let bytes = include_bytes!("../test/test.bi5");
+    let full = decompress(&bytes[..]).unwrap();
+    assert_eq!(decompress_prefix(&bytes[..], 20).unwrap(), full[..20]);
+    let single = include_bytes!("../test/single.bi5");
+    assert_eq!(decompress_prefix(&single[..], 100).unwrap().len(), 20);
+    assert!(decompress_prefix(&b"garbage"[..], 20).is_err());
+    assert!(decompress_prefix(&b""[..], 20).unwrap().is_empty());
+}
@@ -0,0 +1,105 @@
+//! Comparing the ticks of two bi5 files, see `diff_bi5`.
+
+use std::path::Path;
+use anyhow::Error;
+use crate::{Tick, read_bi5_file};
+
+/// A difference between the ticks of two files `a` and `b`
+///
+/// `index_a` and `index_b` are the positions in `a` and `b`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TickDiff {
+    /// The tick is only in `b`
+    Added { index_b: usize, tick: Tick },
+    /// The tick is only in `a`
+    Removed { index_a: usize, tick: Tick },
+    /// Ticks at the same position (or time) differ
+    Changed { index_a: usize, index_b: usize, a: Tick, b: Tick },
+}
+
+/// Compare the ticks of the bi5 files `a` and `b`
+///
+/// The ticks are aligned by `millisecs`: ticks with the same time are paired
+/// in order and compared, the remaining ones are reported as `Removed` or
+/// `Added`. Only if both files have exactly the same times are they compared
+/// position by position. The first entry is the divergence point.
+/// An empty result means the files hold the same ticks.
+///
+/// ```
+/// use bi5::diff_bi5;
+/// assert!(diff_bi5("test/test.bi5", "test/test.bi5").unwrap().is_empty());
+/// ```
+pub fn diff_bi5<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q) -> Result<Vec<TickDiff>, Error> {
+    let a = read_bi5_file(a.as_ref(), None)?;
+    let b = read_bi5_file(b.as_ref(), None)?;
+    Ok(diff_ticks(&a, &b))
+}
+
+/// `diff_bi5` on decoded ticks
+fn diff_ticks(a: &[Tick], b: &[Tick]) -> Vec<TickDiff> {
+    let changed = |i: usize, j: usize| TickDiff::Changed { index_a: i, index_b: j, a: a[i], b: b[j] };
+    if a.iter().map(|t| t.millisecs).eq(b.iter().map(|t| t.millisecs)) {
+        return (0..a.len()).filter(|&i| a[i] != b[i]).map(|i| changed(i, i)).collect();
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut diffs = Vec::new();
+    while i < a.len() || j < b.len() {
+        match (a.get(i), b.get(j)) {
+            (Some(x), Some(y)) if x.millisecs == y.millisecs => {
+                if x != y { diffs.push(changed(i, j)); }
+                i += 1;
+                j += 1;
+            }
+            (Some(x), Some(y)) if x.millisecs > y.millisecs => {
+                diffs.push(TickDiff::Added { index_b: j, tick: *y });
+                j += 1;
+            }
+            (Some(x), _) => {
+                diffs.push(TickDiff::Removed { index_a: i, tick: *x });
+                i += 1;
+            }
+            (None, Some(y)) => {
+                diffs.push(TickDiff::Added { index_b: j, tick: *y });
+                j += 1;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    diffs
+}
+
+#[test]
+/// A changed price compares by position, dropped and added ticks are aligned by time
+fn test_diff_ticks() {
+    let raw = crate::decode::decompress(std::fs::File::open("test/test.bi5").unwrap()).unwrap();
+    let ticks = crate::parse_ticks(&raw).unwrap();
+    let mut changed = ticks.clone();
+    changed[5].bid += 1;
+    let diffs = diff_ticks(&ticks, &changed);
+    assert_eq!(diffs, vec![TickDiff::Changed { index_a: 5, index_b: 5, a: ticks[5], b: changed[5] }]);
+
+    let mut shorter = changed.clone();
+    shorter.remove(100);
+    let diffs = diff_ticks(&ticks, &shorter);
+    assert_eq!(diffs.len(), 2);
+    assert!(matches!(diffs[0], TickDiff::Changed { index_a: 5, .. }));
+    assert_eq!(diffs[1], TickDiff::Removed { index_a: 100, tick: ticks[100] });
+    let diffs = diff_ticks(&shorter[..10], &ticks[..12]);
+    assert!(matches!(diffs[..], [
+        TickDiff::Changed { .. },
+        TickDiff::Added { index_b: 10, .. },
+        TickDiff::Added { index_b: 11, .. },
+    ]));
+
+    // same length, one tick dropped and another added
+    let mut shifted = ticks[..20].to_vec();
+    shifted.remove(3);
+    let mut added = ticks[19];
+    added.millisecs += 1;
+    shifted.push(added);
+    let diffs = diff_ticks(&ticks[..20], &shifted);
+    assert_eq!(diffs, vec![
+        TickDiff::Removed { index_a: 3, tick: ticks[3] },
+        TickDiff::Added { index_b: 19, tick: added },
+    ]);
+}
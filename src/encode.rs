@@ -0,0 +1,78 @@
+//! Encoding ticks as bi5, see `encode_bi5` and `write_bi5_file`.
+//!
+//! The records are the big-endian layout `Tick` is read from, compressed
+//! with LZMA and the decompressed size in the header like Dukascopy files.
+
+use std::io::Cursor;
+#[cfg(feature = "fs")]
+use std::path::Path;
+#[cfg(feature = "fs")]
+use anyhow::Error;
+use lzma_rs::compress::{Options, UnpackedSize};
+use crate::Tick;
+
+impl Tick {
+    /// The 20 byte big-endian record of the tick, the inverse of
+    /// `Tick::try_from(&[u8])`
+    /// ```
+    /// use bi5::Tick;
+    /// let tick = Tick { millisecs: 1, ask: 3, bid: 2, asksize: 0.5, bidsize: 1.0 };
+    /// assert_eq!(Tick::try_from(&tick.to_be_bytes()[..]).unwrap(), tick);
+    /// ```
+    pub fn to_be_bytes(&self) -> [u8; 20] {
+        let mut record = [0u8; 20];
+        let words = [
+            self.millisecs.to_be_bytes(), self.ask.to_be_bytes(), self.bid.to_be_bytes(),
+            self.asksize.to_be_bytes(), self.bidsize.to_be_bytes(),
+        ];
+        for (chunk, word) in record.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word);
+        }
+        record
+    }
+}
+
+/// Encode `ticks` as the content of a bi5 file
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = from_compressed_bytes(&std::fs::read("test/test.bi5").unwrap()).unwrap();
+/// assert_eq!(from_compressed_bytes(&encode_bi5(&ticks)).unwrap(), ticks);
+/// ```
+pub fn encode_bi5(ticks: &[Tick]) -> Vec<u8> {
+    let buf: Vec<u8> = ticks.iter().flat_map(Tick::to_be_bytes).collect();
+    let options = Options { unpacked_size: UnpackedSize::WriteToHeader(Some(buf.len() as u64)) };
+    let mut compressed = Vec::new();
+    lzma_rs::lzma_compress_with_options(&mut Cursor::new(buf), &mut compressed, &options)
+        .expect("compressing to a Vec can't fail");
+    compressed
+}
+
+/// Write `ticks` as bi5 file, readable by `read_bi5_file` and other
+/// Dukascopy tooling
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = read_bi5_file("test/test.bi5", None).unwrap();
+/// let path = std::env::temp_dir().join(format!("bi5-doc-{}.bi5", std::process::id()));
+/// write_bi5_file(&path, &ticks).unwrap();
+/// assert_eq!(read_bi5_file(&path, None).unwrap(), ticks);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn write_bi5_file<P: AsRef<Path>>(path: P, ticks: &[Tick]) -> Result<(), Error> {
+    std::fs::write(path, encode_bi5(ticks))?;
+    Ok(())
+}
+
+#[test]
+/// Test that the records of test.bi5 encode to the same decompressed bytes, with the size in the header
+fn test_encode_bi5() {
+    let buf = crate::decode::decompress(&include_bytes!("../test/test.bi5")[..]).unwrap();
+    let ticks = crate::parse_ticks(&buf).unwrap();
+    assert_eq!(ticks.iter().flat_map(Tick::to_be_bytes).collect::<Vec<_>>(), buf);
+    let encoded = encode_bi5(&ticks);
+    assert_eq!(crate::decode::lzma_unpacked_size(&encoded), Some(buf.len() as u64));
+    assert_eq!(crate::decode::decompress(&encoded[..]).unwrap(), buf);
+    assert_eq!(crate::from_compressed_bytes(&encode_bi5(&[])).unwrap(), vec![]);
+}
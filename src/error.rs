@@ -0,0 +1,174 @@
+//! Error type of the crate.
+//!
+//! Most functions return `anyhow::Error`, into which `Bi5Error` converts.
+//! The fallible iterators yield a `Bi5Error` so callers can match on the
+//! kind of failure. Data that lenient options drop or change is reported as
+//! a `Notice`, nothing is printed by the library.
+
+use std::{fmt, io};
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
+
+/// Errors while reading bi5 files
+///
+/// New kinds of failure may be added, so matches need a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Bi5Error {
+    /// I/O error while opening or reading
+    Io(io::Error),
+    /// The LZMA/xz stream could not be decompressed
+    Decompress(String),
+    /// The decompressed length is not a multiple of the record size
+    Length {
+        /// Decompressed length in bytes
+        len: usize,
+        /// Record size in bytes
+        record: usize,
+    },
+    /// The decompressed buffer is no whole number of records and starts with
+    /// a compression header, the file was probably compressed twice, see
+    /// `Bi5Builder::decompress_twice`
+    DoubleCompressed {
+        /// Decompressed length in bytes
+        len: usize,
+        /// Format of the inner header, e.g. `gzip`
+        format: &'static str,
+    },
+    /// A record could not be parsed
+    Parse(String),
+    /// The records look little-endian, see `Bi5Builder::check_endianness`
+    Endianness,
+    /// A tick's `millisecs` exceed the bound of `Bi5Builder::check_max_millisecs`
+    MillisecsOutOfRange {
+        /// Position of the tick in the file
+        index: usize,
+        /// `millisecs` of the tick
+        millisecs: u32,
+        /// The configured bound
+        max: u32,
+    },
+    /// `TickBuilder` fields out of range
+    InvalidTick(String),
+    /// A point value of zero, see `PointValue::new`
+    ZeroPointValue,
+    /// The path is neither a file nor a directory
+    #[cfg(feature = "fs")]
+    InvalidPath(PathBuf),
+    /// The path is a special file other than a FIFO, e.g. a socket
+    #[cfg(feature = "fs")]
+    SpecialFile {
+        path: PathBuf,
+        /// e.g. `socket`
+        kind: &'static str,
+    },
+    /// An entry of a directory walk could not be read
+    #[cfg(feature = "fs")]
+    Walk(walkdir::Error),
+}
+
+impl fmt::Display for Bi5Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Bi5Error::Io(e) => write!(f, "{}", e),
+            Bi5Error::Decompress(msg) => write!(f, "{}", msg),
+            Bi5Error::Length { len, record } => 
+                write!(f, "Decompressed buffer length {} is not a multiple of {}", len, record),
+            Bi5Error::DoubleCompressed { len, format } =>
+                write!(f, "Decompressed buffer length {} is not a multiple of 20 and starts with a {} header, \
+                           the file is probably compressed twice; decompress the inner layer first", len, format),
+            Bi5Error::Parse(msg) => write!(f, "{}", msg),
+            Bi5Error::Endianness => 
+                write!(f, "Tick times look byte-swapped, the file is probably little-endian instead of big-endian"),
+            Bi5Error::MillisecsOutOfRange { index, millisecs, max } =>
+                write!(f, "Tick {} has millisecs {} beyond {}, the decoding is probably misaligned", index, millisecs, max),
+            Bi5Error::InvalidTick(msg) => write!(f, "Invalid tick: {}", msg),
+            Bi5Error::ZeroPointValue => write!(f, "Point value must not be zero"),
+            #[cfg(feature = "fs")]
+            Bi5Error::InvalidPath(path) => write!(f, "{} must be file or dir", path.to_string_lossy()),
+            #[cfg(feature = "fs")]
+            Bi5Error::SpecialFile { path, kind } =>
+                write!(f, "{} is a {}, not a regular file or directory", path.to_string_lossy(), kind),
+            #[cfg(feature = "fs")]
+            Bi5Error::Walk(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Bi5Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Bi5Error::Io(e) => Some(e),
+            #[cfg(feature = "fs")]
+            Bi5Error::Walk(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Bi5Error {
+    fn from(e: io::Error) -> Self {
+        Bi5Error::Io(e)
+    }
+}
+
+impl From<binread::Error> for Bi5Error {
+    fn from(e: binread::Error) -> Self {
+        Bi5Error::Parse(e.to_string())
+    }
+}
+
+/// Data dropped or changed by a lenient option of `Bi5Builder`, passed to the
+/// callback of `Bi5Builder::on_notice`
+///
+/// New kinds may be added, so matches need a wildcard arm.
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Notice {
+    /// `ignore_trailing_partial` dropped the trailing bytes of a file
+    TrailingBytes {
+        path: PathBuf,
+        /// Number of dropped bytes, 1 to 19
+        bytes: usize,
+    },
+    /// `max_millisecs` dropped ticks of a file
+    ImplausibleTicks {
+        path: PathBuf,
+        /// Number of dropped ticks
+        ticks: usize,
+        /// The configured bound
+        max: u32,
+    },
+    /// `split_resets` moved the ticks after each reset of a file into the
+    /// next hour
+    MillisecsResets {
+        path: PathBuf,
+        /// Number of resets
+        resets: usize,
+    },
+    /// `skip_walk_errors` skipped an entry the walk couldn't read
+    WalkError(walkdir::Error),
+}
+
+#[cfg(feature = "fs")]
+impl fmt::Display for Notice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Notice::TrailingBytes { path, bytes } =>
+                write!(f, "{}: dropped {} trailing bytes", path.to_string_lossy(), bytes),
+            Notice::ImplausibleTicks { path, ticks, max } =>
+                write!(f, "{}: dropped {} ticks beyond {} ms", path.to_string_lossy(), ticks, max),
+            Notice::MillisecsResets { path, resets } =>
+                write!(f, "{}: split {} millisecs resets into hours", path.to_string_lossy(), resets),
+            Notice::WalkError(e) => write!(f, "Skipping {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<walkdir::Error> for Bi5Error {
+    fn from(e: walkdir::Error) -> Self {
+        Bi5Error::Walk(e)
+    }
+}
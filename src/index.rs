@@ -0,0 +1,36 @@
+//! Sidecar index files with the tick count of a bi5 file.
+//!
+//! The sidecar of `14h_ticks.bi5` is `14h_ticks.bi5.idx`, a single line
+//! `<ticks> <compressed size> <options>`. The size detects a bi5 file that
+//! was replaced after the index was written. The options are those that change
+//! the count, e.g. `partial=0 max=- trim=0,0`, a count taken with other options
+//! doesn't apply. Such sidecars are ignored.
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Path of the sidecar of `path`
+pub(crate) fn index_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".idx");
+    PathBuf::from(name)
+}
+
+/// Tick count from the sidecar of `path`, `None` if missing, stale or
+/// written with other `options`
+pub(crate) fn read_index(path: &Path, options: &str) -> Option<usize> {
+    let content = fs::read_to_string(index_path(path)).ok()?;
+    let mut fields = content.trim_end().splitn(3, ' ');
+    let ticks: usize = fields.next()?.parse().ok()?;
+    let size: u64 = fields.next()?.parse().ok()?;
+    (fs::metadata(path).ok()?.len() == size && fields.next() == Some(options)).then_some(ticks)
+}
+
+/// Write the sidecar of `path`, `options` as in `read_index`
+pub(crate) fn write_index(path: &Path, ticks: usize, options: &str) -> io::Result<()> {
+    let size = fs::metadata(path)?.len();
+    fs::write(index_path(path), format!("{} {} {}\n", ticks, size, options))
+}
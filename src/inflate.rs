@@ -0,0 +1,306 @@
+//! Raw DEFLATE (RFC 1951) decoding for the zip and tar.gz readers.
+//!
+//! The decoder is written without further dependencies and inflates while it
+//! is read, keeping only the window of the last 32 KiB of output. The CRC-32
+//! of the output is kept for the container to compare with its checksum.
+
+use std::io::{self, Read};
+use crate::Bi5Error;
+
+const LEN_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LEN_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// Order of the code length code lengths of a dynamic block
+const CLEN_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+/// Distances reach back at most this far
+const WINDOW: usize = 1 << 15;
+
+/// CRC-32 (IEEE 802.3) lookup table, reflected polynomial
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+/// CRC-32 of `data` continuing from `crc`, as in gzip and zip
+pub(crate) fn crc32(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |c, &b| CRC_TABLE[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8))
+}
+
+fn bad(msg: &str) -> Bi5Error {
+    Bi5Error::Decompress(format!("deflate: {}", msg))
+}
+
+fn corrupt() -> Bi5Error {
+    bad("corrupt stream")
+}
+
+/// Canonical Huffman code, decoded bit by bit
+struct Huffman {
+    /// number of codes of each length
+    count: [u16; 16],
+    /// symbols ordered by code
+    symbol: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, Bi5Error> {
+        let mut count = [0u16; 16];
+        for &len in lengths {
+            count[len as usize] += 1;
+        }
+        count[0] = 0;
+        let mut left: i32 = 1;
+        let mut offset = [0u16; 16];
+        for len in 1..16 {
+            left = (left << 1) - count[len] as i32;
+            if left < 0 {
+                return Err(corrupt());
+            }
+            if len < 15 {
+                offset[len + 1] = offset[len] + count[len];
+            }
+        }
+        let mut symbol = vec![0u16; lengths.len()];
+        for (s, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbol[offset[len as usize] as usize] = s as u16;
+                offset[len as usize] += 1;
+            }
+        }
+        Ok(Huffman { count, symbol })
+    }
+}
+
+/// LSB-first bit reader
+struct Bits<R> {
+    inner: R,
+    buf: u32,
+    len: u32,
+}
+
+impl<R: Read> Bits<R> {
+    fn byte(&mut self) -> Result<u8, Bi5Error> {
+        let mut byte = [0u8; 1];
+        self.inner.read_exact(&mut byte).map_err(|_| bad("truncated stream"))?;
+        Ok(byte[0])
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, Bi5Error> {
+        while self.len < n {
+            self.buf |= (self.byte()? as u32) << self.len;
+            self.len += 8;
+        }
+        let x = self.buf & ((1u64 << n) - 1) as u32;
+        self.buf = self.buf.checked_shr(n).unwrap_or(0);
+        self.len -= n;
+        Ok(x)
+    }
+
+    fn decode(&mut self, h: &Huffman) -> Result<u16, Bi5Error> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= self.bits(1)? as i32;
+            let count = h.count[len] as i32;
+            if code - first < count {
+                return Ok(h.symbol[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(corrupt())
+    }
+}
+
+enum Block {
+    /// expecting a block header
+    Header,
+    /// remaining bytes of a stored block
+    Stored(usize),
+    /// literal/length and distance codes
+    Codes(Huffman, Huffman),
+    /// after the last block
+    Done,
+}
+
+/// Streaming deflate decoder
+pub(crate) struct Inflate<R> {
+    bits: Bits<R>,
+    /// output with at least `WINDOW` bytes of history before `pos`
+    out: Vec<u8>,
+    /// first byte of `out` not yet read
+    pos: usize,
+    block: Block,
+    last: bool,
+    /// CRC-32 and length of the output read so far
+    crc: u32,
+    size: u64,
+}
+
+impl<R: Read> Inflate<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Inflate {
+            bits: Bits { inner, buf: 0, len: 0 }, out: Vec::new(), pos: 0, block: Block::Header, last: false,
+            crc: 0, size: 0,
+        }
+    }
+
+    /// Reads the rest of the stream, returns the CRC-32 and length of the
+    /// whole output and the inner reader, positioned after the last block
+    #[cfg(feature = "tar")]
+    pub(crate) fn finish(&mut self) -> io::Result<(u32, u64, &mut R)> {
+        io::copy(self, &mut io::sink())?;
+        // the bit buffer holds no more than the rest of the last byte
+        Ok((self.crc, self.size, &mut self.bits.inner))
+    }
+
+    /// Decodes a block header, a chunk of a stored block or one symbol
+    fn step(&mut self) -> Result<(), Bi5Error> {
+        match &self.block {
+            Block::Done => {}
+            Block::Header if self.last => self.block = Block::Done,
+            Block::Header => {
+                self.last = self.bits.bits(1)? == 1;
+                self.block = match self.bits.bits(2)? {
+                    0 => {
+                        self.bits.buf = 0;
+                        self.bits.len = 0;
+                        let len = self.bits.bits(16)? as u16;
+                        if self.bits.bits(16)? as u16 != !len {
+                            return Err(corrupt());
+                        }
+                        Block::Stored(len as usize)
+                    }
+                    1 => {
+                        let mut lengths = [0u8; 288];
+                        lengths[..144].fill(8);
+                        lengths[144..256].fill(9);
+                        lengths[256..280].fill(7);
+                        lengths[280..].fill(8);
+                        Block::Codes(Huffman::new(&lengths)?, Huffman::new(&[5; 30])?)
+                    }
+                    2 => self.dynamic()?,
+                    _ => return Err(corrupt()),
+                };
+            }
+            Block::Stored(0) => self.block = Block::Header,
+            Block::Stored(n) => {
+                let n = *n;
+                self.out.push(self.bits.byte()?);
+                self.block = Block::Stored(n - 1);
+            }
+            Block::Codes(lit, dist) => {
+                let symbol = self.bits.decode(lit)? as usize;
+                if symbol < 256 {
+                    self.out.push(symbol as u8);
+                } else if symbol == 256 {
+                    self.block = Block::Header;
+                } else {
+                    let i = symbol - 257;
+                    if i >= LEN_BASE.len() {
+                        return Err(corrupt());
+                    }
+                    let len = LEN_BASE[i] as usize + self.bits.bits(LEN_EXTRA[i] as u32)? as usize;
+                    let d = self.bits.decode(dist)? as usize;
+                    if d >= DIST_BASE.len() {
+                        return Err(corrupt());
+                    }
+                    let d = DIST_BASE[d] as usize + self.bits.bits(DIST_EXTRA[d] as u32)? as usize;
+                    if d > self.out.len() {
+                        return Err(corrupt());
+                    }
+                    // the copy may overlap its own output
+                    for _ in 0..len {
+                        self.out.push(self.out[self.out.len() - d]);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the code tables of a dynamic block
+    fn dynamic(&mut self) -> Result<Block, Bi5Error> {
+        let nlen = self.bits.bits(5)? as usize + 257;
+        let ndist = self.bits.bits(5)? as usize + 1;
+        let ncode = self.bits.bits(4)? as usize + 4;
+        let mut clens = [0u8; 19];
+        for &i in &CLEN_ORDER[..ncode] {
+            clens[i] = self.bits.bits(3)? as u8;
+        }
+        let clen = Huffman::new(&clens)?;
+        let mut lengths = Vec::with_capacity(nlen + ndist);
+        while lengths.len() < nlen + ndist {
+            let (len, repeat) = match self.bits.decode(&clen)? {
+                symbol @ 0..=15 => (symbol as u8, 1),
+                16 => (*lengths.last().ok_or_else(corrupt)?, 3 + self.bits.bits(2)?),
+                17 => (0, 3 + self.bits.bits(3)?),
+                _ => (0, 11 + self.bits.bits(7)?),
+            };
+            lengths.extend(std::iter::repeat_n(len, repeat as usize));
+        }
+        if lengths.len() > nlen + ndist {
+            return Err(corrupt());
+        }
+        Ok(Block::Codes(Huffman::new(&lengths[..nlen])?, Huffman::new(&lengths[nlen..])?))
+    }
+}
+
+impl<R: Read> Read for Inflate<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.out.len() - self.pos < buf.len() && !matches!(self.block, Block::Done) {
+            self.step().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.crc = crc32(self.crc, &buf[..n]);
+        self.size += n as u64;
+        self.pos += n;
+        if self.pos > 2 * WINDOW {
+            let drop = self.pos.min(self.out.len() - WINDOW);
+            self.out.drain(..drop);
+            self.pos -= drop;
+        }
+        Ok(n)
+    }
+}
+
+/// Deflate stream of stored blocks
+#[cfg(test)]
+pub(crate) fn write_stored_deflate(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let chunks: Vec<&[u8]> = if data.is_empty() { vec![data] } else { data.chunks(0xffff).collect() };
+    for (i, chunk) in chunks.iter().enumerate() {
+        out.push((i + 1 == chunks.len()) as u8);
+        out.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(!(chunk.len() as u16)).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+    out
+}
+
+#[test]
+/// CRC-32 check value and continuation over chunks
+fn test_crc32() {
+    assert_eq!(crc32(0, b"123456789"), 0xcbf43926);
+    assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xcbf43926);
+    assert_eq!(crc32(0, b""), 0);
+}
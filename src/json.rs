@@ -0,0 +1,256 @@
+//! Minimal JSON parser for the metadata read by `PointTable::from_dukascopy_json`,
+//! and numbers and strings for `Tick::to_json`.
+
+use crate::Bi5Error;
+
+/// A parsed JSON value, objects keep their key order
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Value of `key` if this is an object containing it
+    pub(crate) fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The number, or a string holding one like `"0.0001"`
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(x) => Some(*x),
+            Json::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// `x` as JSON number in its shortest round-trip form, `null` if not finite
+pub(crate) fn number(x: f32) -> String {
+    if x.is_finite() { x.to_string() } else { "null".to_string() }
+}
+
+/// `s` as JSON string literal
+pub(crate) fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Nesting limit, keeps the recursion of malformed input off the stack limit
+const MAX_DEPTH: usize = 128;
+
+/// Parse the JSON document `s`, trailing whitespace is allowed
+pub(crate) fn parse(s: &str) -> Result<Json, Bi5Error> {
+    let mut parser = Parser { s: s.as_bytes(), pos: 0 };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.pos < parser.s.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: &str) -> Bi5Error {
+        Bi5Error::Parse(format!("invalid JSON at byte {}: {}", self.pos, msg))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.s.len() && matches!(self.s[self.pos], b' ' | b'\t' | b'\n' | b'\r') {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.s.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), Bi5Error> {
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("expected '{}'", c as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, Bi5Error> {
+        if !self.s[self.pos..].starts_with(word.as_bytes()) {
+            return Err(self.error("unexpected character"));
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, Bi5Error> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        match self.peek() {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Json, Bi5Error> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a key"));
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            members.push((key, self.value(depth + 1)?));
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Json, Bi5Error> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value(depth + 1)?);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, Bi5Error> {
+        let start = self.pos;
+        while self.pos < self.s.len() && matches!(self.s[self.pos], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+            self.pos += 1;
+        }
+        // the bytes are ASCII, so this can't split a character
+        let text = std::str::from_utf8(&self.s[start..self.pos]).unwrap();
+        text.parse().map(Json::Number).map_err(|_| self.error(&format!("invalid number {:?}", text)))
+    }
+
+    /// Four hex digits of a `\u` escape
+    fn hex4(&mut self) -> Result<u32, Bi5Error> {
+        let digits = self.s.get(self.pos..self.pos + 4).ok_or_else(|| self.error("truncated \\u escape"))?;
+        let code = std::str::from_utf8(digits).ok().and_then(|d| u32::from_str_radix(d, 16).ok())
+            .ok_or_else(|| self.error("invalid \\u escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn string(&mut self) -> Result<String, Bi5Error> {
+        self.expect(b'"')?;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            while self.pos < self.s.len() && !matches!(self.s[self.pos], b'"' | b'\\') {
+                self.pos += 1;
+            }
+            // `"` and `\` never occur inside a multi-byte character
+            out.push_str(std::str::from_utf8(&self.s[start..self.pos]).map_err(|_| self.error("invalid UTF-8"))?);
+            match self.s.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escape = *self.s.get(self.pos).ok_or_else(|| self.error("unexpected end"))?;
+                    self.pos += 1;
+                    match escape {
+                        b'"' => out.push('"'),
+                        b'\\' => out.push('\\'),
+                        b'/' => out.push('/'),
+                        b'b' => out.push('\u{8}'),
+                        b'f' => out.push('\u{c}'),
+                        b'n' => out.push('\n'),
+                        b'r' => out.push('\r'),
+                        b't' => out.push('\t'),
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&code) && self.s[self.pos..].starts_with(b"\\u") {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            out.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                _ => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+}
+
+#[test]
+/// Test nesting, escapes including a surrogate pair, and error positions
+fn test_parse() {
+    let json = parse(r#" {"a": [1, -2.5e1, true, null], "bé": "x\"\ud83d\ude00\u00e9", "c": {}} "#).unwrap();
+    assert_eq!(json.get("a"), Some(&Json::Array(vec![Json::Number(1.0), Json::Number(-25.0), Json::Bool(true), Json::Null])));
+    assert_eq!(json.get("bé").and_then(Json::as_str), Some("x\"😀é"));
+    assert_eq!(json.get("c"), Some(&Json::Object(vec![])));
+    assert_eq!(Json::String("0.01".into()).as_f64(), Some(0.01));
+    for bad in ["", "{", "[1,]", "{\"a\" 1}", "\"abc", "1 2", "tru"] {
+        assert!(parse(bad).is_err(), "{}", bad);
+    }
+    assert_eq!(parse("[1, x]").unwrap_err().to_string(), "invalid JSON at byte 4: unexpected character");
+    assert!(parse(&"[".repeat(1000)).is_err());
+    assert_eq!(parse(&string("a\"\\\n")).unwrap(), Json::String("a\"\\\n".into()));
+}
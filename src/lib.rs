@@ -3,44 +3,151 @@
 //! The crate provides:
 //! - `Tick` struct
 //! - `read_bi5_file` function returning a `Vec<Tick>`
-//! - `Bi5` struct that provides an iterator `Bi5Iter`
+//! - `read_bi5_file_dated` function returning a `Vec<(NaiveDateTime, Tick)>`
+//! - `read_bi5_file_into` function appending to a caller-provided `Vec<Tick>`
+//! - `read_bi5_columnar` function returning the ticks as `TicksColumnar` columns
+//! - `read_raw_bin` function reading uncompressed records
+//! - `diff_bi5` function comparing the ticks of two files
+//! - `write_compact` and `read_compact` for a delta + varint storage format
+//! - `Bi5` struct that provides an iterator `Bi5Iter` and a fallible `TryIter`
+//! - `Bi5Error` error type, and `Notice` for data the lenient options drop
+//! - `decompress_bi5`, `parse_ticks`, `from_compressed_bytes` and `decode_bytes` for in-memory buffers
+//! - `read_bi5_reader` function decoding the ticks of any `Read`
+//! - `TickStream` iterator decoding the ticks of an LZMA stream incrementally
+//! - `encode_bi5` and `write_bi5_file` writing ticks as bi5
+//! - `read_csv_ticks` reading the csv of catbi5 back into ticks
+//! - `Candle` struct, `read_candle_bi5_file` and `parse_candle_name` for Dukascopy candle files
+//! - `testing` module with fixture helpers (feature `testing`)
+//! - `TickProto` protobuf messages of ticks (feature `proto`)
+//!
+//! The filesystem parts (`Bi5`, `read_bi5_file`, ...) are behind the default
+//! `fs` feature. Without it the byte-buffer path also builds for
+//! `wasm32-unknown-unknown`.
 //!
 //! Example usage: 
 //! ```
+//! # #[cfg(feature = "fs")] {
 //! use bi5::Bi5;
 //! let bi5 = Bi5::new("test/test.bi5", None);
 //! for (date_time, tick) in bi5.iter().expect("File error") {
 //!     println!("{},{}", date_time, tick);
 //! }
+//! # }
 //! ```
 //! 
 //! With iterator:
 //! ```
+//! # #[cfg(feature = "fs")] {
 //! use bi5::Bi5;
 //! let bi5 = Bi5::new("test/test.bi5", None);
 //! for (date_time, tick) in bi5.iter().expect("File error") {
 //!     println!("{},{}", date_time, tick);
 //! }
+//! # }
 //! ```
 
+use std::fmt;
+#[cfg(feature = "fs")]
 use std::{
     path::{Path, PathBuf},
     fs::File,
-    io::{Cursor, BufReader},
+    io::Cursor,
     mem::size_of,
     ffi::OsStr,
-    fmt,
+    ops::Range,
+    iter::FusedIterator,
+    sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::{sync_channel, Receiver}},
+    collections::{HashMap, VecDeque},
+    time::SystemTime,
+    thread,
 };
-use chrono::{NaiveDate, NaiveTime, NaiveDateTime, Duration};
+use chrono::{Duration, NaiveDate, NaiveTime, NaiveDateTime};
+#[cfg(feature = "fs")]
+use chrono::Timelike;
+#[cfg(feature = "fs")]
 use walkdir::{WalkDir};
 use binread::BinRead;
-use lzma_rs::lzma_decompress;
+#[cfg(feature = "fs")]
 use anyhow::{anyhow, Error};
 
+mod error;
+pub use error::Bi5Error;
+#[cfg(feature = "fs")]
+pub use error::Notice;
+mod decode;
+pub use decode::{Codec, decompress_bi5, parse_ticks, from_compressed_bytes, read_bi5_reader, decode_bytes, TickStream, millisecs_resets};
+mod encode;
+pub use encode::encode_bi5;
+#[cfg(feature = "fs")]
+pub use encode::write_bi5_file;
+mod candle;
+pub use candle::{Candle, Layout, detect_layout, parse_candles, Timeframe, CandleName, parse_candle_name};
+#[cfg(feature = "fs")]
+pub use candle::read_candle_bi5_file;
+mod analysis;
+pub use analysis::{minmax_decimate, MinMaxDecimate, spread_histogram, spread_percentile, PriceSeries, to_price_series, rolling, Rolling, count_by_interval, align, Align, duplicate_timestamps, resampled_mid_series};
+mod bars;
+pub use bars::{Bar, time_bars, volume_bars, imbalance_bars};
+mod time_format;
+pub use time_format::TimeFormat;
+mod json;
+mod points;
+pub use points::{PointTable, PointValue};
+mod csv;
+pub use csv::{read_csv_ticks, CsvTicks, TickRecord};
+mod adapters;
+pub use adapters::{TickIteratorExt, PriceChanges, SizeChanges, LastPerMs, ClampToRange, FlagAnomalies, DropOneSided, TickDelta, Deltas, undelta, Undelta, to_rows, ROW_COLUMNS, Side, SplitSides, Direction, WithTickDirection, WithPriceDeltas, NormalizeCrossed, WithCumulativeVolume, WithSequence};
+#[cfg(feature = "fs")]
+mod coverage;
+#[cfg(feature = "fs")]
+mod index;
+#[cfg(feature = "fs")]
+pub use coverage::CoverageOptions;
+#[cfg(feature = "fs")]
+mod cache;
+#[cfg(feature = "fs")]
+pub use cache::DecodeCache;
+#[cfg(feature = "fs")]
+mod audit;
+#[cfg(feature = "fs")]
+pub use audit::{AuditReport, FileAudit};
+#[cfg(feature = "fs")]
+mod diff;
+#[cfg(feature = "fs")]
+mod compact;
+#[cfg(feature = "fs")]
+mod partition;
+#[cfg(feature = "fs")]
+pub use partition::{Partition, PartitionWriter};
+#[cfg(feature = "fs")]
+pub use compact::{write_compact, read_compact};
+#[cfg(feature = "fs")]
+pub use diff::{diff_bi5, TickDiff};
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(any(feature = "zip", feature = "tar"))]
+mod inflate;
+#[cfg(feature = "zip")]
+mod zip;
+#[cfg(feature = "zip")]
+pub use zip::ZipIter;
+#[cfg(feature = "tar")]
+mod tar;
+#[cfg(feature = "tar")]
+pub use tar::TarGzIter;
+#[cfg(feature = "proto")]
+mod proto;
+#[cfg(feature = "proto")]
+pub use proto::{TickProto, tick_to_proto, TICK_PROTO_SCHEMA};
+
 /// `Tick` is the basic building block of a bi5 file.
-#[derive(BinRead, Debug, PartialEq)]
+#[derive(BinRead, Debug, Clone, Copy, PartialEq, Default)]
 pub struct Tick {
     /// Milliseconds since file start (usually encoded in the file path)
+    ///
+    /// The tick time is the file start plus `Duration::milliseconds` of this
+    /// value widened to `i64`, which can't overflow: even `u32::MAX` is only
+    /// about 49.7 days.
     #[br(big)]
     pub millisecs: u32,
     /// Ask price
@@ -57,243 +164,4109 @@ pub struct Tick {
     pub bidsize: f32,
 }
 
-impl fmt::Display for Tick {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{},{},{},{},{}", self.millisecs, self.bid, self.ask, self.bidsize, self.asksize)
+// The record length checks use `size_of::<Tick>()`, so the in-memory layout
+// must stay exactly one 20 byte record without padding.
+const _: () = assert!(std::mem::size_of::<Tick>() == 20);
+
+/// Number of milliseconds in one hour, the span covered by a regular bi5 file
+pub const HOUR_MILLISECS: u32 = 3_600_000;
+
+/// A sanity bound for `Bi5Builder::max_millisecs`: a few hours, well beyond
+/// the rollover seen in concatenated files
+pub const MAX_PLAUSIBLE_MILLISECS: u32 = 4 * HOUR_MILLISECS;
+
+impl Tick {
+    /// Returns true if `millisecs` lies beyond the hour covered by the file.
+    ///
+    /// Such ticks are suspect: they come from corrupt or concatenated multi-hour
+    /// files. Their absolute time is still computed correctly and may roll over
+    /// into the next hour or day.
+    pub fn is_out_of_hour(&self) -> bool {
+        self.millisecs >= HOUR_MILLISECS
     }
-}
 
-/// Represents a bi5 file or directory
-pub struct Bi5 {
-    path: PathBuf,
-    date_time: NaiveDateTime
-}
+    /// Absolute time of the tick in a file starting at `file_start`, the time
+    /// yielded by `Bi5Iter`
+    ///
+    /// Ticks beyond the hour roll over into the next hour, day or year.
+    /// ```
+    /// use bi5::Tick;
+    /// use chrono::NaiveDate;
+    /// let file_start = NaiveDate::from_ymd_opt(2022, 12, 31).unwrap().and_hms_opt(23, 0, 0).unwrap();
+    /// let tick = Tick { millisecs: 3_600_250, ..Tick::default() };
+    /// assert_eq!(tick.time(file_start).to_string(), "2023-01-01 00:00:00.250");
+    /// ```
+    pub fn time(&self, file_start: NaiveDateTime) -> NaiveDateTime {
+        file_start + Duration::milliseconds(self.millisecs as i64)
+    }
 
-/// Iterator over bi5 file or directories
-/// 
-/// ```
-/// use bi5::Bi5;
-/// let bi5 = Bi5::new("test/test.bi5", None);
-/// for (date_time, tick) in bi5.iter().expect("File error") {
-///     println!("{},{}", date_time, tick);
-/// }
-/// ```
-pub enum Bi5Iter {
-    File {
-        /// cursor over binary blob
-        cursor: Cursor<Vec<u8>>,
-        /// date_time base of this file 
-        date_time: NaiveDateTime,
-    },
-    Dir {
-        walk_dir: walkdir::IntoIter,
-        file_iter: Box<Bi5Iter>,
-        date_time: NaiveDateTime,
-    },
-    Empty
-}
+    /// Bid price scaled by `point_value`, see `Price::as_f64`
+    #[deprecated(note = "use bid_price().as_f64(..)")]
+    pub fn bid_f64(&self, point_value: f64) -> f64 {
+        self.bid as f64 / point_value
+    }
 
-/// Returns 0000-01-01T00:00:00
-fn zero_timestamp() -> NaiveDateTime {
-    NaiveDateTime::new(
-        NaiveDate::from_ymd_opt(0, 1, 1).unwrap(),
-        NaiveTime::from_num_seconds_from_midnight_opt(0, 0).unwrap()
-    )
-}
+    /// Ask price scaled by `point_value`, see `Price::as_f64`
+    #[deprecated(note = "use ask_price().as_f64(..)")]
+    pub fn ask_f64(&self, point_value: f64) -> f64 {
+        self.ask as f64 / point_value
+    }
 
-impl Bi5 {
+    /// `(bid, ask)` scaled by the integer `point_value`, e.g. `100000` for
+    /// `1.33117`, see `PointValue`, or `Bi5Error::ZeroPointValue` for 0
+    pub fn to_prices(&self, point_value: u32) -> Result<(f64, f64), Bi5Error> {
+        let point_value = PointValue::new(point_value)?.get() as f64;
+        Ok((self.bid_price().as_f64(point_value), self.ask_price().as_f64(point_value)))
+    }
 
-    /// Create `Bi5` representing a bi5 file or directory
-    /// ## Arguments
-    /// - `path` - File or directory path
-    /// - `date_time` - Optional datetime (only meaningful for file)
-    pub fn new<P:AsRef<Path>>(path: P, date_time: Option<NaiveDateTime>) -> Self {
-        Bi5 { 
-            path: path.as_ref().to_path_buf(),
-            date_time: date_time.unwrap_or(zero_timestamp())
+    /// The tick with bid and ask scaled by `point_value`, see `Price::as_f64`
+    ///
+    /// ```
+    /// use bi5::Tick;
+    /// let tick = Tick { millisecs: 1, ask: 133153, bid: 133117, asksize: 0.015, bidsize: 0.02 };
+    /// let scaled = tick.scaled(1e5);
+    /// assert_eq!((scaled.millisecs, scaled.bid, scaled.ask, scaled.bidsize), (1, 1.33117, 1.33153, 0.02));
+    /// ```
+    pub fn scaled(&self, point_value: f64) -> ScaledTick {
+        ScaledTick {
+            millisecs: self.millisecs,
+            bid: self.bid_price().as_f64(point_value),
+            ask: self.ask_price().as_f64(point_value),
+            bidsize: self.bidsize,
+            asksize: self.asksize,
         }
     }
 
-    /// Returns true if `Bi5` is a file
-    pub fn is_file(&self) -> bool {
-        self.path.is_file()
+    /// The tick with bid and ask converted from `from_point` to `to_point`
+    /// raw units per 1.0 (see `Price::as_f64`), e.g. from `1e5` to `1e4`
+    ///
+    /// Prices are rounded to the nearest integer, halves away from zero, so
+    /// `133115` becomes `13312`. Results beyond `u32::MAX` saturate to it.
+    /// The other fields are copied.
+    /// ```
+    /// use bi5::Tick;
+    /// let tick = Tick { millisecs: 1, ask: 133153, bid: 133115, asksize: 0.015, bidsize: 0.02 };
+    /// let rescaled = tick.rescale(1e5, 1e4);
+    /// assert_eq!((rescaled.bid, rescaled.ask), (13312, 13315));
+    /// assert_eq!(tick.rescale(1e5, 1e6).bid, 1331150);
+    /// ```
+    pub fn rescale(&self, from_point: f64, to_point: f64) -> Tick {
+        let rescale = |price: u32| (price as f64 / from_point * to_point).round() as u32;
+        Tick { bid: rescale(self.bid), ask: rescale(self.ask), ..*self }
     }
 
-    fn forward_to_next_good_file(walk_dir: &mut walkdir::IntoIter) 
-    -> Result<Option<(walkdir::DirEntry, NaiveDateTime)>, Error> {
-        loop {
-            if let Some(entry) = walk_dir.next() {
-                let entry = entry?;
-                if let Some(datetime) = entry.path().to_datetime() {
-                    return Ok(Some((entry, datetime)));
-                } else {
-                    continue
-                }
-            } else {
-                return Ok(None);
-            }
+    /// Size-weighted mid `(bid * asksize + ask * bidsize) / (bidsize + asksize)`
+    /// scaled by `point_value`, see `Price::as_f64`
+    ///
+    /// Each price is weighted by the size on the opposite side, so the
+    /// microprice leans towards the side with less size, where the price is
+    /// more likely to move. Falls back to the plain mid if both sizes are 0.
+    pub fn microprice_f64(&self, point_value: f64) -> f64 {
+        let (bid, ask) = (self.bid_price().as_f64(point_value), self.ask_price().as_f64(point_value));
+        let (bidsize, asksize) = (self.bidsize as f64, self.asksize as f64);
+        if bidsize + asksize > 0.0 {
+            (bid * asksize + ask * bidsize) / (bidsize + asksize)
+        } else {
+            (bid + ask) / 2.0
         }
     }
 
-    /// Returns an iterator or `Error`
-    pub fn iter(&self) -> Result<Bi5Iter, Error> 
-    {
-        
-        if self.path.is_file() {
+    /// Raw bid price as `Price`, which can't be mixed up with a scaled one
+    ///
+    /// ```
+    /// use bi5::Tick;
+    /// let tick = Tick { millisecs: 0, ask: 133153, bid: 133117, asksize: 0.015, bidsize: 0.02 };
+    /// assert_eq!(tick.bid_price().as_raw(), 133117);
+    /// assert_eq!(tick.bid_price().as_f64(1e5), 1.33117);
+    /// assert!(tick.bid_price() < tick.ask_price());
+    /// ```
+    pub fn bid_price(&self) -> Price {
+        Price(self.bid)
+    }
 
-            let file: File = File::open(&self.path)?;
-            let file_len: u64 = file.metadata()?.len();
+    /// Raw ask price as `Price`, see `bid_price`
+    pub fn ask_price(&self) -> Price {
+        Price(self.ask)
+    }
 
-            let mut buf: Vec<u8> = Vec::new();  // buffer to decode into
-            if file_len == 0 {
-                return Ok(Bi5Iter::File { 
-                    cursor: Cursor::new(buf),
-                    date_time: self.date_time
-                })
-            }
-            let mut f_reader: BufReader<File> = BufReader::new(file);
-            lzma_decompress(&mut f_reader, &mut buf)?;
-        
-            if buf.len() % size_of::<Tick>() != 0 {
-                return Err(anyhow!(
-                    "Decompressed buffer length {} is not a multiple of {}", 
-                    buf.len(), 
-                    size_of::<Tick>()
-                ));
-            }
+    /// Exact bid price with `decimals` decimal places, the fixed-point
+    /// counterpart of `Price::as_f64` for a point value of `10^decimals`
+    ///
+    /// ```
+    /// use bi5::Tick;
+    /// let tick = Tick { millisecs: 0, ask: 133153, bid: 133117, asksize: 0.015, bidsize: 0.02 };
+    /// assert_eq!(tick.bid_decimal(5).to_string(), "1.33117");
+    /// ```
+    pub fn bid_decimal(&self, decimals: u32) -> FixedPoint {
+        FixedPoint { mantissa: self.bid, scale: decimals }
+    }
 
-            Ok(Bi5Iter::File { 
-                cursor: Cursor::new(buf),
-                date_time: self.date_time,
-            })
+    /// Exact ask price with `decimals` decimal places, see `bid_decimal`
+    pub fn ask_decimal(&self, decimals: u32) -> FixedPoint {
+        FixedPoint { mantissa: self.ask, scale: decimals }
+    }
 
-        } else if self.path.is_dir() {
+    /// `ask - bid` in raw points, negative for crossed ticks
+    ///
+    /// ```
+    /// use bi5::*;
+    /// let ticks = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap();
+    /// let wide = ticks.into_iter().filter(|(_, tick)| tick.spread() >= 30).count();
+    /// assert!(wide > 0);
+    /// ```
+    pub fn spread(&self) -> i64 {
+        self.ask as i64 - self.bid as i64
+    }
 
-            let mut walk_dir = WalkDir::new(&self.path)
-                .sort_by_key(direntry_to_key)
-                .into_iter();
+    /// Returns true if `bid > ask`
+    pub fn is_crossed(&self) -> bool {
+        self.bid > self.ask
+    }
 
-            if let Some((entry, date_time)) = 
-                Self::forward_to_next_good_file(&mut walk_dir)? 
-            {
-                let file_iter = Bi5::new(entry.path(), Some(date_time)).iter()?;
-                return Ok(Bi5Iter::Dir { walk_dir, file_iter: Box::new(file_iter), date_time })
-            } else {
-                return Ok(Bi5Iter::Empty);
-            }
+    /// Returns true if `bid == ask`
+    pub fn is_locked(&self) -> bool {
+        self.bid == self.ask
+    }
+
+    /// Returns true if `bidsize` or `asksize` is zero
+    ///
+    /// Dukascopy sizes are the volume available at the price, in millions
+    /// of units. A zero size means no volume was reported for that side, so
+    /// its price is only indicative and the tick shouldn't count as a
+    /// two-sided quote. Size-weighted measures like `microprice_f64`,
+    /// `imbalance` or a VWAP get a degenerate weight from it.
+    pub fn is_one_sided(&self) -> bool {
+        self.bidsize == 0.0 || self.asksize == 0.0
+    }
+
+    /// The `Anomaly` of the quote, if any
+    pub fn anomaly(&self) -> Option<Anomaly> {
+        if self.is_crossed() {
+            Some(Anomaly::Crossed)
+        } else if self.is_locked() {
+            Some(Anomaly::Locked)
         } else {
-            Err(anyhow!("{} must be file or dir", self.path.to_string_lossy()))
+            None
         }
     }
+
+    /// Size imbalance `(bidsize - asksize) / (bidsize + asksize)` in `[-1, 1]`,
+    /// 0 if both sizes are zero
+    pub fn imbalance(&self) -> f32 {
+        let total = self.bidsize + self.asksize;
+        if total == 0.0 { 0.0 } else { (self.bidsize - self.asksize) / total }
+    }
+
+    /// Equality that compares `millisecs`, `ask` and `bid` exactly and the
+    /// sizes within the tolerance `eps`
+    ///
+    /// ```
+    /// use bi5::Tick;
+    /// let a = Tick { millisecs: 1, ask: 133153, bid: 133117, asksize: 0.015, bidsize: 0.02 };
+    /// let b = Tick { asksize: 0.015 + f32::EPSILON, ..a };
+    /// assert!(a != b && a.approx_eq(&b, 1e-6));
+    /// ```
+    pub fn approx_eq(&self, other: &Tick, eps: f32) -> bool {
+        self.millisecs == other.millisecs
+            && self.ask == other.ask
+            && self.bid == other.bid
+            && (self.asksize - other.asksize).abs() <= eps
+            && (self.bidsize - other.bidsize).abs() <= eps
+    }
 }
 
-impl Iterator for Bi5Iter {
-    type Item = (NaiveDateTime, Tick);
-    fn next(&mut self) -> Option<Self::Item> {
-        match self {
-            Bi5Iter::Empty => { None }
-            Bi5Iter::File { cursor, date_time } => {
-                Tick::read(cursor).ok().map(|tick|(*date_time + Duration::milliseconds(tick.millisecs as i64), tick))
-            },
-            Bi5Iter::Dir { walk_dir, file_iter, date_time } => {
-                if let Some(tick) = file_iter.next() {
-                    return Some(tick)
-                } else { // ticks exhausted, get new
-                    if let Some((entry, date_time_)) = Bi5::forward_to_next_good_file(walk_dir).ok()? {
-                        *date_time = date_time_;
-                        *file_iter = Box::new(Bi5::new(entry.path(), Some(date_time_)).iter().ok()?);
-                        self.next()
-                    } else {
-                        None
-                    }
-                }
+/// Builds a `Tick` and checks that it is a possible quote
+///
+/// `build` fails if `bid > ask` (unless `allow_crossed` is set) or a size is
+/// negative or not finite. Construct `Tick` directly for deliberate anomalies.
+/// ```
+/// use bi5::TickBuilder;
+/// let tick = TickBuilder::new().millisecs(1).bid(133117).ask(133153).bidsize(0.02).build().unwrap();
+/// assert_eq!((tick.bid, tick.asksize), (133117, 0.0));
+/// assert!(TickBuilder::new().bid(2).ask(1).build().is_err());
+/// assert!(TickBuilder::new().bid(2).ask(1).allow_crossed(true).build().is_ok());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TickBuilder {
+    tick: Tick,
+    allow_crossed: bool,
+}
+
+impl TickBuilder {
+    /// All fields zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Milliseconds since file start
+    pub fn millisecs(mut self, millisecs: u32) -> Self {
+        self.tick.millisecs = millisecs;
+        self
+    }
+
+    /// Raw bid price
+    pub fn bid(mut self, bid: u32) -> Self {
+        self.tick.bid = bid;
+        self
+    }
+
+    /// Raw ask price
+    pub fn ask(mut self, ask: u32) -> Self {
+        self.tick.ask = ask;
+        self
+    }
+
+    /// Bid size
+    pub fn bidsize(mut self, bidsize: f32) -> Self {
+        self.tick.bidsize = bidsize;
+        self
+    }
+
+    /// Ask size
+    pub fn asksize(mut self, asksize: f32) -> Self {
+        self.tick.asksize = asksize;
+        self
+    }
+
+    /// Accept `bid > ask`, default `false`
+    pub fn allow_crossed(mut self, allow: bool) -> Self {
+        self.allow_crossed = allow;
+        self
+    }
+
+    /// The `Tick` or `Bi5Error::InvalidTick`
+    pub fn build(self) -> Result<Tick, Bi5Error> {
+        let tick = self.tick;
+        if tick.is_crossed() && !self.allow_crossed {
+            return Err(Bi5Error::InvalidTick(format!("bid {} > ask {}", tick.bid, tick.ask)));
+        }
+        for (name, size) in [("bidsize", tick.bidsize), ("asksize", tick.asksize)] {
+            if !size.is_finite() || size < 0.0 {
+                return Err(Bi5Error::InvalidTick(format!("{} {} is not a finite non-negative number", name, size)));
             }
         }
+        Ok(tick)
     }
 }
 
-/// Decompress and parse a bi5 file
-/// ## Arguments
-/// - `path` - Path to bi5 file
-/// - `date_time` - Optional date_time of file
-/// ## Returns
-/// `Vec` of `Tick`s or `Error`
-/// 
-/// ## Usage
+/// Quote anomaly of a `Tick`, see `TickIteratorExt::flag_anomalies`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anomaly {
+    /// `bid > ask`
+    Crossed,
+    /// `bid == ask`
+    Locked,
+}
+
+/// An exact decimal price `mantissa * 10^-scale`, see `Tick::bid_decimal`
+///
+/// Displays without floating point rounding, e.g. `1.33117`. Equality
+/// compares the raw fields, so `1.0` with scale 1 and `1` with scale 0
+/// differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FixedPoint {
+    /// Raw price in units of `10^-scale`
+    pub mantissa: u32,
+    /// Number of decimal places
+    pub scale: u32,
+}
+
+impl FixedPoint {
+    /// The nearest `f64`
+    pub fn to_f64(&self) -> f64 {
+        self.mantissa as f64 / 10f64.powi(self.scale as i32)
+    }
+}
+
+impl fmt::Display for FixedPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let scale = self.scale as usize;
+        let digits = format!("{:0width$}", self.mantissa, width = scale + 1);
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        if frac.is_empty() { write!(f, "{}", int) } else { write!(f, "{}.{}", int, frac) }
+    }
+}
+
+/// A `Tick` with scaled prices, see `Tick::scaled`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ScaledTick {
+    /// Milliseconds since file start
+    pub millisecs: u32,
+    /// Bid price
+    pub bid: f64,
+    /// Ask price
+    pub ask: f64,
+    /// Bid size
+    pub bidsize: f32,
+    /// Ask size
+    pub asksize: f32,
+}
+
+/// A raw price in points, see `Tick::bid_price`
+///
+/// Scaling is explicit, so a raw price can't be passed where a scaled one is
+/// expected or vice versa. Ordering compares the raw prices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Price(u32);
+
+impl Price {
+    /// Price of `raw` points
+    pub fn from_raw(raw: u32) -> Self {
+        Price(raw)
+    }
+
+    /// The raw price in points
+    pub fn as_raw(&self) -> u32 {
+        self.0
+    }
+
+    /// Price scaled by `point_value`, the number of raw price units per 1.0
+    /// (e.g. `1e5` for most FX pairs, `1e3` for JPY pairs)
+    pub fn as_f64(&self, point_value: f64) -> f64 {
+        self.0 as f64 / point_value
+    }
+
+    /// Exact price with `decimals` decimal places, see `Tick::bid_decimal`
+    pub fn as_decimal(&self, decimals: u32) -> FixedPoint {
+        FixedPoint { mantissa: self.0, scale: decimals }
+    }
+}
+
+impl fmt::Display for Tick {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{},{},{}", self.millisecs, self.bid, self.ask, self.bidsize, self.asksize)
+    }
+}
+
+impl Tick {
+    /// Displays like `Tick` but with the sizes rounded to `size_precision`
+    /// decimals, avoiding `f32` artifacts such as `0.014999999`
+    /// ```
+    /// use bi5::Tick;
+    /// let tick = Tick { millisecs: 1, ask: 3, bid: 2, asksize: 0.015, bidsize: 1.0 / 3.0 };
+    /// assert_eq!(tick.display_with(3).to_string(), "1,2,3,0.333,0.015");
+    /// ```
+    pub fn display_with(&self, size_precision: usize) -> TickDisplay<'_> {
+        TickDisplay { tick: self, size_precision, point_value: None }
+    }
+}
+
+/// `Display` of a `Tick` with fixed size precision, see `Tick::display_with`
+pub struct TickDisplay<'a> {
+    tick: &'a Tick,
+    size_precision: usize,
+    point_value: Option<PointValue>,
+}
+
+impl TickDisplay<'_> {
+    /// Display the prices scaled by `point_value`, exactly if it is a power
+    /// of ten
+    /// ```
+    /// use bi5::{PointValue, Tick};
+    /// let tick = Tick { millisecs: 1, ask: 133150, bid: 133117, asksize: 0.015, bidsize: 0.02 };
+    /// assert_eq!(tick.display_with(3).point_value(PointValue::FX).to_string(), "1,1.33117,1.33150,0.020,0.015");
+    /// ```
+    pub fn point_value(mut self, point_value: PointValue) -> Self {
+        self.point_value = Some(point_value);
+        self
+    }
+}
+
+/// Writes the raw `price`, scaled by `point_value` if given
+pub(crate) fn write_price<W: fmt::Write + ?Sized>(f: &mut W, price: u32, point_value: Option<PointValue>) -> fmt::Result {
+    match point_value {
+        None => write!(f, "{}", price),
+        Some(point_value) => match point_value.decimals() {
+            Some(scale) => write!(f, "{}", FixedPoint { mantissa: price, scale }),
+            None => write!(f, "{}", price as f64 / point_value.get() as f64),
+        },
+    }
+}
+
+impl fmt::Display for TickDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (tick, p) = (self.tick, self.size_precision);
+        write!(f, "{},", tick.millisecs)?;
+        write_price(f, tick.bid, self.point_value)?;
+        f.write_str(",")?;
+        write_price(f, tick.ask, self.point_value)?;
+        write!(f, ",{:.*},{:.*}", p, tick.bidsize, p, tick.asksize)
+    }
+}
+
+/// The raw `price` formatted like `TickDisplay::point_value`, e.g. for
+/// writers of other formats
+/// ```
+/// use bi5::{format_price, PointValue};
+/// assert_eq!(format_price(133117, Some(PointValue::FX)), "1.33117");
+/// assert_eq!(format_price(133117, None), "133117");
+/// ```
+pub fn format_price(price: u32, point_value: Option<PointValue>) -> String {
+    let mut s = String::new();
+    write_price(&mut s, price, point_value).expect("writing to a String can't fail");
+    s
+}
+
+impl Tick {
+    /// JSON object with the field names as-is, non-finite sizes are `null`
+    /// ```
+    /// use bi5::Tick;
+    /// let tick = Tick { millisecs: 1, ask: 3, bid: 2, asksize: 0.015, bidsize: f32::NAN };
+    /// assert_eq!(tick.to_json(), r#"{"millisecs":1,"ask":3,"bid":2,"asksize":0.015,"bidsize":null}"#);
+    /// ```
+    pub fn to_json(&self) -> String {
+        format!("{{{}}}", self.json_fields())
+    }
+
+    fn json_fields(&self) -> String {
+        format!("\"millisecs\":{},\"ask\":{},\"bid\":{},\"asksize\":{},\"bidsize\":{}",
+            self.millisecs, self.ask, self.bid, json::number(self.asksize), json::number(self.bidsize))
+    }
+}
+
+/// A tick with its absolute time, as yielded by `Bi5Iter`, for writing both
+/// as one record
 /// ```
 /// use bi5::*;
-/// let ticks = read_bi5_file("test/test.bi5", None).expect("Read failed");
-/// assert_eq!(
-///     ticks.first(),
-///     Some(&Tick { millisecs: 1860002, ask: 133153, bid: 133117, asksize: 0.015, bidsize: 0.02 })
-/// );
+/// let (t, tick) = decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap()[0];
+/// assert_eq!(TimedTick::from((t, tick)).to_json(),
+///            r#"{"t":"0000-01-01T00:31:00.002","millisecs":1860002,"ask":133153,"bid":133117,"asksize":0.015,"bidsize":0.02}"#);
 /// ```
-pub fn read_bi5_file<P:AsRef<Path>+Copy>(path: P, date_time: Option<NaiveDateTime>) 
-    -> Result<Vec<Tick>, Error>
-{
-    let bi5 = Bi5::new(path, date_time);
-    let ticks = bi5.iter()?.map(|x|x.1).collect();
-    Ok(ticks)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimedTick {
+    /// Absolute tick time
+    pub t: NaiveDateTime,
+    pub tick: Tick,
+}
+
+impl TimedTick {
+    /// JSON object of `t` in ISO 8601 followed by the fields of
+    /// `Tick::to_json`
+    pub fn to_json(&self) -> String {
+        self.to_json_with_symbol(None)
+    }
 
+    /// `to_json` with a leading `"symbol"` field if `symbol` is given
+    /// ```
+    /// use bi5::*;
+    /// let timed = TimedTick::from(decode_bytes(include_bytes!("../test/test.bi5"), None).unwrap()[0]);
+    /// assert!(timed.to_json_with_symbol(Some("EURUSD")).starts_with(r#"{"symbol":"EURUSD","t":"#));
+    /// ```
+    pub fn to_json_with_symbol(&self, symbol: Option<&str>) -> String {
+        let symbol = symbol.map(|s| format!("\"symbol\":{},", json::string(s))).unwrap_or_default();
+        format!("{{{}\"t\":\"{}\",{}}}", symbol, self.t.format("%Y-%m-%dT%H:%M:%S%.3f"), self.tick.json_fields())
+    }
 }
 
-trait ToDateTime {
-    fn to_datetime(&self) -> Option<NaiveDateTime>;
+impl From<(NaiveDateTime, Tick)> for TimedTick {
+    fn from((t, tick): (NaiveDateTime, Tick)) -> Self {
+        TimedTick { t, tick }
+    }
 }
 
-impl ToDateTime for Path {
-    fn to_datetime(&self) -> Option<NaiveDateTime>
-    {
-        if!self.is_file() { 
-            None 
-        } else {
-            let mut v: Vec<&OsStr> = self.iter().collect();
-            let f: &str = v.pop()?.to_str()?;
-            if f.len() < 2 { return None; }
-            let h: u32 = (&f[0..2]).parse::<u32>().ok()?;
-            let d: u32 = v.pop()?.to_str()?.parse::<u32>().ok()?;
-            let m: u32 = v.pop()?.to_str()?.parse::<u32>().ok()?;
-            let y: u32 = v.pop()?.to_str()?.parse::<u32>().ok()?;
-            Some(NaiveDateTime::new(
-                NaiveDate::from_ymd_opt(y as i32, m+1, d)?, 
-                NaiveTime::from_hms_opt(h, 0, 0)?
-            ))
-        }
+impl From<TimedTick> for (NaiveDateTime, Tick) {
+    fn from(timed: TimedTick) -> Self {
+        (timed.t, timed.tick)
     }
 }
 
-fn direntry_to_key(entry: &walkdir::DirEntry) -> NaiveDateTime {
-    entry.path().to_datetime().unwrap_or(zero_timestamp())
+/// Represents a bi5 file or directory
+///
+/// A `Bi5` only holds the path, the start and the options; files are opened
+/// by `iter()` and friends. So cloning is cheap and each clone iterates
+/// independently, e.g. one per thread. Clones share the `cancel_token` and
+/// the `DecodeCache`.
+/// ```
+/// use bi5::Bi5;
+/// let bi5 = Bi5::new("test/test.bi5", None);
+/// let threads: Vec<_> = (0..2).map(|_| {
+///     let bi5 = bi5.clone();
+///     std::thread::spawn(move || bi5.iter().unwrap().count())
+/// }).collect();
+/// for thread in threads {
+///     assert_eq!(thread.join().unwrap(), 10412);
+/// }
+/// ```
+#[cfg(feature = "fs")]
+#[derive(Clone)]
+pub struct Bi5 {
+    path: PathBuf,
+    date_time: NaiveDateTime,
+    options: Options,
 }
 
-#[test]
-/// Test correct length, and correctness of first and last tick in test/test.bi5
-fn test_read_bi5() {
-    match read_bi5_file("test/test.bi5", None) {
-        Err(_) => assert!(false),
-        Ok(ticks) => {
-            assert_eq!(ticks.len(), 10412);
-            assert_eq!(
-                ticks.first(), 
-                Some(&Tick { millisecs: 1860002, bid: 133117, ask: 133153, bidsize: 0.02, asksize: 0.015 })
-            );
-            assert_eq!(
-                ticks.last(), 
-                Some(&Tick { millisecs: 3599899,  bid: 131427, ask: 131453,bidsize: 0.02, asksize: 0.015 })
-            );
-        }
+/// Order in which the files of a directory are read, see `Bi5::with_order`
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    /// Oldest file first
+    #[default]
+    Chronological,
+    /// Newest file first, the ticks of each file stay in ascending order
+    ReverseChronological,
+}
+
+/// Options of a `Bi5`, set with `Bi5Builder`
+#[cfg(feature = "fs")]
+#[derive(Clone, Debug, Default)]
+struct Options {
+    ignore_trailing_partial: bool,
+    decode_capacity: usize,
+    codec: Codec,
+    skip_walk_errors: bool,
+    skip_errors: bool,
+    max_millisecs: Option<u32>,
+    check_max_millisecs: Option<u32>,
+    check_endianness: bool,
+    split_resets: bool,
+    decompress_twice: bool,
+    follow_links: bool,
+    max_depth: Option<usize>,
+    trim_edges: (usize, usize),
+    extension: Option<std::ffi::OsString>,
+    time_shift: Option<Duration>,
+    cancel: Option<Arc<AtomicBool>>,
+    read_buffer: Option<usize>,
+    tolerance: Option<Duration>,
+    cache: Option<DecodeCache>,
+    order: Order,
+    /// files outside are skipped by the walk, set by `Bi5::window`
+    window: Option<(NaiveDateTime, NaiveDateTime)>,
+    on_notice: Option<NoticeFn>,
+}
+
+/// Callback of `Bi5Builder::on_notice`
+#[cfg(feature = "fs")]
+#[derive(Clone)]
+struct NoticeFn(Arc<dyn Fn(Notice) + Send + Sync>);
+
+#[cfg(feature = "fs")]
+impl fmt::Debug for NoticeFn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("NoticeFn")
     }
 }
 
-#[test]
-/// Test correct length, and correctness of first and last tick in test/test.bi5
-fn test_read2_bi5() {
-    let bi5 = Bi5::new("test/test.bi5", Some(NaiveDateTime::from_timestamp_millis(12345678).unwrap()));
-    if let Some((t,tick)) = bi5.iter().unwrap().next() {
-        assert_eq!(t.timestamp_millis(), 12345678+1860002);
-        assert_eq!(
-            tick, 
-            Tick { millisecs: 1860002, bid: 133117, ask: 133153, bidsize: 0.02, asksize: 0.015 }
-        );
+#[cfg(feature = "fs")]
+impl Options {
+    /// The options that change the tick count of a file, as stored in the
+    /// sidecar index
+    fn count_key(&self) -> String {
+        let max = self.max_millisecs.map_or("-".to_string(), |ms| ms.to_string());
+        format!("partial={} max={} trim={},{}",
+            self.ignore_trailing_partial as u8, max, self.trim_edges.0, self.trim_edges.1)
+    }
+}
+
+/// Builder for a `Bi5` with non-default options
+///
+/// ```
+/// use bi5::Bi5Builder;
+/// let bi5 = Bi5Builder::new("test/test.bi5")
+///     .ignore_trailing_partial(true)
+///     .build();
+/// assert_eq!(bi5.iter().unwrap().count(), 10412);
+/// ```
+#[cfg(feature = "fs")]
+pub struct Bi5Builder {
+    path: PathBuf,
+    date_time: Option<NaiveDateTime>,
+    options: Options,
+}
+
+#[cfg(feature = "fs")]
+impl Bi5Builder {
+
+    /// Create a builder for the bi5 file or directory at `path`
+    pub fn new<P:AsRef<Path>>(path: P) -> Self {
+        Bi5Builder { path: path.as_ref().to_path_buf(), date_time: None, options: Options::default() }
+    }
+
+    /// Datetime of the file start (only meaningful for file)
+    pub fn date_time(mut self, date_time: NaiveDateTime) -> Self {
+        self.date_time = Some(date_time);
+        self
+    }
+
+    /// Don't fail on decompressed buffers with 1-19 trailing bytes, instead
+    /// read the complete ticks and drop the remainder (default `false`).
+    /// The number of dropped bytes is reported to `on_notice`.
+    pub fn ignore_trailing_partial(mut self, ignore: bool) -> Self {
+        self.options.ignore_trailing_partial = ignore;
+        self
     }
-}
\ No newline at end of file
+
+    /// Call `f` with a `Notice` for data that the lenient options drop or
+    /// change (default none, the notices are discarded)
+    ///
+    /// ```
+    /// use bi5::{Bi5Builder, Notice};
+    /// let bi5 = Bi5Builder::new("test/test.bi5")
+    ///     .ignore_trailing_partial(true)
+    ///     .on_notice(|notice: Notice| eprintln!("{}", notice))
+    ///     .build();
+    /// assert_eq!(bi5.iter().unwrap().count(), 10412);
+    /// ```
+    pub fn on_notice(mut self, f: impl Fn(Notice) + Send + Sync + 'static) -> Self {
+        self.options.on_notice = Some(NoticeFn(Arc::new(f)));
+        self
+    }
+
+    /// Pre-allocate `bytes` for the decompressed buffer of each file to avoid
+    /// reallocations while decoding (default 0). A good hint is the typical
+    /// tick count times 20, or about 6 times the compressed file size.
+    pub fn decode_capacity_hint(mut self, bytes: usize) -> Self {
+        self.options.decode_capacity = bytes;
+        self
+    }
+
+    /// Read the compressed files through a buffer of `bytes` (default 8 KiB
+    /// like `BufReader`, at least 64). Larger buffers mean fewer, bigger
+    /// reads, which helps on network filesystems with a high latency per
+    /// request. Typical files are a few tens of KiB, so e.g. 256 KiB reads
+    /// most in one request.
+    /// ```
+    /// let bi5 = bi5::Bi5Builder::new("test/test.bi5").read_buffer_size(1 << 18).build();
+    /// assert_eq!(bi5.iter().unwrap().count(), 10412);
+    /// ```
+    pub fn read_buffer_size(mut self, bytes: usize) -> Self {
+        self.options.read_buffer = Some(bytes);
+        self
+    }
+
+    /// Accept tick times that step back by up to `tolerance`, or lie up to
+    /// `tolerance` beyond the hour of the file, in `audit` (default zero, which
+    /// is strict). Some feeds have such clock jitter without being corrupt.
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.options.tolerance = Some(tolerance);
+        self
+    }
+
+    /// Compression format of the files (default `Codec::Auto`)
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.options.codec = codec;
+        self
+    }
+
+    /// Skip directories and entries the walk can't read, e.g. because of
+    /// missing permissions, instead of reporting `Bi5Error::Walk` (default
+    /// `false`). Decode errors are not affected. Skipped entries are reported
+    /// to `on_notice`.
+    pub fn skip_walk_errors(mut self, skip: bool) -> Self {
+        self.options.skip_walk_errors = skip;
+        self
+    }
+
+    /// Let `iter` of a directory skip files that fail to decode, and walk
+    /// errors, instead of ending the stream (default `false`). Skipped files
+    /// are collected in `Bi5Iter::failed_files`, `Bi5::par_read` skips and
+    /// returns them too. Nothing is printed.
+    pub fn skip_errors(mut self, skip: bool) -> Self {
+        self.options.skip_errors = skip;
+        self
+    }
+
+    /// Drop ticks whose `millisecs` exceed `max_millisecs`, e.g.
+    /// `MAX_PLAUSIBLE_MILLISECS` (default no bound). Such values come from
+    /// corrupt or misaligned data and would give timestamps far in the future.
+    /// The number of dropped ticks is reported to `on_notice`.
+    pub fn max_millisecs(mut self, max_millisecs: Option<u32>) -> Self {
+        self.options.max_millisecs = max_millisecs;
+        self
+    }
+
+    /// Drop the first `head` and the last `tail` ticks of every file
+    /// (default none), a heuristic against adjacent hour files overlapping
+    /// at their boundaries. Files with fewer ticks yield none.
+    pub fn trim_edges(mut self, head: usize, tail: usize) -> Self {
+        self.options.trim_edges = (head, tail);
+        self
+    }
+
+    /// Fail with `Bi5Error::MillisecsOutOfRange` if a tick's `millisecs`
+    /// exceed `max_millisecs`, e.g. `MAX_PLAUSIBLE_MILLISECS` (default no
+    /// check). Unlike `max_millisecs` this drops nothing, it flags misaligned
+    /// decoding early. Applied before `max_millisecs`.
+    pub fn check_max_millisecs(mut self, max_millisecs: Option<u32>) -> Self {
+        self.options.check_max_millisecs = max_millisecs;
+        self
+    }
+
+    /// Fail with `Bi5Error::Endianness` if the first ticks of a file look
+    /// little-endian (default `false`). This is a heuristic on the tick times,
+    /// it catches byte-swapped files that would otherwise decode to garbage.
+    pub fn check_endianness(mut self, check: bool) -> Self {
+        self.options.check_endianness = check;
+        self
+    }
+
+    /// Treat each reset of `millisecs`, see `millisecs_resets`, as the start
+    /// of the next hour (default `false`): an hour is added to the ticks after
+    /// every reset, as if the file had been split there. This repairs files
+    /// concatenated from several hours without rebasing. The number of resets
+    /// is reported to `on_notice`.
+    pub fn split_resets(mut self, split: bool) -> Self {
+        self.options.split_resets = split;
+        self
+    }
+
+    /// Decompress a second LZMA or xz layer of files that were compressed
+    /// twice (default `false`), instead of failing with
+    /// `Bi5Error::DoubleCompressed`. gzip can't be decoded, such files still
+    /// fail.
+    pub fn decompress_twice(mut self, decompress_twice: bool) -> Self {
+        self.options.decompress_twice = decompress_twice;
+        self
+    }
+
+    /// Follow symbolic links while walking a directory (default `false`)
+    ///
+    /// A link pointing to one of its own ancestors is a loop, it is reported
+    /// as `Bi5Error::Walk` (or skipped with `skip_walk_errors`) rather than
+    /// followed forever. Files reachable through several links are read once
+    /// per link.
+    pub fn follow_links(mut self, follow: bool) -> Self {
+        self.options.follow_links = follow;
+        self
+    }
+
+    /// Don't descend more than `depth` directories below the path while
+    /// walking (default unlimited)
+    ///
+    /// The path itself is depth 0, so for a `symbol/year/month/day/file`
+    /// tree below the path the files are at depth 5. Deeper stray
+    /// directories are skipped without being read.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.options.max_depth = Some(depth);
+        self
+    }
+
+    /// Only consider files with this extension (e.g. `"bi5"`) during a
+    /// directory walk (by default any extension). Without it every file
+    /// named like an hour file, e.g. `14h_ticks.csv`, is decoded as bi5.
+    pub fn extension<S: AsRef<OsStr>>(mut self, extension: S) -> Self {
+        self.options.extension = Some(extension.as_ref().to_os_string());
+        self
+    }
+
+    /// Add `offset` to every tick time and file start (default zero). The
+    /// file datetimes derived from the paths, e.g. for sorting, are unchanged.
+    pub fn time_shift(mut self, offset: Duration) -> Self {
+        self.options.time_shift = Some(offset);
+        self
+    }
+
+    /// End the iteration of a directory once `token` is set, e.g. from a
+    /// Ctrl-C handler (default none). The token is checked before each tick,
+    /// so `next` returns `None` promptly and no further files are
+    /// decompressed. A single file is decoded up front and isn't affected.
+    /// ```
+    /// use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+    /// let token = Arc::new(AtomicBool::new(false));
+    /// let bi5 = bi5::Bi5Builder::new("test").cancel_token(token.clone()).build();
+    /// // in the signal handler
+    /// token.store(true, Ordering::Relaxed);
+    /// assert_eq!(bi5.iter().unwrap().count(), 0);
+    /// ```
+    pub fn cancel_token(mut self, token: Arc<AtomicBool>) -> Self {
+        self.options.cancel = Some(token);
+        self
+    }
+
+    /// Create the `Bi5`
+    pub fn build(self) -> Bi5 {
+        Bi5 {
+            path: self.path,
+            date_time: self.date_time.unwrap_or(zero_timestamp()),
+            options: self.options,
+        }
+    }
+}
+
+/// Iterator over bi5 file or directories
+///
+/// Yields the absolute time of each tick, see `Tick::time`, so there's no
+/// need to add `millisecs` to the file start.
+/// ```
+/// use bi5::Bi5;
+/// let bi5 = Bi5::new("test/test.bi5", None);
+/// for (date_time, tick) in bi5.iter().expect("File error") {
+///     println!("{},{}", date_time, tick);
+/// }
+/// ```
+#[cfg(feature = "fs")]
+// one `Bi5Iter` exists per read, boxing the walk state would only add an allocation
+#[allow(clippy::large_enum_variant)]
+pub enum Bi5Iter {
+    File(FileIter),
+    Dir(DirIter),
+    Empty
+}
+
+#[cfg(feature = "fs")]
+impl Bi5Iter {
+    /// `(done, total)` for a progress bar: ticks of a file, see
+    /// `FileIter::progress`, or files of a directory, see `DirIter::progress`
+    ///
+    /// ```
+    /// use bi5::Bi5;
+    /// let mut iter = Bi5::new("test/test.bi5", None).iter().unwrap();
+    /// iter.nth(99);
+    /// assert_eq!(iter.progress(), Some((100, 10412)));
+    /// ```
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        match self {
+            Bi5Iter::Empty => Some((0, 0)),
+            Bi5Iter::File(file_iter) => Some(file_iter.progress()),
+            Bi5Iter::Dir(dir_iter) => dir_iter.progress(),
+        }
+    }
+
+    /// The files skipped so far because of `Bi5Builder::skip_errors`, with
+    /// their errors
+    /// ```
+    /// use bi5::Bi5;
+    /// let mut iter = Bi5::builder("test").skip_errors(true).build().iter().unwrap();
+    /// iter.by_ref().for_each(drop);
+    /// assert!(iter.failed_files().is_empty());
+    /// ```
+    pub fn failed_files(&self) -> &[(PathBuf, Bi5Error)] {
+        match self {
+            Bi5Iter::Dir(dir_iter) => &dir_iter.failed_files,
+            _ => &[],
+        }
+    }
+}
+
+/// Iterator over the ticks of a single file, see `Bi5Iter::File`
+///
+/// As the whole file is decompressed up front and ticks are fixed size
+/// records, the number of remaining ticks is known exactly, and the ticks can
+/// be read newest-to-oldest with `rev()`. The ticks of a directory are decoded
+/// file by file during the walk, so `DirIter` can't be reversed.
+///
+/// ```
+/// use bi5::Bi5;
+/// let iter = Bi5::new("test/test.bi5", None).iter_file().expect("File error");
+/// assert_eq!(iter.len(), 10412);
+/// let (_, last) = Bi5::new("test/test.bi5", None).iter_file().unwrap().rev().next().unwrap();
+/// assert_eq!(last.millisecs, 3599899);
+/// ```
+#[cfg(feature = "fs")]
+pub struct FileIter {
+    /// cursor over binary blob
+    cursor: Cursor<Vec<u8>>,
+    /// date_time base of this file 
+    date_time: NaiveDateTime,
+}
+
+#[cfg(feature = "fs")]
+impl FileIter {
+    /// `buf` must hold a whole number of ticks
+    pub(crate) fn new(buf: Vec<u8>, date_time: NaiveDateTime) -> Self {
+        FileIter { cursor: Cursor::new(buf), date_time }
+    }
+
+    /// Datetime of the file start
+    pub fn date_time(&self) -> NaiveDateTime {
+        self.date_time
+    }
+
+    /// Skip the next `n` ticks without parsing them
+    ///
+    /// Together with `take_ticks` this selects the ticks `[offset, offset + limit)`
+    /// of a file, e.g. to shard a large file across workers.
+    /// ```
+    /// use bi5::Bi5;
+    /// let iter = Bi5::new("test/test.bi5", None).iter_file().unwrap();
+    /// let shard = iter.skip_ticks(10000).take_ticks(100);
+    /// assert_eq!(shard.len(), 100);
+    /// ```
+    pub fn skip_ticks(mut self, n: usize) -> Self {
+        self.advance(n);
+        self
+    }
+
+    /// Move the cursor past the next `n` ticks
+    fn advance(&mut self, n: usize) {
+        let skip = n.min(self.len()) * size_of::<Tick>();
+        self.cursor.set_position(self.cursor.position() + skip as u64);
+    }
+
+    /// `(done, total)` ticks, where `done` were read from the front.
+    /// Ticks skipped by `skip_ticks` count as done, those dropped by
+    /// `take_ticks` or read with `rev()` are no longer part of the total.
+    pub fn progress(&self) -> (usize, usize) {
+        let done = self.cursor.position() as usize / size_of::<Tick>();
+        (done, done + self.len())
+    }
+
+    /// Keep at most the next `n` ticks
+    pub fn take_ticks(mut self, n: usize) -> Self {
+        let end = self.cursor.position() as usize + n.min(self.len()) * size_of::<Tick>();
+        self.cursor.get_mut().truncate(end);
+        self
+    }
+
+    /// Like `next` but reports a record that can't be parsed as `Err`
+    fn try_next(&mut self) -> Option<Result<(NaiveDateTime, Tick), Bi5Error>> {
+        if self.len() == 0 {
+            return None;
+        }
+        let date_time = self.date_time;
+        Some(Tick::read(&mut self.cursor)
+            .map(|tick|(tick.time(date_time), tick))
+            .map_err(Bi5Error::from))
+    }
+}
+
+/// Iterator over the files of a directory, see `Bi5Iter::Dir`
+#[cfg(feature = "fs")]
+pub struct DirIter {
+    walk_dir: walkdir::IntoIter,
+    file_iter: Option<FileIter>,
+    date_time: NaiveDateTime,
+    /// the directory the walk was started from, supplies the options
+    dir: Box<Bi5>,
+    /// set once `next` returned `None`
+    done: bool,
+    /// path of the current file
+    path: PathBuf,
+    /// files skipped with `Bi5Builder::skip_errors`
+    failed_files: Vec<(PathBuf, Bi5Error)>,
+    /// files opened so far, including the current one
+    files_opened: usize,
+    /// number of files of the walk, counted on the first `progress`
+    files_total: std::cell::OnceCell<Option<usize>>,
+}
+
+#[cfg(feature = "fs")]
+impl DirIter {
+    /// Datetime of the file currently being read
+    pub fn date_time(&self) -> NaiveDateTime {
+        self.date_time
+    }
+
+    /// `(done, total)` files, where `done` counts the file being read as done.
+    /// The total is counted with a separate walk over the paths on the first
+    /// call, `None` if that walk fails.
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        let total = (*self.files_total.get_or_init(|| self.dir.scan().ok().map(|scan| scan.files().len())))?;
+        Some((self.files_opened.min(total), total))
+    }
+
+    /// Open the next good file of the walk, `None` at the end of the walk
+    fn open_next(&mut self) -> Option<Result<(), Bi5Error>> {
+        self.file_iter = None;
+        let (entry, date_time) = match self.dir.forward_to_next_good_file(&mut self.walk_dir) {
+            Ok(next) => next?,
+            Err(e) => return Some(Err(e)),
+        };
+        self.files_opened += 1;
+        self.date_time = date_time;
+        self.path = entry.path().to_path_buf();
+        Some(self.dir.file(entry.path(), date_time).file_iter().map(|file_iter| {
+            self.file_iter = Some(file_iter);
+        }))
+    }
+
+    /// Like `next` but reports walk and decode errors as `Err`, after which
+    /// the walk continues with the next file
+    fn try_next(&mut self) -> Option<Result<(NaiveDateTime, Tick), Bi5Error>> {
+        loop {
+            if self.dir.options.cancel.as_ref().is_some_and(|token| token.load(Ordering::Relaxed)) {
+                return None;
+            }
+            if let Some(item) = self.file_iter.as_mut().and_then(FileIter::try_next) {
+                return Some(item);
+            }
+            if let Err(e) = self.open_next()? {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// `f` of all `items` on `threads` threads, 0 for one per core, in the order
+/// of `items`
+#[cfg(feature = "fs")]
+fn parallel_map<T: Sync, R: Send>(items: &[T], threads: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }.min(items.len()).max(1);
+    // threads take the next item from a shared counter, so slow files don't hold up a whole share
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads).map(|_| scope.spawn(|| {
+            let mut results = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(i) else { break };
+                results.push((i, f(item)));
+            }
+            results
+        })).collect();
+        workers.into_iter().flat_map(|worker| worker.join().expect("worker thread panicked")).collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Number of ticks `Bi5::resolve_point_value` guesses from
+#[cfg(feature = "fs")]
+pub const POINT_VALUE_SAMPLE: usize = 1000;
+
+/// Returns 0000-01-01T00:00:00
+fn zero_timestamp() -> NaiveDateTime {
+    NaiveDateTime::new(
+        NaiveDate::from_ymd_opt(0, 1, 1).unwrap(),
+        NaiveTime::from_num_seconds_from_midnight_opt(0, 0).unwrap()
+    )
+}
+
+#[cfg(feature = "fs")]
+impl Bi5 {
+
+    /// Create `Bi5` representing a bi5 file or directory
+    /// ## Arguments
+    /// - `path` - File or directory path
+    /// - `date_time` - Optional datetime (only meaningful for file)
+    pub fn new<P:AsRef<Path>>(path: P, date_time: Option<NaiveDateTime>) -> Self {
+        Bi5 { 
+            path: path.as_ref().to_path_buf(),
+            date_time: date_time.unwrap_or(zero_timestamp()),
+            options: Options::default(),
+        }
+    }
+
+    /// Create a `Bi5Builder` for `path`
+    pub fn builder<P:AsRef<Path>>(path: P) -> Bi5Builder {
+        Bi5Builder::new(path)
+    }
+
+    /// `Bi5` for a file found in this directory, with the same options
+    fn file(&self, path: &Path, date_time: NaiveDateTime) -> Bi5 {
+        Bi5 { path: path.to_path_buf(), date_time, options: self.options.clone() }
+    }
+
+    /// Pass `notice` to the `Bi5Builder::on_notice` callback, if any
+    fn notice(&self, notice: Notice) {
+        if let Some(NoticeFn(f)) = &self.options.on_notice {
+            f(notice);
+        }
+    }
+
+    /// Decompress the file into a buffer of whole ticks
+    fn decode(&self) -> Result<Vec<u8>, Bi5Error> {
+        let mut buf = self.decompress()?;
+        if self.options.ignore_trailing_partial {
+            let dropped = decode::truncate_partial(&mut buf);
+            if dropped > 0 {
+                self.notice(Notice::TrailingBytes { path: self.path.clone(), bytes: dropped });
+            }
+        } else {
+            decode::check_len(&buf)?;
+        }
+        if self.options.check_endianness {
+            decode::check_endianness(&buf)?;
+        }
+        if let Some(max_millisecs) = self.options.check_max_millisecs {
+            decode::check_max_millisecs(&buf, max_millisecs)?;
+        }
+        if let Some(max_millisecs) = self.options.max_millisecs {
+            let dropped = decode::drop_implausible(&mut buf, max_millisecs);
+            if dropped > 0 {
+                self.notice(Notice::ImplausibleTicks { path: self.path.clone(), ticks: dropped, max: max_millisecs });
+            }
+        }
+        if self.options.split_resets {
+            let resets = decode::split_resets(&mut buf);
+            if resets > 0 {
+                self.notice(Notice::MillisecsResets { path: self.path.clone(), resets });
+            }
+        }
+        Ok(buf)
+    }
+
+    /// Returns true if `Bi5` is a file
+    ///
+    /// A FIFO counts as file, so process substitution like
+    /// `catbi5 <(curl ...)` can be read. It can only be read once.
+    pub fn is_file(&self) -> bool {
+        self.path.is_file() || self.special_file_kind() == Some("FIFO")
+    }
+
+    /// Returns true if `Bi5` is a directory, also with a trailing separator
+    /// or `.` components like `./test/`
+    pub fn is_dir(&self) -> bool {
+        self.path.is_dir()
+    }
+
+    /// Kind of special file at `path`, `None` for regular files, directories
+    /// and missing paths
+    fn special_file_kind(&self) -> Option<&'static str> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            let file_type = std::fs::metadata(&self.path).ok()?.file_type();
+            if file_type.is_fifo() {
+                return Some("FIFO");
+            } else if file_type.is_socket() {
+                return Some("socket");
+            } else if file_type.is_block_device() {
+                return Some("block device");
+            } else if file_type.is_char_device() {
+                return Some("character device");
+            }
+        }
+        None
+    }
+
+    /// The error for a path that is neither file nor directory
+    fn invalid_path(&self) -> Bi5Error {
+        match self.special_file_kind() {
+            Some(kind) => Bi5Error::SpecialFile { path: self.path.clone(), kind },
+            None => Bi5Error::InvalidPath(self.path.clone()),
+        }
+    }
+
+    fn forward_to_next_good_file(&self, walk_dir: &mut walkdir::IntoIter) 
+    -> Result<Option<(walkdir::DirEntry, NaiveDateTime)>, Bi5Error> {
+        loop {
+            if let Some(entry) = walk_dir.next() {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) if self.options.skip_walk_errors => {
+                        self.notice(Notice::WalkError(e));
+                        continue
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                if let Some(datetime) = self.walk_datetime(entry.path()) {
+                    return Ok(Some((entry, datetime)));
+                } else {
+                    continue
+                }
+            } else {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Returns an iterator or `Error`
+    ///
+    /// In a directory the stream ends at the first file that fails to
+    /// decode, unless `Bi5Builder::skip_errors` is set, see `try_iter` for
+    /// the errors themselves.
+    pub fn iter(&self) -> Result<Bi5Iter, Error> 
+    {
+        
+        if self.is_file() {
+
+            Ok(Bi5Iter::File(self.iter_file()?))
+
+        } else if self.is_dir() {
+            self.iter_walk(self.walk_dir())
+        } else {
+            Err(self.invalid_path().into())
+        }
+    }
+
+    /// Like `iter` but reads the files of a directory in the order the
+    /// filesystem lists them, which is not chronological
+    ///
+    /// The entries of a directory aren't collected and sorted first, so
+    /// the first ticks come sooner in huge trees. Each tick still gets the
+    /// time of its own file, and the ticks of a file are in order. Use it
+    /// when the files are processed independently. `with_order` has no
+    /// effect.
+    /// ```
+    /// use bi5::Bi5;
+    /// assert_eq!(Bi5::new("test", None).iter_unsorted().unwrap().count(), 0);
+    /// ```
+    pub fn iter_unsorted(&self) -> Result<Bi5Iter, Error> {
+        if self.is_file() {
+            Ok(Bi5Iter::File(self.iter_file()?))
+        } else if self.is_dir() {
+            Self::open_dir_iter(self.unsorted_dir_iter(self.walk_dir()))
+        } else {
+            Err(self.invalid_path().into())
+        }
+    }
+
+    /// Like `iter` but with prices scaled by `point_value`, see `Tick::scaled`
+    /// ```
+    /// use bi5::Bi5;
+    /// let (_, tick) = Bi5::new("test/test.bi5", None).iter_scaled(1e5).unwrap().next().unwrap();
+    /// assert_eq!((tick.bid, tick.ask), (1.33117, 1.33153));
+    /// ```
+    pub fn iter_scaled(&self, point_value: f64) -> Result<impl Iterator<Item = (NaiveDateTime, ScaledTick)>, Error> {
+        Ok(self.iter()?.map(move |(t, tick)| (t, tick.scaled(point_value))))
+    }
+
+    /// Datetime of a file found by the walk, `None` if it isn't a bi5 file
+    fn walk_datetime(&self, path: &Path) -> Option<NaiveDateTime> {
+        let date_time = match &self.options.extension {
+            Some(extension) if path.extension() != Some(extension.as_os_str()) => None,
+            _ => path.to_datetime(),
+        }?;
+        self.in_window(date_time).then_some(date_time)
+    }
+
+    /// False if the hour of the file starting at `date_time` lies outside
+    /// the window
+    fn in_window(&self, date_time: NaiveDateTime) -> bool {
+        let Some((start, end)) = self.options.window else { return true };
+        let file_start = date_time + self.options.time_shift.unwrap_or(Duration::zero());
+        file_start + Duration::hours(1) > start && file_start < end
+    }
+
+    /// A `WalkDir` over `path` configured with the options
+    fn walk_dir(&self) -> WalkDir {
+        let walk_dir = WalkDir::new(&self.path).follow_links(self.options.follow_links);
+        match self.options.max_depth {
+            Some(depth) => walk_dir.max_depth(depth),
+            None => walk_dir,
+        }
+    }
+
+    /// Sort the entries of each directory of the walk by `Order`
+    fn sort_walk(&self, walk_dir: WalkDir) -> WalkDir {
+        match self.options.order {
+            Order::Chronological => walk_dir.sort_by_key(direntry_to_key),
+            Order::ReverseChronological => walk_dir.sort_by(|a, b| direntry_to_key(b).cmp(&direntry_to_key(a))),
+        }
+    }
+
+    /// Returns an iterator over the bi5 files found by `walk_dir`
+    fn iter_walk(&self, walk_dir: WalkDir) -> Result<Bi5Iter, Error> {
+        Self::open_dir_iter(self.dir_iter(walk_dir))
+    }
+
+    /// `Bi5Iter` of `dir_iter` with its first file opened
+    fn open_dir_iter(mut dir_iter: DirIter) -> Result<Bi5Iter, Error> {
+        match dir_iter.open_next() {
+            Some(Ok(())) => Ok(Bi5Iter::Dir(dir_iter)),
+            Some(Err(e)) => Err(e.into()),
+            None => Ok(Bi5Iter::Empty),
+        }
+    }
+
+    /// Returns a `DirIter` over the files found by `walk_dir`, no file is open yet
+    fn dir_iter(&self, walk_dir: WalkDir) -> DirIter {
+        self.unsorted_dir_iter(self.sort_walk(walk_dir))
+    }
+
+    /// Like `dir_iter` but in the order of the walk
+    fn unsorted_dir_iter(&self, walk_dir: WalkDir) -> DirIter {
+        let walk_dir = walk_dir.into_iter();
+        let dir = Box::new(self.file(&self.path, self.date_time));
+        DirIter {
+            walk_dir, file_iter: None, date_time: self.date_time, dir, done: false,
+            path: PathBuf::new(), failed_files: Vec::new(), files_opened: 0, files_total: Default::default(),
+        }
+    }
+
+    /// Returns an iterator whose items are `Result`s
+    ///
+    /// Unlike `iter`, which silently ends the stream, errors are yielded as
+    /// `Err` items: a path that is neither file nor directory, walk errors,
+    /// files that fail to decode and records that fail to parse. In a
+    /// directory the iteration continues with the next file after an error.
+    ///
+    /// ```
+    /// use bi5::Bi5;
+    /// let bi5 = Bi5::new("test/test.bi5", None);
+    /// for item in bi5.try_iter() {
+    ///     let (date_time, tick) = item.expect("Read error");
+    ///     println!("{},{}", date_time, tick);
+    /// }
+    /// ```
+    pub fn try_iter(&self) -> TryIter {
+        if self.is_file() {
+            match self.file_iter() {
+                Ok(file_iter) => TryIter { error: None, iter: Bi5Iter::File(file_iter) },
+                Err(e) => TryIter { error: Some(e), iter: Bi5Iter::Empty },
+            }
+        } else if self.path.is_dir() {
+            TryIter { error: None, iter: Bi5Iter::Dir(self.dir_iter(self.walk_dir())) }
+        } else {
+            TryIter { error: Some(self.invalid_path()), iter: Bi5Iter::Empty }
+        }
+    }
+
+    /// Returns an iterator that, in addition to the ticks, yields a
+    /// `Bi5Item::EmptyHour` marker for each file without ticks
+    ///
+    /// This distinguishes an hour without data (an empty file) from a missing
+    /// file. Like `iter`, the stream ends at the first file that fails.
+    ///
+    /// ```
+    /// use bi5::{Bi5, Bi5Item};
+    /// let bi5 = Bi5::new("test/test.bi5", None);
+    /// let empty = bi5.iter_with_markers().unwrap().filter(|item| matches!(item, Bi5Item::EmptyHour(_))).count();
+    /// assert_eq!(empty, 0);
+    /// ```
+    pub fn iter_with_markers(&self) -> Result<MarkerIter, Error> {
+        let iter = if self.is_file() {
+            Bi5Iter::File(self.iter_file()?)
+        } else if self.path.is_dir() {
+            Bi5Iter::Dir(self.dir_iter(self.walk_dir()))
+        } else {
+            return Err(self.invalid_path().into());
+        };
+        Ok(MarkerIter { iter, fresh: true })
+    }
+
+    /// Returns an iterator over the hour files of a single day directory
+    /// `.../YYYY/MM/DD/`, or `Error` if `day_dir` is not a directory
+    ///
+    /// Only the hour files directly inside `day_dir` are read, in hour order.
+    /// As with `iter` each tick's time is computed from its own file start,
+    /// so the result is one continuous and correctly timed day stream.
+    pub fn iter_day<P:AsRef<Path>>(day_dir: P) -> Result<Bi5Iter, Error> {
+        let day_dir = day_dir.as_ref();
+        if !day_dir.is_dir() {
+            return Err(anyhow!("{} must be dir", day_dir.to_string_lossy()));
+        }
+        Bi5::new(day_dir, None).iter_walk(WalkDir::new(day_dir).max_depth(1))
+    }
+
+    /// Returns an iterator over the hour file of `symbol` containing `dt` in
+    /// the archive `root`, i.e. `root/SYMBOL/YYYY/MM/DD/HHh_ticks.bi5` with
+    /// the 0-indexed Dukascopy month, or `Error` if there is no such file
+    ///
+    /// ```
+    /// use bi5::Bi5;
+    /// let dt = chrono::NaiveDate::from_ymd_opt(2022, 12, 16).unwrap().and_hms_opt(14, 0, 0).unwrap();
+    /// assert!(Bi5::open_hour("test".as_ref(), "EURUSD", dt).is_err());
+    /// ```
+    pub fn open_hour(root: &Path, symbol: &str, dt: NaiveDateTime) -> Result<FileIter, Error> {
+        let path = root.join(dukascopy_path(symbol, dt));
+        let hour = dt.date().and_hms_opt(dt.hour(), 0, 0).expect("valid hour");
+        Bi5::new(path, Some(hour)).iter_file()
+    }
+
+    /// Returns an iterator over `(epoch_millis, bid, ask)` tuples or `Error`
+    ///
+    /// `epoch_millis` is the absolute tick time as Unix milliseconds, `bid` and
+    /// `ask` are scaled by `point_value` (see `Price::as_f64`).
+    ///
+    /// ```
+    /// use bi5::Bi5;
+    /// let bi5 = Bi5::new("test/test.bi5", None);
+    /// let (_, bid, ask) = bi5.iter_tuples(1e5).unwrap().next().unwrap();
+    /// assert_eq!((bid, ask), (1.33117, 1.33153));
+    /// ```
+    pub fn iter_tuples(&self, point_value: f64) -> Result<impl Iterator<Item = (i64, f64, f64)>, Error> {
+        Ok(self.iter()?.map(move |(t, tick)| {
+            (t.timestamp_millis(), tick.bid_price().as_f64(point_value), tick.ask_price().as_f64(point_value))
+        }))
+    }
+
+    /// Returns an iterator over the ticks of all bi5 files in a zip archive
+    ///
+    /// Entries are read in name order and dated from their path inside the
+    /// archive (e.g. `EURUSD/2022/11/16/14h_ticks.bi5`), other entries are
+    /// skipped. Stored and deflated entries are supported, any other method
+    /// is an error. An entry that fails to decode is yielded as `Err` and the
+    /// iteration continues with the next entry.
+    #[cfg(feature = "zip")]
+    pub fn from_zip(path: &Path) -> Result<ZipIter, Error> {
+        Ok(ZipIter::open(path)?)
+    }
+
+    /// Returns an iterator over the ticks of all bi5 files in a `.tar.gz`
+    ///
+    /// The archive is inflated while reading, without extracting it. Entries
+    /// are read in archive order and dated from their archived path (e.g.
+    /// `EURUSD/2022/11/16/14h_ticks.bi5`), other entries are skipped. The
+    /// first error, e.g. an entry that fails to decode, a bad tar header
+    /// checksum or a gzip CRC-32 mismatch at the end, is yielded as `Err` and
+    /// ends the iteration.
+    #[cfg(feature = "tar")]
+    pub fn from_tar_gz(path: &Path) -> Result<TarGzIter, Error> {
+        Ok(TarGzIter::open(path)?)
+    }
+
+    /// Returns an iterator with `f` applied to every tick, e.g. to correct a
+    /// constant price offset. The datetime is computed before `f` is applied.
+    ///
+    /// ```
+    /// use bi5::Bi5;
+    /// let bi5 = Bi5::new("test/test.bi5", None);
+    /// let (_, tick) = bi5.iter_map(|t| bi5::Tick { bid: t.bid + 2, ..t }).unwrap().next().unwrap();
+    /// assert_eq!(tick.bid, 133119);
+    /// ```
+    pub fn iter_map<F>(&self, mut f: F) -> Result<impl Iterator<Item = (NaiveDateTime, Tick)>, Error>
+    where F: FnMut(Tick) -> Tick
+    {
+        Ok(self.iter()?.map(move |(t, tick)| (t, f(tick))))
+    }
+
+    /// Iterate the files of a directory in the order of their first tick,
+    /// for archives whose paths don't encode the file start
+    ///
+    /// All files with the extension of `Bi5Builder::extension`, by default
+    /// `bi5`, are read, whether or not their path parses. Only the first tick
+    /// of each file is decoded to sort them. A file's start is its path date
+    /// if there is one, else the date of the `Bi5`, so files without path
+    /// dates are ordered by the `millisecs` of their first tick. Files
+    /// without ticks are skipped, one that fails to decode is an `Error`.
+    /// Like `iter` the stream ends at a file that fails later.
+    /// ```
+    /// use bi5::Bi5;
+    /// assert_eq!(Bi5::new("test", None).iter_sort_by_content().unwrap().count(), 10412 + 1);
+    /// ```
+    pub fn iter_sort_by_content(&self) -> Result<impl Iterator<Item = (NaiveDateTime, Tick)>, Error> {
+        if self.is_file() {
+            return Ok(Box::new(self.iter()?) as Box<dyn Iterator<Item = (NaiveDateTime, Tick)>>);
+        } else if !self.is_dir() {
+            return Err(self.invalid_path().into());
+        }
+        let extension = self.options.extension.clone().unwrap_or_else(|| "bi5".into());
+        let mut files: Vec<(NaiveDateTime, PathBuf)> = Vec::new();
+        for entry in self.walk_dir() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if self.options.skip_walk_errors => {
+                    self.notice(Notice::WalkError(e));
+                    continue
+                }
+                Err(e) => return Err(Bi5Error::from(e).into()),
+            };
+            if !entry.file_type().is_file() || entry.path().extension() != Some(extension.as_os_str()) {
+                continue;
+            }
+            let prefix = decode::decompress_prefix(File::open(entry.path())?, 4)
+                .map_err(|e| anyhow!("{}: {}", entry.path().to_string_lossy(), e))?;
+            let Ok(millisecs) = <[u8; 4]>::try_from(prefix) else { continue };
+            let file_start = path_datetime(entry.path()).unwrap_or(self.date_time);
+            let first = file_start + Duration::milliseconds(u32::from_be_bytes(millisecs) as i64);
+            files.push((first, entry.into_path()));
+        }
+        files.sort();
+        let dir = self.clone();
+        Ok(Box::new(files.into_iter()
+            .map(move |(_, path)| {
+                let file_start = path_datetime(&path).unwrap_or(dir.date_time);
+                dir.file(&path, file_start).file_iter()
+            })
+            .map_while(Result::ok)
+            .flatten()))
+    }
+
+    /// The ticks in `[start, end)`
+    ///
+    /// Like `iter().clamp_to_range(start, end)`, but the files of a
+    /// directory whose hour lies outside the window are skipped by the walk
+    /// without being decompressed. Ticks are still checked one by one, and
+    /// the iterator ends at the first tick at or after `end`.
+    /// ```
+    /// use bi5::Bi5;
+    /// let t = |s: &str| s.parse::<chrono::NaiveDateTime>().unwrap();
+    /// let window = Bi5::new("test/test.bi5", None).window(t("0000-01-01T00:40:00"), t("0000-01-01T00:41:00")).unwrap();
+    /// assert!(window.map(|(t, _)| t.format("%M").to_string()).all(|minute| minute == "40"));
+    /// ```
+    pub fn window(&self, start: NaiveDateTime, end: NaiveDateTime) -> Result<ClampToRange<Bi5Iter>, Error> {
+        Ok(self.iter_range(start, end)?.clamp_to_range(start, end))
+    }
+
+    /// The ticks of the files whose hour overlaps `[from, to)`
+    ///
+    /// Files outside are skipped by the walk without being decompressed, but
+    /// the ticks of the files inside aren't checked, so with bounds within an
+    /// hour there are ticks before `from` or after `to`; `window` also
+    /// clamps the ticks. An empty range gives `Bi5Iter::Empty`.
+    /// ```
+    /// use bi5::{Bi5, Bi5Iter};
+    /// let t = |s: &str| s.parse::<chrono::NaiveDateTime>().unwrap();
+    /// let bi5 = Bi5::new("test/test.bi5", None);
+    /// assert_eq!(bi5.iter_range(t("0000-01-01T00:00:00"), t("0000-01-01T01:00:00")).unwrap().count(), 10412);
+    /// assert!(matches!(bi5.iter_range(t("0000-01-01T01:00:00"), t("0000-01-01T02:00:00")).unwrap(), Bi5Iter::Empty));
+    /// ```
+    pub fn iter_range(&self, from: NaiveDateTime, to: NaiveDateTime) -> Result<Bi5Iter, Error> {
+        let mut bi5 = self.clone();
+        bi5.options.window = Some((from, to));
+        if from >= to || (bi5.is_file() && !bi5.in_window(bi5.date_time)) {
+            return Ok(Bi5Iter::Empty);
+        }
+        bi5.iter()
+    }
+
+    /// Average number of ticks per second between the first and the last
+    /// tick, optionally restricted to `range`, computed in one pass
+    ///
+    /// Returns 0 if there are fewer than two ticks.
+    /// ```
+    /// use bi5::Bi5;
+    /// let rate = Bi5::new("test/test.bi5", None).tick_rate(None).unwrap();
+    /// assert!((rate - 10412.0 / 1739.897).abs() < 1e-9);
+    /// ```
+    pub fn tick_rate(&self, range: Option<Range<NaiveDateTime>>) -> Result<f64, Error> {
+        let range = range.unwrap_or(NaiveDateTime::MIN..NaiveDateTime::MAX);
+        let mut first_last: Option<(NaiveDateTime, NaiveDateTime)> = None;
+        let mut count: u64 = 0;
+        for (t, _) in self.iter()?.clamp_to_range(range.start, range.end) {
+            first_last = Some((first_last.map_or(t, |(first, _)| first), t));
+            count += 1;
+        }
+        Ok(match first_last {
+            Some((first, last)) if count > 1 && last > first => 
+                count as f64 / ((last - first).num_milliseconds() as f64 / 1000.0),
+            _ => 0.0,
+        })
+    }
+
+    /// Returns an iterator decoding the ticks of an LZMA file incrementally,
+    /// or `Error` if `Bi5` is not a file
+    ///
+    /// This keeps peak memory low for large files, see `TickStream`. Of the
+    /// builder's options only the time shift applies, the others need the
+    /// whole decompressed buffer of `iter`.
+    /// ```
+    /// use bi5::Bi5;
+    /// let ticks = Bi5::new("test/test.bi5", None).iter_stream().unwrap();
+    /// assert_eq!(ticks.map(Result::unwrap).count(), 10412);
+    /// ```
+    pub fn iter_stream(&self) -> Result<TickStream<File>, Error> {
+        if !self.is_file() {
+            return Err(anyhow!("{} must be file", self.path.to_string_lossy()));
+        }
+        let date_time = self.date_time + self.options.time_shift.unwrap_or(Duration::zero());
+        Ok(TickStream::new(File::open(&self.path)?, date_time))
+    }
+
+    /// Returns an iterator over the ticks of a file or `Error` if `Bi5` is not a file
+    pub fn iter_file(&self) -> Result<FileIter, Error> {
+        if !self.is_file() {
+            return Err(anyhow!("{} must be file", self.path.to_string_lossy()));
+        }
+        Ok(self.file_iter()?)
+    }
+
+    /// Best-effort guess of the point value (see `Price::as_f64`) from the
+    /// first `sample` ticks, `None` without ticks
+    ///
+    /// If the path names a symbol that `PointTable::builtin` knows, e.g.
+    /// `EURUSD/2022/11/16/14h_ticks.bi5`, its point value is used. Otherwise
+    /// it is guessed from the median raw mid: `1e5` for `10^4..10^6` (quotes
+    /// of 0.1 to 10 like most FX pairs), `1e3` above and `1e2` below. The
+    /// magnitude alone can't tell e.g. `1.33117` from `133.117`, so a JPY
+    /// pair in an unlabeled file is taken for `1e5`.
+    /// ```
+    /// use bi5::Bi5;
+    /// assert_eq!(Bi5::new("test/test.bi5", None).infer_point_value(100), Some(1e5));
+    /// ```
+    pub fn infer_point_value(&self, sample: usize) -> Option<f64> {
+        self.resolve_point_value_with(None, sample)
+    }
+
+    /// Point value (see `Price::as_f64`) of `symbol_hint` or else the symbol
+    /// of the path, looked up in `PointTable::builtin`, falling back to the
+    /// guess from the prices of `infer_point_value` over the first
+    /// `POINT_VALUE_SAMPLE` ticks if neither is known. `None` only if it
+    /// comes to the guess and there are no ticks.
+    /// ```
+    /// use bi5::Bi5;
+    /// let bi5 = Bi5::new("test/test.bi5", None);
+    /// assert_eq!(bi5.resolve_point_value(Some("USDJPY")), Some(1e3));
+    /// assert_eq!(bi5.resolve_point_value(Some("DEU.IDX")), Some(1e5));
+    /// assert_eq!(bi5.resolve_point_value(None), Some(1e5));
+    /// ```
+    pub fn resolve_point_value(&self, symbol_hint: Option<&str>) -> Option<f64> {
+        self.resolve_point_value_with(symbol_hint, POINT_VALUE_SAMPLE)
+    }
+
+    fn resolve_point_value_with(&self, symbol_hint: Option<&str>, sample: usize) -> Option<f64> {
+        let table = PointTable::builtin();
+        let known = symbol_hint.and_then(|symbol| table.point_value(symbol))
+            .or_else(|| symbol_from_path(&self.path).and_then(|symbol| table.point_value(&symbol)));
+        if known.is_some() {
+            return known;
+        }
+        let mut mids: Vec<u64> = self.iter().ok()?.take(sample)
+            .map(|(_, tick)| (tick.bid as u64 + tick.ask as u64) / 2)
+            .collect();
+        if mids.is_empty() {
+            return None;
+        }
+        let mid = mids.len() / 2;
+        let median = *mids.select_nth_unstable(mid).1;
+        Some(match median {
+            0..=9_999 => 1e2,
+            10_000..=999_999 => 1e5,
+            _ => 1e3,
+        })
+    }
+
+    /// Time of the tick at position `n` of a file, `None` past the last tick,
+    /// or `Error` if `Bi5` is not a file
+    ///
+    /// The file is decompressed as usual, but only the `millisecs` of tick
+    /// `n` are read instead of parsing every tick like `iter().nth(n)`.
+    /// ```
+    /// use bi5::Bi5;
+    /// let bi5 = Bi5::new("test/test.bi5", None);
+    /// assert_eq!(bi5.nth_tick_time(1).unwrap(), bi5.iter().unwrap().nth(1).map(|(t, _)| t));
+    /// assert_eq!(bi5.nth_tick_time(10412).unwrap(), None);
+    /// ```
+    pub fn nth_tick_time(&self, n: usize) -> Result<Option<NaiveDateTime>, Error> {
+        let file_iter = self.iter_file()?;
+        if n >= file_iter.len() {
+            return Ok(None);
+        }
+        let start = n * size_of::<Tick>();
+        let buf = &file_iter.cursor.get_ref()[start..start + 4];
+        let millisecs = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        Ok(Some(file_iter.date_time() + Duration::milliseconds(millisecs as i64)))
+    }
+
+    /// Check that the file decompresses to a whole number of ticks, and to
+    /// the size given in the LZMA header if there is one, without parsing
+    /// the ticks
+    ///
+    /// This is meant as a fast gate before ingestion. Unlike iterating it
+    /// ignores `ignore_trailing_partial` and the `millisecs` options.
+    /// ```
+    /// use bi5::Bi5;
+    /// assert!(Bi5::new("test/test.bi5", None).verify_length().is_ok());
+    /// ```
+    pub fn verify_length(&self) -> Result<(), Bi5Error> {
+        if !self.is_file() {
+            return Err(self.invalid_path());
+        }
+        decode::check_len(&self.decompress()?)
+    }
+
+    /// Write the decompressed file, the concatenated 20 byte records, to
+    /// `out` and return the number of bytes, or `Error` if `Bi5` is not a
+    /// file or doesn't decompress to whole ticks
+    /// ```
+    /// use bi5::Bi5;
+    /// let out = std::env::temp_dir().join(format!("bi5-doc-raw-{}", std::process::id()));
+    /// assert_eq!(Bi5::new("test/test.bi5", None).decompress_to_file(&out).unwrap(), 10412 * 20);
+    /// # std::fs::remove_file(&out).unwrap();
+    /// ```
+    pub fn decompress_to_file(&self, out: &Path) -> Result<usize, Error> {
+        if !self.is_file() {
+            return Err(self.invalid_path().into());
+        }
+        let buf = self.decompress()?;
+        decode::check_len(&buf)?;
+        std::fs::write(out, &buf)?;
+        Ok(buf.len())
+    }
+
+    /// Decompress the file with the codec and buffer options
+    fn decompress(&self) -> Result<Vec<u8>, Bi5Error> {
+        let file: File = File::open(&self.path)?;
+        let key = match self.options.cache {
+            Some(_) => DecodeCache::key(&self.path, &file.metadata()?),
+            None => None,
+        };
+        if let (Some(cache), Some(key)) = (&self.options.cache, &key) {
+            if let Some(buf) = cache.get(key) {
+                return Ok(buf);
+            }
+        }
+        let read_buffer = self.options.read_buffer.unwrap_or(decode::DEFAULT_READ_BUFFER);
+        let mut buf = decode::decompress_with(file, self.options.decode_capacity, self.options.codec, read_buffer)?;
+        if self.options.decompress_twice && !buf.len().is_multiple_of(size_of::<Tick>())
+            && matches!(decode::compressed_format(&buf), Some("LZMA" | "xz")) {
+            buf = decode::decompress(&buf[..])?;
+        }
+        if let (Some(cache), Some(key)) = (&self.options.cache, key) {
+            cache.insert(key, buf.clone());
+        }
+        Ok(buf)
+    }
+
+    /// Decode the file into a `FileIter`
+    fn file_iter(&self) -> Result<FileIter, Bi5Error> {
+        let mut buf: Vec<u8> = self.decode()?;
+        let (head, tail) = self.options.trim_edges;
+        if head > 0 || tail > 0 {
+            let n = buf.len() / size_of::<Tick>();
+            let end = n.saturating_sub(tail).max(head.min(n));
+            buf.truncate(end * size_of::<Tick>());
+            buf.drain(..head.min(n) * size_of::<Tick>());
+        }
+        let date_time = self.date_time + self.options.time_shift.unwrap_or(Duration::zero());
+        Ok(FileIter::new(buf, date_time))
+    }
+
+    /// The (shifted) file start and all ticks of the file, see `files`
+    fn decode_chunk(&self) -> Result<(NaiveDateTime, Vec<Tick>), Bi5Error> {
+        let file_iter = self.file_iter()?;
+        Ok((file_iter.date_time(), file_iter.map(|x|x.1).collect()))
+    }
+
+    /// Add `offset` to every tick time, e.g. to correct a known constant
+    /// clock skew of a feed, see `Bi5Builder::time_shift`
+    ///
+    /// ```
+    /// use bi5::Bi5;
+    /// let bi5 = Bi5::new("test/test.bi5", None).with_time_shift(chrono::Duration::seconds(-60));
+    /// assert_eq!(bi5.iter().unwrap().next().unwrap().0.to_string(), "0000-01-01 00:30:00.002");
+    /// ```
+    pub fn with_time_shift(mut self, offset: Duration) -> Self {
+        self.options.time_shift = Some(offset);
+        self
+    }
+
+    /// Keep decompressed files in `cache`, so repeated reads of unchanged
+    /// files skip LZMA, see `DecodeCache`. The files of a directory share it.
+    pub fn with_cache(mut self, cache: DecodeCache) -> Self {
+        self.options.cache = Some(cache);
+        self
+    }
+
+    /// Read the files of a directory in `order` with `iter` and `files`
+    /// (default `Order::Chronological`). The ticks within each file are
+    /// always in file order, so with `Order::ReverseChronological` the times
+    /// jump back at every file boundary. `scan` and `audit` stay
+    /// chronological.
+    ///
+    /// ```
+    /// use bi5::*;
+    /// let bi5 = Bi5::new("test/test.bi5", None).with_order(Order::ReverseChronological);
+    /// assert_eq!(bi5.iter().unwrap().next().unwrap().1.millisecs, 1860002);
+    /// ```
+    pub fn with_order(mut self, order: Order) -> Self {
+        self.options.order = order;
+        self
+    }
+
+    /// Returns an iterator over whole decoded files
+    ///
+    /// Each item is the file datetime and all ticks of that file. Files are
+    /// decoded lazily one at a time, so the chunks can be handed to worker
+    /// threads without holding the whole directory in memory.
+    ///
+    /// ```
+    /// use bi5::Bi5;
+    /// let bi5 = Bi5::new("test/test.bi5", None);
+    /// for file in bi5.files() {
+    ///     let (date_time, ticks) = file.expect("File error");
+    ///     println!("{}: {} ticks", date_time, ticks.len());
+    /// }
+    /// ```
+    pub fn files(&self) -> Bi5Files {
+        if self.is_file() {
+            Bi5Files { file: Some(Ok(self.file(&self.path, self.date_time))), walk_dir: None, dir: None }
+        } else if self.path.is_dir() {
+            let walk_dir = self.sort_walk(self.walk_dir()).into_iter();
+            Bi5Files { file: None, walk_dir: Some(walk_dir), dir: Some(self.file(&self.path, self.date_time)) }
+        } else {
+            let err = self.invalid_path().into();
+            Bi5Files { file: Some(Err(err)), walk_dir: None, dir: None }
+        }
+    }
+
+    /// First and last file start found in the paths, without decompressing
+    /// any file, or `None` if there are no bi5 files
+    ///
+    /// The last file covers one more hour after its start. Entries the walk
+    /// can't read are ignored.
+    /// ```
+    /// use bi5::Bi5;
+    /// assert!(Bi5::new("test", None).date_bounds().is_none());
+    /// ```
+    pub fn date_bounds(&self) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        self.walk_dir()
+            .into_iter()
+            .filter_map(|entry| self.walk_datetime(entry.ok()?.path()))
+            .fold(None, |bounds, t| match bounds {
+                None => Some((t, t)),
+                Some((min, max)) => Some((min.min(t), max.max(t))),
+            })
+    }
+
+    /// Hours between the first and the last file (see `date_bounds`) without
+    /// a file, or with an empty (0 byte) one, in chronological order
+    ///
+    /// Only the paths and file sizes are looked at, nothing is decompressed.
+    /// ```
+    /// use bi5::{Bi5, CoverageOptions};
+    /// let options = CoverageOptions { skip_weekends: true, ..Default::default() };
+    /// assert!(Bi5::new("test", None).missing_hours(&options).is_empty());
+    /// ```
+    pub fn missing_hours(&self, options: &CoverageOptions) -> Vec<NaiveDateTime> {
+        let present: std::collections::HashSet<NaiveDateTime> = self.walk_dir()
+            .into_iter()
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let t = self.walk_datetime(entry.path())?;
+                (entry.metadata().ok()?.len() > 0).then_some(t)
+            })
+            .collect();
+        let mut missing = Vec::new();
+        if let Some((first, last)) = self.date_bounds() {
+            let mut t = first;
+            while t <= last {
+                if !present.contains(&t) && options.expects_data(t) {
+                    missing.push(t);
+                }
+                t += Duration::hours(1);
+            }
+        }
+        missing
+    }
+
+    /// Write a sidecar index `<file>.idx` with the tick count next to each
+    /// file, returns the number of files indexed
+    ///
+    /// The counts are taken with the options of this `Bi5`, and are only used
+    /// by a `count` with the same count changing options. The first file that
+    /// fails to decode ends the indexing with `Error`.
+    pub fn write_index(&self) -> Result<usize, Error> {
+        let scan = self.scan()?;
+        let key = self.options.count_key();
+        for (path, date_time) in scan.files() {
+            let ticks = self.file(path, *date_time).file_iter()?.len();
+            index::write_index(path, ticks, &key)?;
+        }
+        Ok(scan.files().len())
+    }
+
+    /// Number of ticks in all files
+    ///
+    /// Files with an up to date sidecar index (see `write_index`) written with
+    /// the same options aren't decompressed. A file that fails to decode gives
+    /// `Error`.
+    /// ```
+    /// use bi5::Bi5;
+    /// assert_eq!(Bi5::new("test/test.bi5", None).count().unwrap(), 10412);
+    /// ```
+    pub fn count(&self) -> Result<usize, Error> {
+        let mut count = 0;
+        let key = self.options.count_key();
+        for (path, date_time) in self.scan()?.files() {
+            count += match index::read_index(path, &key) {
+                Some(ticks) => ticks,
+                None => self.file(path, *date_time).file_iter()?.len(),
+            };
+        }
+        Ok(count)
+    }
+
+    /// Tick count and the first and last tick of all files
+    ///
+    /// Each file is decompressed, but only its first and last record are
+    /// parsed, the count comes from the buffer length. A file that fails to
+    /// decode gives `Error`.
+    /// ```
+    /// use bi5::Bi5;
+    /// let summary = Bi5::new("test/test.bi5", None).summary().unwrap();
+    /// assert_eq!(summary.tick_count, 10412);
+    /// assert_eq!(summary.last.unwrap().1.millisecs, 3599899);
+    /// ```
+    pub fn summary(&self) -> Result<Bi5Summary, Error> {
+        let mut summary = Bi5Summary::default();
+        for (path, date_time) in self.scan()?.files() {
+            let mut file_iter = self.file(path, *date_time).file_iter()?;
+            summary.tick_count += file_iter.len();
+            let Some(first) = file_iter.next() else { continue };
+            let last = file_iter.next_back().unwrap_or(first);
+            if summary.first.is_none_or(|(t, _)| first.0 < t) {
+                summary.first = Some(first);
+            }
+            if summary.last.is_none_or(|(t, _)| last.0 >= t) {
+                summary.last = Some(last);
+            }
+        }
+        Ok(summary)
+    }
+
+    /// The first `max_ticks` ticks and whether there were more
+    ///
+    /// Bounds the memory of collecting a large tree. Files after the one
+    /// where the cap is reached aren't decompressed, except the next one when
+    /// the cap falls exactly on a file end, to tell whether more ticks follow.
+    /// ```
+    /// use bi5::Bi5;
+    /// let (ticks, truncated) = Bi5::new("test/test.bi5", None).collect_capped(100).unwrap();
+    /// assert_eq!((ticks.len(), truncated), (100, true));
+    /// ```
+    pub fn collect_capped(&self, max_ticks: usize) -> Result<(Vec<(NaiveDateTime, Tick)>, bool), Error> {
+        let mut iter = self.iter()?;
+        let mut ticks = Vec::with_capacity(max_ticks.min(iter.size_hint().0));
+        ticks.extend(iter.by_ref().take(max_ticks));
+        let truncated = ticks.len() == max_ticks && iter.next().is_some();
+        Ok((ticks, truncated))
+    }
+
+    /// Returns an iterator over the ticks whose time of day lies in the
+    /// session `[open, close)`, e.g. exchange hours of an index CFD
+    ///
+    /// For an overnight session (`open > close`) the session runs from `open`
+    /// to `close` on the next day. Hour files entirely outside the session
+    /// are not decompressed. Like `iter`, the stream ends at the first file
+    /// that fails.
+    /// ```
+    /// use bi5::Bi5;
+    /// use chrono::NaiveTime;
+    /// let hm = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+    /// let bi5 = Bi5::new("test/test.bi5", None);
+    /// assert_eq!(bi5.iter_within_hours(hm(8, 0), hm(9, 0)).unwrap().count(), 0);
+    /// assert_eq!(bi5.iter_within_hours(hm(23, 0), hm(0, 45)).unwrap().count(),
+    ///            bi5.iter().unwrap().filter(|(t, _)| t.time() < hm(0, 45)).count());
+    /// ```
+    pub fn iter_within_hours(&self, open: NaiveTime, close: NaiveTime) 
+        -> Result<impl Iterator<Item = (NaiveDateTime, Tick)>, Error> 
+    {
+        let in_session = move |t: NaiveTime| if open <= close {
+            open <= t && t < close
+        } else {
+            t >= open || t < close
+        };
+        let shift = self.options.time_shift.unwrap_or(Duration::zero());
+        let files: Vec<(PathBuf, NaiveDateTime)> = self.scan()?.files.into_iter()
+            .filter(|(_, date_time)| {
+                // an hour overlaps the session if it starts in it or the session starts in it
+                let start = (*date_time + shift).time();
+                let to_open = (open.num_seconds_from_midnight() + 86400 - start.num_seconds_from_midnight()) % 86400;
+                in_session(start) || to_open < 3600
+            })
+            .collect();
+        let dir = self.file(&self.path, self.date_time);
+        Ok(files.into_iter()
+            .map(move |(path, date_time)| dir.file(&path, date_time).file_iter())
+            .map_while(Result::ok)
+            .flatten()
+            .filter(move |(t, _)| in_session(t.time())))
+    }
+
+    /// Walk the directory once and keep the sorted list of files
+    ///
+    /// The returned `Bi5Scan` can be iterated repeatedly without walking the
+    /// directory again. Files added or removed later are not picked up.
+    ///
+    /// ```
+    /// use bi5::Bi5;
+    /// let scan = Bi5::new("test/test.bi5", None).scan().unwrap();
+    /// assert_eq!(scan.iter().count(), scan.iter().count());
+    /// ```
+    pub fn scan(&self) -> Result<Bi5Scan, Error> {
+        let dir = self.file(&self.path, self.date_time);
+        if self.is_file() {
+            return Ok(Bi5Scan { files: vec![(self.path.clone(), self.date_time)], dir });
+        } else if !self.path.is_dir() {
+            return Err(self.invalid_path().into());
+        }
+        let mut walk_dir = self.walk_dir().sort_by_key(direntry_to_key).into_iter();
+        let mut files = Vec::new();
+        while let Some((entry, date_time)) = self.forward_to_next_good_file(&mut walk_dir)? {
+            files.push((entry.into_path(), date_time));
+        }
+        files.sort_by(|(a, a_time), (b, b_time)| (a_time, a).cmp(&(b_time, b)));
+        Ok(Bi5Scan { files, dir })
+    }
+
+    /// Check every file: decoding, tick count, time order and hour alignment
+    /// (see `Bi5Builder::tolerance`), crossed and locked quotes and the time
+    /// range, e.g. after a bulk download
+    ///
+    /// Files that fail to decode are recorded in the report, only a failing
+    /// walk is an `Error`.
+    /// ```
+    /// use bi5::Bi5;
+    /// let report = Bi5::new("test/test.bi5", None).audit().unwrap();
+    /// assert_eq!(report.ticks(), 10412);
+    /// assert_eq!(report.problems().count(), 0);
+    /// ```
+    pub fn audit(&self) -> Result<AuditReport, Error> {
+        let files = self.scan()?.files().iter().map(|(path, date_time)| self.audit_file(path, *date_time)).collect();
+        Ok(AuditReport { files })
+    }
+
+    /// Like `audit` but decodes the files on `threads` threads, 0 for one per
+    /// core. The report is the same, in chronological order.
+    /// ```
+    /// use bi5::Bi5;
+    /// let report = Bi5::new("test/test.bi5", None).audit_parallel(0).unwrap();
+    /// assert_eq!(report, Bi5::new("test/test.bi5", None).audit().unwrap());
+    /// ```
+    pub fn audit_parallel(&self, threads: usize) -> Result<AuditReport, Error> {
+        let scan = self.scan()?;
+        let files = parallel_map(scan.files(), threads, |(path, date_time)| self.audit_file(path, *date_time));
+        Ok(AuditReport { files })
+    }
+
+    /// All ticks of a file or directory like `iter().collect()`, with the
+    /// files decoded on `threads` threads, 0 for one per core
+    ///
+    /// The files are listed in the order of the walk of `iter`, including
+    /// `Order`, and their ticks concatenated in that order, so the result is
+    /// the same as the sequential one. Unlike `iter`, a file that fails to
+    /// decode is an `Error` rather than the end of the data, unless
+    /// `Bi5Builder::skip_errors` skips it. The skipped files are returned with
+    /// their errors next to the ticks, like `Bi5Iter::failed_files`.
+    /// ```
+    /// use bi5::Bi5;
+    /// let read = Bi5::new("test/test.bi5", None).par_read(0).unwrap();
+    /// assert_eq!(read.ticks, Bi5::new("test/test.bi5", None).iter().unwrap().collect::<Vec<_>>());
+    /// assert!(read.failed_files.is_empty());
+    /// ```
+    pub fn par_read(&self, threads: usize) -> Result<ParRead, Error> {
+        if self.is_file() {
+            return Ok(ParRead { ticks: self.file_iter()?.collect(), failed_files: Vec::new() });
+        } else if !self.path.is_dir() {
+            return Err(self.invalid_path().into());
+        }
+        let mut walk_dir = self.sort_walk(self.walk_dir()).into_iter();
+        let mut files = Vec::new();
+        while let Some((entry, date_time)) = self.forward_to_next_good_file(&mut walk_dir)? {
+            files.push((entry.into_path(), date_time));
+        }
+        let decoded = parallel_map(&files, threads, |(path, date_time)| self.file(path, *date_time).file_iter());
+        let (mut ticks, mut failed_files) = (Vec::new(), Vec::new());
+        for ((path, _), file_iter) in files.into_iter().zip(decoded) {
+            match file_iter {
+                Ok(file_iter) => ticks.extend(file_iter),
+                Err(e) if self.options.skip_errors => failed_files.push((path, e)),
+                Err(e) => return Err(anyhow!("{}: {}", path.to_string_lossy(), e)),
+            }
+        }
+        Ok(ParRead { ticks, failed_files })
+    }
+
+    /// Check the file at `path` for `audit`
+    fn audit_file(&self, path: &Path, date_time: NaiveDateTime) -> FileAudit {
+        let tolerance = self.options.tolerance.map_or(0, |tolerance| tolerance.num_milliseconds());
+        let mut file = FileAudit {
+            path: path.to_path_buf(), date_time, error: None,
+            ticks: 0, monotonic: true, out_of_hour: 0, crossed: 0, locked: 0, time_range: None,
+        };
+        match self.file(path, date_time).file_iter() {
+            Ok(file_iter) => {
+                let mut prev = 0;
+                for (t, tick) in file_iter {
+                    file.ticks += 1;
+                    file.monotonic &= tick.millisecs as i64 + tolerance >= prev;
+                    file.out_of_hour += (tick.millisecs as i64 >= HOUR_MILLISECS as i64 + tolerance) as usize;
+                    prev = prev.max(tick.millisecs as i64);
+                    file.crossed += tick.is_crossed() as usize;
+                    file.locked += tick.is_locked() as usize;
+                    file.time_range = Some(file.time_range.map_or((t, t), |(min, max)| (min.min(t), max.max(t))));
+                }
+            }
+            Err(e) => file.error = Some(e.to_string()),
+        }
+        file
+    }
+
+    /// Decode on a background thread and receive the items of `try_iter`
+    /// over a channel
+    ///
+    /// The consumer works on the ticks while the next file decompresses. The
+    /// channel holds up to `SPAWN_READER_BOUND` ticks, the reader blocks when
+    /// the consumer falls behind and stops when the receiver is dropped.
+    ///
+    /// ```
+    /// use bi5::Bi5;
+    /// let rx = Bi5::new("test/test.bi5", None).spawn_reader();
+    /// assert_eq!(rx.iter().filter_map(Result::ok).count(), 10412);
+    /// ```
+    pub fn spawn_reader(self) -> Receiver<Result<(NaiveDateTime, Tick), Bi5Error>> {
+        let (tx, rx) = sync_channel(SPAWN_READER_BOUND);
+        thread::spawn(move || {
+            for item in self.try_iter() {
+                if tx.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+
+    /// Returns an endless iterator over the ticks of a directory that keeps
+    /// watching for new files, or `Error` if `Bi5` is not a directory
+    ///
+    /// After the existing files the directory is scanned again every `poll`,
+    /// the ticks of files that appeared since are yielded in path order. An
+    /// incomplete LZMA stream can't be decoded, so a file that fails is
+    /// retried on the next scan until it's complete. A file whose length or
+    /// modification time changes, e.g. one created empty and written later,
+    /// is read again and only its ticks beyond those already yielded are
+    /// yielded. Writers should still prefer to write to a temporary name and
+    /// rename.
+    pub fn follow(&self, poll: std::time::Duration) -> Result<Follow, Error> {
+        if !self.path.is_dir() {
+            return Err(anyhow!("{} must be dir", self.path.to_string_lossy()));
+        }
+        Ok(Follow {
+            dir: self.file(&self.path, self.date_time),
+            poll,
+            seen: HashMap::new(),
+            pending: VecDeque::new(),
+            file_iter: None,
+            scanned: false,
+        })
+    }
+}
+
+/// Endless iterator over a growing directory, see `Bi5::follow`
+#[cfg(feature = "fs")]
+pub struct Follow {
+    dir: Bi5,
+    poll: std::time::Duration,
+    /// length and modification time of the files read, with the number of
+    /// ticks yielded from each
+    seen: HashMap<PathBuf, (u64, Option<SystemTime>, usize)>,
+    /// new files of the last scan
+    pending: VecDeque<(PathBuf, NaiveDateTime)>,
+    file_iter: Option<FileIter>,
+    scanned: bool,
+}
+
+#[cfg(feature = "fs")]
+impl Iterator for Follow {
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.file_iter.as_mut().and_then(Iterator::next) {
+                return Some(item);
+            }
+            if let Some((path, date_time)) = self.pending.pop_front() {
+                // the metadata is taken first, a write during the read
+                // changes it and the file is read again on the next scan
+                let Some((len, modified)) = file_stamp(&path) else { continue };
+                // incomplete files are picked up again by the next scan
+                if let Ok(file_iter) = self.dir.file(&path, date_time).file_iter() {
+                    let yielded = self.seen.get(&path).map_or(0, |&(_, _, ticks)| ticks);
+                    let ticks = file_iter.len();
+                    // an empty file is probably still being written
+                    if ticks > 0 {
+                        self.seen.insert(path, (len, modified, ticks.max(yielded)));
+                    }
+                    self.file_iter = Some(file_iter.skip_ticks(yielded));
+                }
+                continue;
+            }
+            if std::mem::replace(&mut self.scanned, true) {
+                thread::sleep(self.poll);
+            }
+            if let Ok(scan) = self.dir.scan() {
+                self.pending = scan.files().iter()
+                    .filter(|(path, _)| match (self.seen.get(path), file_stamp(path)) {
+                        (Some(&(len, modified, _)), Some(stamp)) => stamp != (len, modified),
+                        _ => true,
+                    })
+                    .cloned()
+                    .collect();
+            }
+        }
+    }
+}
+
+/// Length and modification time of the file at `path`, `None` if it's gone
+#[cfg(feature = "fs")]
+fn file_stamp(path: &Path) -> Option<(u64, Option<SystemTime>)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()))
+}
+
+/// Capacity of the channel of `Bi5::spawn_reader` in ticks, a few busy hours
+#[cfg(feature = "fs")]
+pub const SPAWN_READER_BOUND: usize = 1 << 16;
+
+/// Iterator over the decoded files of a `Bi5`, see `Bi5::files`
+#[cfg(feature = "fs")]
+pub struct Bi5Files {
+    file: Option<Result<Bi5, Error>>,
+    walk_dir: Option<walkdir::IntoIter>,
+    dir: Option<Bi5>,
+}
+
+#[cfg(feature = "fs")]
+impl Iterator for Bi5Files {
+    type Item = Result<(NaiveDateTime, Vec<Tick>), Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(file) = self.file.take() {
+            return Some(file.and_then(|file| Ok(file.decode_chunk()?)));
+        }
+        let walk_dir = self.walk_dir.as_mut()?;
+        let dir = self.dir.as_ref()?;
+        match dir.forward_to_next_good_file(walk_dir) {
+            Ok(Some((entry, date_time))) => {
+                Some(dir.file(entry.path(), date_time).decode_chunk().map_err(Error::from))
+            }
+            Ok(None) => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+/// Tick count and time span of a `Bi5`, see `Bi5::summary`
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[non_exhaustive]
+pub struct Bi5Summary {
+    /// Number of ticks in all files
+    pub tick_count: usize,
+    /// The earliest of the first ticks of the files, `None` without ticks
+    pub first: Option<(NaiveDateTime, Tick)>,
+    /// The latest of the last ticks of the files, `None` without ticks
+    pub last: Option<(NaiveDateTime, Tick)>,
+}
+
+/// Ticks and skipped files of `Bi5::par_read`
+#[cfg(feature = "fs")]
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct ParRead {
+    /// All ticks, in the order of `iter`
+    pub ticks: Vec<(NaiveDateTime, Tick)>,
+    /// The files skipped by `Bi5Builder::skip_errors`, with their errors
+    pub failed_files: Vec<(PathBuf, Bi5Error)>,
+}
+
+/// Sorted file list of a `Bi5`, see `Bi5::scan`
+#[cfg(feature = "fs")]
+pub struct Bi5Scan {
+    files: Vec<(PathBuf, NaiveDateTime)>,
+    /// supplies the options
+    dir: Bi5,
+}
+
+#[cfg(feature = "fs")]
+impl Bi5Scan {
+    /// The files and their datetimes in chronological order, files of the
+    /// same hour (e.g. of different instruments) ordered by path
+    pub fn files(&self) -> &[(PathBuf, NaiveDateTime)] {
+        &self.files
+    }
+
+    /// Returns an iterator over the ticks of all files, like `Bi5::iter` the
+    /// stream ends at the first file that fails to decode
+    pub fn iter(&self) -> ScanIter<'_> {
+        ScanIter { files: self.files.iter(), file_iter: None, dir: &self.dir }
+    }
+}
+
+/// Iterator over the ticks of a `Bi5Scan`
+#[cfg(feature = "fs")]
+pub struct ScanIter<'a> {
+    files: std::slice::Iter<'a, (PathBuf, NaiveDateTime)>,
+    file_iter: Option<FileIter>,
+    dir: &'a Bi5,
+}
+
+#[cfg(feature = "fs")]
+impl Iterator for ScanIter<'_> {
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.file_iter.as_mut().and_then(Iterator::next) {
+                return Some(item);
+            }
+            let (path, date_time) = self.files.next()?;
+            self.file_iter = Some(self.dir.file(path, *date_time).file_iter().ok()?);
+        }
+    }
+}
+
+/// Item of `MarkerIter`
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bi5Item {
+    /// A tick and its datetime
+    Tick(NaiveDateTime, Tick),
+    /// A file without ticks, with the datetime of its start
+    EmptyHour(NaiveDateTime),
+}
+
+/// Iterator over ticks and empty-file markers, see `Bi5::iter_with_markers`
+#[cfg(feature = "fs")]
+pub struct MarkerIter {
+    iter: Bi5Iter,
+    /// the current file was just opened and not checked for emptiness yet
+    fresh: bool,
+}
+
+#[cfg(feature = "fs")]
+impl Iterator for MarkerIter {
+    type Item = Bi5Item;
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.iter {
+            Bi5Iter::File(file_iter) => {
+                if std::mem::replace(&mut self.fresh, false) && file_iter.len() == 0 {
+                    return Some(Bi5Item::EmptyHour(file_iter.date_time()));
+                }
+                file_iter.next().map(|(t, tick)| Bi5Item::Tick(t, tick))
+            }
+            Bi5Iter::Dir(dir_iter) => loop {
+                if let Some(file_iter) = dir_iter.file_iter.as_mut() {
+                    if std::mem::replace(&mut self.fresh, false) && file_iter.len() == 0 {
+                        return Some(Bi5Item::EmptyHour(file_iter.date_time()));
+                    }
+                    if let Some((t, tick)) = file_iter.next() {
+                        return Some(Bi5Item::Tick(t, tick));
+                    }
+                }
+                dir_iter.open_next()?.ok()?;
+                self.fresh = true;
+            },
+            Bi5Iter::Empty => None,
+        }
+    }
+}
+
+/// Iterator over `Result` items, see `Bi5::try_iter`
+#[cfg(feature = "fs")]
+pub struct TryIter {
+    error: Option<Bi5Error>,
+    iter: Bi5Iter,
+}
+
+#[cfg(feature = "fs")]
+impl Iterator for TryIter {
+    type Item = Result<(NaiveDateTime, Tick), Bi5Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+        match &mut self.iter {
+            Bi5Iter::Empty => None,
+            Bi5Iter::File(file_iter) => file_iter.try_next(),
+            Bi5Iter::Dir(dir_iter) => dir_iter.try_next(),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Iterator for Bi5Iter {
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Bi5Iter::Empty => { None }
+            Bi5Iter::File(file_iter) => file_iter.next(),
+            Bi5Iter::Dir(dir_iter) => dir_iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Bi5Iter::Empty => (0, Some(0)),
+            Bi5Iter::File(file_iter) => file_iter.size_hint(),
+            Bi5Iter::Dir(dir_iter) => dir_iter.size_hint(),
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        match self {
+            Bi5Iter::File(file_iter) => file_iter.nth(n),
+            iter => {
+                // `skip` would call `nth` again
+                for _ in 0..n {
+                    iter.next()?;
+                }
+                iter.next()
+            }
+        }
+    }
+
+    fn last(self) -> Option<Self::Item> {
+        match self {
+            Bi5Iter::File(file_iter) => file_iter.last(),
+            iter => iter.fold(None, |_, item| Some(item)),
+        }
+    }
+
+    fn count(self) -> usize {
+        match self {
+            Bi5Iter::File(file_iter) => file_iter.len(),
+            iter => iter.fold(0, |n, _| n + 1),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Iterator for FileIter {
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        let date_time = self.date_time;
+        Tick::read(&mut self.cursor).ok().map(|tick|(tick.time(date_time), tick))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+
+    // the records have a fixed size, so these seek instead of reading the
+    // ticks in between
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.advance(n);
+        self.next()
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+
+    fn count(self) -> usize {
+        self.len()
+    }
+}
+
+#[cfg(feature = "fs")]
+impl DoubleEndedIterator for FileIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len() == 0 {
+            return None;
+        }
+        let buf = self.cursor.get_mut();
+        let start = buf.len() - size_of::<Tick>();
+        let tick = Tick::read(&mut Cursor::new(&buf[start..])).ok()?;
+        buf.truncate(start);
+        Some((tick.time(self.date_time), tick))
+    }
+}
+
+#[cfg(feature = "fs")]
+impl ExactSizeIterator for FileIter {
+    fn len(&self) -> usize {
+        let remaining = self.cursor.get_ref().len() - self.cursor.position() as usize;
+        remaining / size_of::<Tick>()
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Iterator for DirIter {
+    type Item = (NaiveDateTime, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        // like before, the stream ends at the first file that fails, and
+        // stays ended even though the walk could go on
+        if self.done {
+            return None;
+        }
+        loop {
+            match self.try_next() {
+                Some(Ok(item)) => return Some(item),
+                Some(Err(e)) if self.dir.options.skip_errors => {
+                    let path = match &e {
+                        Bi5Error::Walk(walk_error) => walk_error.path().unwrap_or(&self.dir.path).to_path_buf(),
+                        _ => self.path.clone(),
+                    };
+                    self.failed_files.push((path, e));
+                }
+                _ => {
+                    self.done = true;
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.file_iter.as_ref().map_or(0, FileIter::len), None)
+    }
+}
+
+// After the first `None` all of them keep returning `None`
+#[cfg(feature = "fs")]
+impl FusedIterator for Bi5Iter {}
+
+#[cfg(feature = "fs")]
+impl FusedIterator for FileIter {}
+
+#[cfg(feature = "fs")]
+impl FusedIterator for DirIter {}
+
+/// Bars of `interval` of the mid price over a day directory `.../YYYY/MM/DD/`,
+/// see `Bi5::iter_day` and `time_bars`
+///
+/// The prices of the bars are scaled by `point_value`, see `Price::as_f64`.
+/// ```
+/// use bi5::*;
+/// let root = std::env::temp_dir().join(format!("bi5-doc-day-bars-{}", std::process::id()));
+/// let day = root.join("EURUSD/2022/11/16");
+/// std::fs::create_dir_all(&day).unwrap();
+/// std::fs::copy("test/test.bi5", day.join("14h_ticks.bi5")).unwrap();
+/// let bars = read_day_bars(&day, chrono::Duration::minutes(5), 1e5).unwrap();
+/// assert_eq!(bars.len(), 6);
+/// assert!(bars.iter().all(|bar| bar.low > 1.3 && bar.high < 1.34));
+/// # std::fs::remove_dir_all(&root).unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_day_bars<P: AsRef<Path>>(day_dir: P, interval: Duration, point_value: f64) -> Result<Vec<Bar>, Error> {
+    let mut bars = time_bars(Bi5::iter_day(day_dir)?, interval);
+    for bar in &mut bars {
+        for price in [&mut bar.open, &mut bar.high, &mut bar.low, &mut bar.close] {
+            *price /= point_value;
+        }
+    }
+    Ok(bars)
+}
+
+/// Input a tick of `interleave` comes from
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    A,
+    B,
+}
+
+/// Iterator returned by `interleave`
+#[cfg(feature = "fs")]
+pub struct Interleave {
+    a: std::iter::Peekable<Bi5Iter>,
+    b: std::iter::Peekable<Bi5Iter>,
+}
+
+#[cfg(feature = "fs")]
+impl Iterator for Interleave {
+    type Item = (NaiveDateTime, Source, Tick);
+    fn next(&mut self) -> Option<Self::Item> {
+        // on equal times `a` comes first, so the merge is stable
+        let source = match (self.a.peek(), self.b.peek()) {
+            (Some((a, _)), Some((b, _))) => if b < a { Source::B } else { Source::A },
+            (Some(_), None) => Source::A,
+            (None, Some(_)) => Source::B,
+            (None, None) => return None,
+        };
+        let iter = if source == Source::A { &mut self.a } else { &mut self.b };
+        iter.next().map(|(t, tick)| (t, source, tick))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, a_upper) = self.a.size_hint();
+        let (b, b_upper) = self.b.size_hint();
+        (a.saturating_add(b), a_upper.zip(b_upper).and_then(|(a, b)| a.checked_add(b)))
+    }
+}
+
+/// Merge the ticks of two instruments into one stream ordered by time,
+/// each tagged with its `Source`, e.g. for pairs trading
+///
+/// Both inputs must be in time order, like the files of a directory. Ticks
+/// with the same time are yielded from `a` first.
+/// ```
+/// use bi5::*;
+/// let a = Bi5::new("test/test.bi5", None);
+/// let b = Bi5::new("test/single.bi5", None);
+/// let merged: Vec<_> = interleave(a, b).unwrap().collect();
+/// assert_eq!(merged.len(), 10412 + 1);
+/// assert!(merged.windows(2).all(|w| w[0].0 <= w[1].0));
+/// assert_eq!(merged.iter().filter(|(_, source, _)| *source == Source::B).count(), 1);
+/// ```
+#[cfg(feature = "fs")]
+pub fn interleave(a: Bi5, b: Bi5) -> Result<Interleave, Error> {
+    Ok(Interleave { a: a.iter()?.peekable(), b: b.iter()?.peekable() })
+}
+
+/// Decompress and parse a bi5 file
+/// ## Arguments
+/// - `path` - Path to bi5 file
+/// - `date_time` - Optional date_time of file
+/// ## Returns
+/// `Vec` of `Tick`s or `Error`
+/// 
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = read_bi5_file("test/test.bi5", None).expect("Read failed");
+/// assert_eq!(
+///     ticks.first(),
+///     Some(&Tick { millisecs: 1860002, ask: 133153, bid: 133117, asksize: 0.015, bidsize: 0.02 })
+/// );
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_bi5_file<P:AsRef<Path>+Copy>(path: P, date_time: Option<NaiveDateTime>) 
+    -> Result<Vec<Tick>, Error>
+{
+    let bi5 = Bi5::new(path, date_time);
+    let ticks = bi5.iter()?.map(|x|x.1).collect();
+    Ok(ticks)
+
+}
+
+/// Decompress and parse a bi5 file, appending to `ticks`
+///
+/// Like `read_bi5_file` but reuses the caller's vector, e.g. a buffer that is
+/// cleared and refilled for each file in a long-running process. On error the
+/// ticks appended so far are left in `ticks`.
+/// ## Arguments
+/// - `path` - Path to bi5 file
+/// - `ticks` - Vector the ticks are appended to
+/// ## Returns
+/// Number of ticks appended or `Error`
+///
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let mut ticks = Vec::new();
+/// for _ in 0..2 {
+///     ticks.clear();
+///     assert_eq!(read_bi5_file_into("test/test.bi5", &mut ticks).unwrap(), 10412);
+/// }
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_bi5_file_into<P:AsRef<Path>>(path: P, ticks: &mut Vec<Tick>) -> Result<usize, Error> {
+    let len = ticks.len();
+    ticks.extend(Bi5::new(path, None).iter()?.map(|x|x.1));
+    Ok(ticks.len() - len)
+}
+
+/// Parse an uncompressed file of concatenated 20 byte records, e.g. written
+/// by `Bi5::decompress_to_file`, without LZMA
+/// ## Arguments
+/// - `path` - Path to the raw file
+/// ## Returns
+/// `Vec` of `Tick`s or `Error` if the length is not a multiple of 20
+///
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let raw = std::env::temp_dir().join(format!("bi5-doc-read-raw-{}", std::process::id()));
+/// Bi5::new("test/test.bi5", None).decompress_to_file(&raw).unwrap();
+/// assert_eq!(read_raw_bin(&raw).unwrap(), read_bi5_file("test/test.bi5", None).unwrap());
+/// # std::fs::remove_file(&raw).unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_raw_bin<P: AsRef<Path>>(path: P) -> Result<Vec<Tick>, Error> {
+    parse_ticks(&std::fs::read(path)?)
+}
+
+/// Ticks as parallel columns, see `read_bi5_columnar`
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct TicksColumnar {
+    pub millisecs: Vec<u32>,
+    pub bid: Vec<u32>,
+    pub ask: Vec<u32>,
+    pub bidsize: Vec<f32>,
+    pub asksize: Vec<f32>,
+}
+
+impl TicksColumnar {
+    /// Number of ticks
+    pub fn len(&self) -> usize {
+        self.millisecs.len()
+    }
+
+    /// True if there are no ticks
+    pub fn is_empty(&self) -> bool {
+        self.millisecs.is_empty()
+    }
+}
+
+/// Decompress and parse a bi5 file into columns, e.g. for vectorized math
+///
+/// The records are decoded straight into the columns without building
+/// `Tick`s in between.
+/// ## Arguments
+/// - `path` - Path to bi5 file
+/// ## Returns
+/// `TicksColumnar` or `Error`
+///
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let columns = read_bi5_columnar("test/test.bi5").unwrap();
+/// assert_eq!(columns.len(), 10412);
+/// assert_eq!((columns.millisecs[0], columns.bid[0], columns.asksize[0]), (1860002, 133117, 0.015));
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_bi5_columnar<P: AsRef<Path>>(path: P) -> Result<TicksColumnar, Error> {
+    let buf = Bi5::new(path, None).decode()?;
+    let n = buf.len() / size_of::<Tick>();
+    let mut columns = TicksColumnar {
+        millisecs: Vec::with_capacity(n),
+        bid: Vec::with_capacity(n),
+        ask: Vec::with_capacity(n),
+        bidsize: Vec::with_capacity(n),
+        asksize: Vec::with_capacity(n),
+    };
+    let word = |record: &[u8], i: usize| [record[4 * i], record[4 * i + 1], record[4 * i + 2], record[4 * i + 3]];
+    // record layout as in `Tick`: millisecs, ask, bid, asksize, bidsize
+    for record in buf.chunks_exact(size_of::<Tick>()) {
+        columns.millisecs.push(u32::from_be_bytes(word(record, 0)));
+        columns.ask.push(u32::from_be_bytes(word(record, 1)));
+        columns.bid.push(u32::from_be_bytes(word(record, 2)));
+        columns.asksize.push(f32::from_be_bytes(word(record, 3)));
+        columns.bidsize.push(f32::from_be_bytes(word(record, 4)));
+    }
+    Ok(columns)
+}
+
+/// Decompress and parse a bi5 file, keeping the absolute time of each tick
+/// ## Arguments
+/// - `path` - Path to bi5 file
+/// - `date_time` - Optional date_time of file
+/// ## Returns
+/// `Vec` of `(NaiveDateTime, Tick)` or `Error`
+///
+/// ## Usage
+/// ```
+/// use bi5::*;
+/// let ticks = read_bi5_file_dated("test/test.bi5", None).expect("Read failed");
+/// assert_eq!(ticks.first().unwrap().0.to_string(), "0000-01-01 00:31:00.002");
+/// ```
+#[cfg(feature = "fs")]
+pub fn read_bi5_file_dated<P:AsRef<Path>+Copy>(path: P, date_time: Option<NaiveDateTime>)
+    -> Result<Vec<(NaiveDateTime, Tick)>, Error>
+{
+    let bi5 = Bi5::new(path, date_time);
+    Ok(bi5.iter()?.collect())
+}
+
+/// The file start of an existing file, see `path_datetime`
+#[cfg(feature = "fs")]
+trait ToDateTime {
+    fn to_datetime(&self) -> Option<NaiveDateTime>;
+}
+
+#[cfg(feature = "fs")]
+impl ToDateTime for Path {
+    fn to_datetime(&self) -> Option<NaiveDateTime>
+    {
+        // sidecar index files share the hour prefix of their bi5 file
+        if!self.is_file() || self.extension() == Some(OsStr::new("idx")) { 
+            None 
+        } else {
+            path_datetime(self)
+        }
+    }
+}
+
+/// Derives the file start from a Dukascopy path `.../YYYY/MM/DD/HHh_ticks.bi5`
+/// without looking at the filesystem, so the file needn't exist
+///
+/// Dukascopy months are 0-indexed (`00` is January, `11` is December) while
+/// days are 1-indexed (`01` to `31`) and hours run from `00` to `23`.
+/// Re-exported archives with English month abbreviations (`Jan` to `Dec`,
+/// case-insensitive) in place of the month number are also recognized. The
+/// hour is the number before `h_ticks` in the file name, e.g. also in
+/// `EURUSD_9h_ticks.bi5`, or else the first two characters. Other paths give
+/// `None`.
+/// ```
+/// use bi5::path_datetime;
+/// use std::path::Path;
+/// let t = path_datetime(Path::new("not/saved/EURUSD/2022/11/16/14h_ticks.bi5"));
+/// assert_eq!(t.unwrap().to_string(), "2022-12-16 14:00:00");
+/// assert_eq!(path_datetime(Path::new("2022/Dec/16/EURUSD_9h_ticks.bi5")).unwrap().to_string(), "2022-12-16 09:00:00");
+/// assert_eq!(path_datetime(Path::new("2022/11/16/24h_ticks.bi5")), None);
+/// ```
+pub fn path_datetime(path: &std::path::Path) -> Option<NaiveDateTime> {
+    // only the last four components need to be UTF-8
+    let mut segments: Vec<&str> = path.iter().rev().take(4).map(std::ffi::OsStr::to_str).collect::<Option<_>>()?;
+    segments.reverse();
+    segments_datetime(&segments)
+}
+
+/// Derives the file start from a Dukascopy download URL such as
+/// `https://datafeed.dukascopy.com/datafeed/EURUSD/2020/00/15/13h_ticks.bi5`,
+/// with the same rules as for file paths (months are 0-indexed)
+///
+/// ```
+/// use bi5::datetime_from_dukascopy_url;
+/// let t = datetime_from_dukascopy_url("https://datafeed.dukascopy.com/datafeed/EURUSD/2020/00/15/13h_ticks.bi5");
+/// assert_eq!(t.unwrap().to_string(), "2020-01-15 13:00:00");
+/// ```
+pub fn datetime_from_dukascopy_url(url: &str) -> Option<NaiveDateTime> {
+    let path = url.split(['?', '#']).next()?;
+    let path = match path.split_once("://") {
+        // drop the host
+        Some((_, rest)) => rest.split_once('/')?.1,
+        None => path,
+    };
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    segments_datetime(&segments)
+}
+
+/// Derives the file start from the last four segments `YYYY/MM/DD/HHh...`
+fn segments_datetime(segments: &[&str]) -> Option<NaiveDateTime> {
+    let [y, m, d, f] = segments.get(segments.len().checked_sub(4)?..)? else { return None };
+    let h: u32 = parse_hour(f)?;
+    let d: u32 = d.parse::<u32>().ok()?;
+    let m: u32 = parse_month(m)?;
+    let y: u32 = y.parse::<u32>().ok()?;
+    Some(NaiveDateTime::new(
+        NaiveDate::from_ymd_opt(y as i32, m.checked_add(1)?, d)?, 
+        NaiveTime::from_hms_opt(h, 0, 0)?
+    ))
+}
+
+/// Relative Dukascopy path `SYMBOL/YYYY/MM/DD/HHh_ticks.bi5` of the hour
+/// containing `dt`, the inverse of parsing the file start from a path
+///
+/// As in Dukascopy paths the month is 0-indexed (`00` is January) while the
+/// day is 1-indexed.
+/// ```
+/// use bi5::dukascopy_path;
+/// let dt = chrono::NaiveDate::from_ymd_opt(2022, 12, 16).unwrap().and_hms_opt(14, 31, 0).unwrap();
+/// assert_eq!(dukascopy_path("EURUSD", dt), std::path::Path::new("EURUSD/2022/11/16/14h_ticks.bi5"));
+/// ```
+pub fn dukascopy_path(symbol: &str, dt: NaiveDateTime) -> std::path::PathBuf {
+    use chrono::{Datelike, Timelike};
+    std::path::PathBuf::from(format!(
+        "{}/{:04}/{:02}/{:02}/{:02}h_ticks.bi5",
+        symbol, dt.year(), dt.month0(), dt.day(), dt.hour()
+    ))
+}
+
+/// The instrument of a Dukascopy path `.../SYMBOL/YYYY/MM/DD/HHh_ticks.bi5`,
+/// the directory above the year, or `None` if the path doesn't have this layout
+///
+/// ```
+/// use bi5::symbol_from_path;
+/// let path = std::path::Path::new("data/EURUSD/2022/11/16/14h_ticks.bi5");
+/// assert_eq!(symbol_from_path(path).as_deref(), Some("EURUSD"));
+/// ```
+#[cfg(feature = "fs")]
+pub fn symbol_from_path(path: &Path) -> Option<String> {
+    path_datetime(path)?;
+    let symbol = path.iter().rev().nth(4)?.to_str()?;
+    Some(symbol.to_string())
+}
+
+/// Hour of a file name, the one or two digits before `h_ticks`, or else
+/// the first two characters
+fn parse_hour(name: &str) -> Option<u32> {
+    let digits = match name.find("h_ticks") {
+        Some(end) => {
+            let digits = name[..end].bytes().rev().take_while(u8::is_ascii_digit).count().min(2);
+            &name[end - digits..end]
+        }
+        None => name.get(0..2)?,
+    };
+    // `parse` also takes a sign
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// 0-indexed month from a number or a three-letter English abbreviation
+fn parse_month(s: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+    match s.parse::<u32>() {
+        Ok(m) => Some(m),
+        Err(_) => MONTHS.iter().position(|m| m.eq_ignore_ascii_case(s)).map(|m| m as u32),
+    }
+}
+
+/// Sort key of the entries of a directory: files by their start, then
+/// directories and other names by their number or month, e.g. `9` before
+/// `10`, and finally by name, so the order doesn't depend on the filesystem
+#[cfg(feature = "fs")]
+fn direntry_to_key(entry: &walkdir::DirEntry) -> (NaiveDateTime, Option<u32>, std::ffi::OsString) {
+    let name = entry.file_name();
+    let number = name.to_str().and_then(parse_month);
+    (entry.path().to_datetime().unwrap_or(zero_timestamp()), number, name.to_os_string())
+}
+
+#[cfg(feature = "fs")]
+#[test]
+#[allow(clippy::assertions_on_constants)]
+/// Test correct length, and correctness of first and last tick in test/test.bi5
+fn test_read_bi5() {
+    match read_bi5_file("test/test.bi5", None) {
+        Err(_) => assert!(false),
+        Ok(ticks) => {
+            assert_eq!(ticks.len(), 10412);
+            assert_eq!(
+                ticks.first(), 
+                Some(&Tick { millisecs: 1860002, bid: 133117, ask: 133153, bidsize: 0.02, asksize: 0.015 })
+            );
+            assert_eq!(
+                ticks.last(), 
+                Some(&Tick { millisecs: 3599899,  bid: 131427, ask: 131453,bidsize: 0.02, asksize: 0.015 })
+            );
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test correct length, and correctness of first and last tick in test/test.bi5
+fn test_read2_bi5() {
+    let bi5 = Bi5::new("test/test.bi5", Some(NaiveDateTime::from_timestamp_millis(12345678).unwrap()));
+    if let Some((t,tick)) = bi5.iter().unwrap().next() {
+        assert_eq!(t.timestamp_millis(), 12345678+1860002);
+        assert_eq!(
+            tick, 
+            Tick { millisecs: 1860002, bid: 133117, ask: 133153, bidsize: 0.02, asksize: 0.015 }
+        );
+    }
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that dated ticks carry the file datetime plus millisecs
+fn test_read_bi5_dated() {
+    let date_time = NaiveDate::from_ymd_opt(2022, 12, 16).unwrap().and_hms_opt(14, 0, 0).unwrap();
+    let ticks = read_bi5_file_dated("test/test.bi5", Some(date_time)).unwrap();
+    assert_eq!(ticks.len(), 10412);
+    let (t, tick) = ticks.first().unwrap();
+    assert_eq!(*t, date_time + Duration::milliseconds(1860002));
+    assert_eq!(tick.millisecs, 1860002);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that a tick beyond the hour of a 23:00 file rolls over into the next day
+fn test_out_of_hour_rolls_into_next_day() {
+    let mut buf: Vec<u8> = Vec::new();
+    for x in [3_700_000u32, 133153, 133117] { buf.extend_from_slice(&x.to_be_bytes()); }
+    for x in [0.015f32, 0.02] { buf.extend_from_slice(&x.to_be_bytes()); }
+    let date_time = NaiveDate::from_ymd_opt(2022, 12, 31).unwrap().and_hms_opt(23, 0, 0).unwrap();
+    let mut iter = FileIter::new(buf, date_time);
+    let (t, tick) = iter.next().unwrap();
+    assert_eq!(t, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap().and_hms_milli_opt(0, 1, 40, 0).unwrap());
+    assert!(tick.is_out_of_hour());
+    assert!(iter.next().is_none());
+    assert!(!Tick { millisecs: HOUR_MILLISECS - 1, ask: 0, bid: 0, asksize: 0.0, bidsize: 0.0 }.is_out_of_hour());
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that files() yields a single chunk for a file and an error for a missing path
+fn test_files() {
+    let files: Vec<_> = Bi5::new("test/test.bi5", None).files().collect();
+    assert_eq!(files.len(), 1);
+    let (date_time, ticks) = files[0].as_ref().unwrap();
+    assert_eq!(*date_time, zero_timestamp());
+    assert_eq!(ticks.len(), 10412);
+    let missing: Vec<_> = Bi5::new("test/missing", None).files().collect();
+    assert_eq!(missing.len(), 1);
+    assert!(missing[0].is_err());
+}
+
+/// Creates a fresh directory tree under the temp dir with a copy of
+/// test/test.bi5 at each of the relative `files` paths
+#[cfg(all(test, feature = "fs"))]
+fn test_dir(name: &str, files: &[&str]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("bi5-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    for file in files {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::copy("test/test.bi5", &path).unwrap();
+    }
+    root
+}
+
+/// Ticks at `millisecs` with the prices and sizes of the first tick of test.bi5
+#[cfg(all(test, feature = "fs"))]
+fn ticks_at(millisecs: &[u32]) -> Vec<Tick> {
+    millisecs.iter().map(|&millisecs| Tick { millisecs, ask: 133153, bid: 133117, asksize: 0.015, bidsize: 0.02 }).collect()
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that files() yields one chunk per hour-file in chronological order
+fn test_files_dir() {
+    let root = test_dir("files", &["EURUSD/2022/11/16/15h_ticks.bi5", "EURUSD/2022/11/16/14h_ticks.bi5", "README.txt"]);
+    let files: Vec<_> = Bi5::new(&root, None).files().map(|f| f.unwrap()).collect();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].0.to_string(), "2022-12-16 14:00:00");
+    assert_eq!(files[1].0.to_string(), "2022-12-16 15:00:00");
+    assert_eq!(files[1].1.len(), 10412);
+}
+
+#[test]
+/// Test that approx_eq tolerates size differences but not price differences
+fn test_approx_eq() {
+    let a = Tick { millisecs: 1860002, ask: 133153, bid: 133117, asksize: 0.015, bidsize: 0.02 };
+    assert!(a.approx_eq(&Tick { bidsize: 0.020001, ..a }, 1e-5));
+    assert!(!a.approx_eq(&Tick { bidsize: 0.021, ..a }, 1e-5));
+    assert!(!a.approx_eq(&Tick { bid: 133118, ..a }, 1.0));
+    assert!(!a.approx_eq(&Tick { millisecs: 1860003, ..a }, 1.0));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that trailing partial ticks only fail without ignore_trailing_partial,
+/// and are reported as a notice with it
+fn test_ignore_trailing_partial() {
+    let mut raw = decode::decompress(File::open("test/test.bi5").unwrap()).unwrap();
+    raw.extend_from_slice(&[1, 2, 3]);
+    let mut compressed = Vec::new();
+    lzma_rs::lzma_compress(&mut Cursor::new(raw), &mut compressed).unwrap();
+    let root = test_dir("trailing", &[]);
+    std::fs::create_dir_all(&root).unwrap();
+    let path = root.join("00h_ticks.bi5");
+    std::fs::write(&path, compressed).unwrap();
+    assert!(Bi5::new(&path, None).iter().is_err());
+    let notices = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = notices.clone();
+    let bi5 = Bi5::builder(&path).ignore_trailing_partial(true)
+        .on_notice(move |notice| sink.lock().unwrap().push(notice.to_string()))
+        .build();
+    assert_eq!(bi5.iter().unwrap().count(), 10412);
+    assert_eq!(*notices.lock().unwrap(), vec![format!("{}: dropped 3 trailing bytes", path.to_string_lossy())]);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that len() of a file iterator counts down to zero
+fn test_exact_size() {
+    let mut iter = Bi5::new("test/test.bi5", None).iter_file().unwrap();
+    assert_eq!(iter.len(), 10412);
+    iter.next();
+    assert_eq!(iter.len(), 10411);
+    assert_eq!(iter.by_ref().count(), 10411);
+    assert_eq!(iter.len(), 0);
+    assert_eq!(Bi5::new("test/test.bi5", None).iter().unwrap().size_hint(), (10412, Some(10412)));
+    assert!(Bi5::new("test", None).iter_file().is_err());
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the capacity hint doesn't change the decoded ticks
+fn test_decode_capacity_hint() {
+    let bi5 = Bi5::builder("test/test.bi5").decode_capacity_hint(10412 * 20).build();
+    assert_eq!(bi5.decode().unwrap().capacity(), 10412 * 20);
+    assert!(bi5.iter().unwrap().eq(Bi5::new("test/test.bi5", None).iter().unwrap()));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that tuples carry the absolute time in Unix millis
+fn test_iter_tuples() {
+    let date_time = NaiveDateTime::from_timestamp_millis(1_671_199_200_000).unwrap();
+    let bi5 = Bi5::new("test/test.bi5", Some(date_time));
+    let tuples: Vec<_> = bi5.iter_tuples(1e5).unwrap().collect();
+    assert_eq!(tuples.len(), 10412);
+    assert_eq!(tuples[0], (1_671_199_200_000 + 1860002, 1.33117, 1.33153));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test the 0-indexed month and 1-indexed day of Dukascopy paths
+fn test_to_datetime_month_day_matrix() {
+    let cases = [
+        ("2020/00/01/00h_ticks.bi5", Some((2020, 1, 1, 0))),
+        ("2020/00/31/13h_ticks.bi5", Some((2020, 1, 31, 13))),
+        ("2020/11/01/00h_ticks.bi5", Some((2020, 12, 1, 0))),
+        ("2020/11/31/23h_ticks.bi5", Some((2020, 12, 31, 23))),
+        ("2020/01/29/00h_ticks.bi5", Some((2020, 2, 29, 0))),
+        ("2021/01/29/00h_ticks.bi5", None),
+        ("2020/12/01/00h_ticks.bi5", None),
+        ("2020/00/00/00h_ticks.bi5", None),
+        ("2020/00/32/00h_ticks.bi5", None),
+        ("2020/00/01/24h_ticks.bi5", None),
+        ("2020/4294967295/01/00h_ticks.bi5", None),
+        ("2020/Jan/15/13h_ticks.bi5", Some((2020, 1, 15, 13))),
+        ("2020/dec/31/23h_ticks.bi5", Some((2020, 12, 31, 23))),
+        ("2020/Feb/30/00h_ticks.bi5", None),
+        ("2020/January/01/00h_ticks.bi5", None),
+    ];
+    let root = test_dir("matrix", &cases.iter().map(|c| c.0).collect::<Vec<_>>());
+    for (path, expected) in cases {
+        let expected = expected.map(|(y, m, d, h)| {
+            NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(h, 0, 0).unwrap()
+        });
+        assert_eq!(root.join(path).to_datetime(), expected, "{}", path);
+    }
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that hour 13 and hour 14 of a day are ordered and rebased on their own hour
+fn test_iter_day() {
+    let root = test_dir("day", &[
+        "EURUSD/2022/11/16/14h_ticks.bi5",
+        "EURUSD/2022/11/16/13h_ticks.bi5",
+        "EURUSD/2022/11/16/extra/2022/11/16/15h_ticks.bi5",
+    ]);
+    let ticks: Vec<_> = Bi5::iter_day(root.join("EURUSD/2022/11/16")).unwrap().collect();
+    assert_eq!(ticks.len(), 2 * 10412);
+    assert!(ticks.windows(2).all(|w| w[0].0 <= w[1].0));
+    let day = NaiveDate::from_ymd_opt(2022, 12, 16).unwrap();
+    assert_eq!(ticks[0].0, day.and_hms_milli_opt(13, 31, 0, 2).unwrap());
+    assert_eq!(ticks[10411].0, day.and_hms_milli_opt(13, 59, 59, 899).unwrap());
+    assert_eq!(ticks[10412].0, day.and_hms_milli_opt(14, 31, 0, 2).unwrap());
+    assert_eq!(ticks[10412].0 - ticks[0].0, Duration::hours(1));
+    assert!(Bi5::iter_day("test/test.bi5").is_err());
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that try_iter reports a corrupt file and continues with the next one
+fn test_try_iter() {
+    let root = test_dir("try", &["2022/11/16/13h_ticks.bi5", "2022/11/16/15h_ticks.bi5"]);
+    std::fs::write(root.join("2022/11/16/14h_ticks.bi5"), [0x5d, 0, 0, 0x40, 0]).unwrap();
+    let items: Vec<_> = Bi5::new(&root, None).try_iter().collect();
+    assert_eq!(items.len(), 2 * 10412 + 1);
+    assert!(matches!(items[10412], Err(Bi5Error::Decompress(_))));
+    assert_eq!(items[10413].as_ref().unwrap().0.to_string(), "2022-12-16 15:31:00.002");
+    assert_eq!(Bi5::new(&root, None).iter().unwrap().count(), 10412);
+    let missing: Vec<_> = Bi5::new(root.join("missing"), None).try_iter().collect();
+    assert!(matches!(missing[..], [Err(Bi5Error::InvalidPath(_))]));
+}
+
+#[test]
+/// Test that either zero size makes a tick one-sided
+fn test_is_one_sided() {
+    let tick = Tick { millisecs: 0, ask: 133153, bid: 133117, asksize: 1.0, bidsize: 3.0 };
+    assert!(!tick.is_one_sided());
+    assert!(Tick { bidsize: 0.0, ..tick }.is_one_sided());
+    assert!(Tick { asksize: -0.0, ..tick }.is_one_sided());
+    assert!(!Tick { asksize: f32::MIN_POSITIVE, ..tick }.is_one_sided());
+}
+
+#[test]
+/// Test imbalance including the zero size guard
+fn test_imbalance() {
+    let tick = Tick { millisecs: 0, ask: 133153, bid: 133117, asksize: 1.0, bidsize: 3.0 };
+    assert_eq!(tick.imbalance(), 0.5);
+    assert_eq!(Tick { bidsize: 0.0, ..tick }.imbalance(), -1.0);
+    assert_eq!(Tick { asksize: 0.0, bidsize: 0.0, ..tick }.imbalance(), 0.0);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Ticks are appended after existing content
+fn test_read_bi5_file_into() {
+    let first = Tick { millisecs: 0, ask: 1, bid: 1, asksize: 0.0, bidsize: 0.0 };
+    let mut ticks = vec![first];
+    assert_eq!(read_bi5_file_into("test/test.bi5", &mut ticks).unwrap(), 10412);
+    assert_eq!(ticks.len(), 10413);
+    assert_eq!(ticks[0], first);
+    assert_eq!(ticks[1..], read_bi5_file("test/test.bi5", None).unwrap()[..]);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that walk errors are reported as `Bi5Error::Walk` unless skipped, then
+/// as a notice
+fn test_skip_walk_errors() {
+    let missing = std::env::temp_dir().join(format!("bi5-missing-{}", std::process::id()));
+    let bi5 = Bi5::new(&missing, None);
+    let mut walk_dir = WalkDir::new(&missing).into_iter();
+    assert!(matches!(bi5.forward_to_next_good_file(&mut walk_dir), Err(Bi5Error::Walk(_))));
+    let notices = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = notices.clone();
+    let bi5 = Bi5::builder(&missing).skip_walk_errors(true)
+        .on_notice(move |notice| sink.lock().unwrap().push(notice))
+        .build();
+    let mut walk_dir = WalkDir::new(&missing).into_iter();
+    assert!(matches!(bi5.forward_to_next_good_file(&mut walk_dir), Ok(None)));
+    assert!(matches!(notices.lock().unwrap()[..], [Notice::WalkError(_)]));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that skip_errors continues after a corrupt file and collects it
+fn test_skip_errors() {
+    let root = test_dir("skip-errors", &["EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/16/16h_ticks.bi5"]);
+    let corrupt = root.join("EURUSD/2022/11/16/15h_ticks.bi5");
+    std::fs::write(&corrupt, &std::fs::read("test/test.bi5").unwrap()[..1000]).unwrap();
+    assert_eq!(Bi5::new(&root, None).iter().unwrap().count(), 10412);
+    let mut iter = Bi5::builder(&root).skip_errors(true).build().iter().unwrap();
+    assert_eq!(iter.by_ref().count(), 2 * 10412);
+    let failed = iter.failed_files();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].0, corrupt);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test the tick rate inside a window and for windows with too few ticks
+fn test_tick_rate() {
+    let bi5 = Bi5::new("test/test.bi5", None);
+    let start = zero_timestamp() + Duration::minutes(31);
+    let rate = bi5.tick_rate(Some(start..start + Duration::minutes(1))).unwrap();
+    let ticks: Vec<_> = bi5.iter().unwrap().clamp_to_range(start, start + Duration::minutes(1)).collect();
+    let span = (ticks.last().unwrap().0 - ticks[0].0).num_milliseconds() as f64 / 1000.0;
+    assert_eq!(rate, ticks.len() as f64 / span);
+    assert_eq!(bi5.tick_rate(Some(start..start)).unwrap(), 0.0);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that ticks beyond `max_millisecs` are dropped, reported and the rest kept in order
+fn test_max_millisecs() {
+    let ticks = ticks_at(&[1, 3_700_000, 0xdead_beef, 2]);
+    let path = testing::write_test_file(&test_dir("max-millisecs", &[]), "00h_ticks.bi5", &ticks);
+    let notices = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = notices.clone();
+    let bi5 = Bi5::builder(&path).max_millisecs(Some(MAX_PLAUSIBLE_MILLISECS))
+        .on_notice(move |notice| sink.lock().unwrap().push(notice))
+        .build();
+    let millisecs: Vec<u32> = bi5.iter().unwrap().map(|(_, t)| t.millisecs).collect();
+    assert_eq!(millisecs, vec![1, 3_700_000, 2]);
+    assert!(matches!(notices.lock().unwrap()[..], [Notice::ImplausibleTicks { ticks: 1, .. }]));
+    assert_eq!(Bi5::new(&path, None).iter().unwrap().count(), 4);
+    let bi5 = Bi5::builder(&path).check_max_millisecs(Some(MAX_PLAUSIBLE_MILLISECS)).build();
+    let err = bi5.try_iter().next().unwrap().unwrap_err();
+    assert!(matches!(err, Bi5Error::MillisecsOutOfRange { index: 2, millisecs: 0xdead_beef, .. }), "{}", err);
+    let bi5 = Bi5::builder(&path).check_max_millisecs(Some(u32::MAX)).build();
+    assert_eq!(bi5.iter().unwrap().count(), 4);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the largest `millisecs` gives a valid time about 49.7 days on
+fn test_max_u32_millisecs() {
+    let tick = Tick { millisecs: u32::MAX, ..Tick::default() };
+    let path = testing::write_test_file(&test_dir("max-u32", &[]), "23h_ticks.bi5", &[tick]);
+    let start = NaiveDate::from_ymd_opt(2022, 12, 31).unwrap().and_hms_opt(23, 0, 0).unwrap();
+    let (t, tick) = Bi5::new(&path, Some(start)).iter().unwrap().next().unwrap();
+    assert_eq!(tick.millisecs, u32::MAX);
+    assert_eq!(t.to_string(), "2023-02-19 16:02:47.295");
+    assert_eq!(t - start, Duration::milliseconds(4_294_967_295));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that a scan lists the files in order and can be iterated repeatedly
+fn test_scan() {
+    let root = test_dir("scan", &["EURUSD/2022/11/16/15h_ticks.bi5", "EURUSD/2022/10/16/14h_ticks.bi5", "README.txt"]);
+    let scan = Bi5::new(&root, None).scan().unwrap();
+    let hours: Vec<String> = scan.files().iter().map(|(_, t)| t.to_string()).collect();
+    assert_eq!(hours, vec!["2022-11-16 14:00:00", "2022-12-16 15:00:00"]);
+    assert_eq!(scan.iter().count(), 2 * 10412);
+    assert_eq!(scan.iter().count(), 2 * 10412);
+    std::fs::remove_dir_all(&root).unwrap();
+    assert!(Bi5::new(&root, None).scan().is_err());
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that an empty file in a directory yields one marker in order
+fn test_iter_with_markers() {
+    let root = test_dir("markers", &["EURUSD/2022/11/16/13h_ticks.bi5", "EURUSD/2022/11/16/15h_ticks.bi5"]);
+    std::fs::write(root.join("EURUSD/2022/11/16/14h_ticks.bi5"), b"").unwrap();
+    let items: Vec<Bi5Item> = Bi5::new(&root, None).iter_with_markers().unwrap().collect();
+    assert_eq!(items.len(), 2 * 10412 + 1);
+    let hour = NaiveDate::from_ymd_opt(2022, 12, 16).unwrap().and_hms_opt(14, 0, 0).unwrap();
+    assert_eq!(items[10412], Bi5Item::EmptyHour(hour));
+    let items: Vec<Bi5Item> = Bi5::new(root.join("EURUSD/2022/11/16/14h_ticks.bi5"), None).iter_with_markers().unwrap().collect();
+    assert_eq!(items, vec![Bi5Item::EmptyHour(zero_timestamp())]);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the symbol needs the full layout below it
+fn test_symbol_from_path() {
+    for (path, expected) in [
+        ("/data/GBPUSD/2022/11/16/14h_ticks.bi5", Some("GBPUSD")),
+        ("2022/11/16/14h_ticks.bi5", None),
+        ("GBPUSD/2022/11/16/README.txt", None),
+    ] {
+        assert_eq!(symbol_from_path(Path::new(path)).as_deref(), expected, "{}", path);
+    }
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that shards cover the file without gaps or overlap
+fn test_skip_take_ticks() {
+    let all: Vec<_> = Bi5::new("test/test.bi5", None).iter_file().unwrap().collect();
+    let mut shards = Vec::new();
+    for offset in (0..all.len()).step_by(4000) {
+        let iter = Bi5::new("test/test.bi5", None).iter_file().unwrap();
+        shards.extend(iter.skip_ticks(offset).take_ticks(4000));
+    }
+    assert_eq!(shards, all);
+    let iter = Bi5::new("test/test.bi5", None).iter_file().unwrap();
+    assert_eq!(iter.skip_ticks(20000).take_ticks(5).len(), 0);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test the bounds of a tree spanning a year boundary
+fn test_date_bounds() {
+    let root = test_dir("bounds", &[
+        "EURUSD/2022/11/31/23h_ticks.bi5",
+        "EURUSD/2023/00/01/00h_ticks.bi5",
+        "EURUSD/2022/11/30/05h_ticks.bi5",
+        "README.txt",
+    ]);
+    let (min, max) = Bi5::new(&root, None).date_bounds().unwrap();
+    assert_eq!((min.to_string(), max.to_string()), ("2022-12-30 05:00:00".into(), "2023-01-01 00:00:00".into()));
+    let file = root.join("EURUSD/2022/11/30/05h_ticks.bi5");
+    assert_eq!(Bi5::new(&file, None).date_bounds(), Some((min, min)));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test missing and empty hours, with and without the weekend close
+fn test_missing_hours() {
+    // Friday 2022-12-16 21h to Sunday 2022-12-18 22h
+    let root = test_dir("coverage", &["EURUSD/2022/11/16/21h_ticks.bi5", "EURUSD/2022/11/18/22h_ticks.bi5"]);
+    std::fs::create_dir_all(root.join("EURUSD/2022/11/17")).unwrap();
+    std::fs::write(root.join("EURUSD/2022/11/17/03h_ticks.bi5"), b"").unwrap();
+    let bi5 = Bi5::new(&root, None);
+    assert_eq!(bi5.missing_hours(&CoverageOptions::default()).len(), 48);
+    let options = CoverageOptions { skip_weekends: true, ..Default::default() };
+    assert!(bi5.missing_hours(&options).is_empty());
+    std::fs::remove_file(root.join("EURUSD/2022/11/16/21h_ticks.bi5")).unwrap();
+    std::fs::copy("test/test.bi5", root.join("EURUSD/2022/11/16/20h_ticks.bi5")).unwrap();
+    let friday = NaiveDate::from_ymd_opt(2022, 12, 16).unwrap().and_hms_opt(21, 0, 0).unwrap();
+    assert_eq!(bi5.missing_hours(&options), vec![friday]);
+}
+
+#[cfg(all(feature = "fs", unix))]
+#[test]
+/// Test that a symlinked month directory is only read when following links
+fn test_follow_links() {
+    let root = test_dir("links", &["store/16/14h_ticks.bi5"]);
+    std::fs::create_dir_all(root.join("EURUSD/2022")).unwrap();
+    std::os::unix::fs::symlink(root.join("store"), root.join("EURUSD/2022/11")).unwrap();
+    assert_eq!(Bi5::new(root.join("EURUSD"), None).iter().unwrap().count(), 0);
+    let bi5 = Bi5::builder(root.join("EURUSD")).follow_links(true).build();
+    let (t, _) = bi5.iter().unwrap().next().unwrap();
+    assert_eq!(t.to_string(), "2022-12-16 14:31:00.002");
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that counts come from the sidecars, which are ignored once stale or
+/// written with other options, and never read as bi5 files
+fn test_write_index() {
+    let root = test_dir("index", &["EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/16/15h_ticks.bi5"]);
+    let bi5 = Bi5::new(&root, None);
+    assert_eq!(bi5.write_index().unwrap(), 2);
+    let file = root.join("EURUSD/2022/11/16/14h_ticks.bi5");
+    assert!(root.join("EURUSD/2022/11/16/14h_ticks.bi5.idx").is_file());
+    assert_eq!(bi5.iter().unwrap().count(), 2 * 10412);
+    let size = std::fs::metadata(&file).unwrap().len();
+    std::fs::write(index::index_path(&file), format!("7 {} partial=0 max=- trim=0,0", size)).unwrap();
+    assert_eq!(bi5.count().unwrap(), 10412 + 7);
+    std::fs::write(index::index_path(&file), "7 1 partial=0 max=- trim=0,0").unwrap();
+    assert_eq!(bi5.count().unwrap(), 2 * 10412);
+    std::fs::write(index::index_path(&file), format!("7 {}", size)).unwrap();
+    assert_eq!(bi5.count().unwrap(), 2 * 10412);
+    // counts written with trimmed edges don't apply to an untrimmed count
+    let trimmed = Bi5::builder(&root).trim_edges(2, 3).build();
+    assert_eq!(trimmed.write_index().unwrap(), 2);
+    assert_eq!(trimmed.count().unwrap(), 2 * (10412 - 5));
+    assert_eq!(bi5.count().unwrap(), 2 * 10412);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that non-bi5 files are skipped, and hour-named exports only with `extension`
+fn test_extension() {
+    let root = test_dir("extension", &["EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/16/README.txt"]);
+    assert_eq!(Bi5::new(&root, None).iter().unwrap().count(), 10412);
+    std::fs::write(root.join("EURUSD/2022/11/16/15h_ticks.csv"), "t,bid,ask").unwrap();
+    assert!(Bi5::new(&root, None).try_iter().any(|item| item.is_err()));
+    let bi5 = Bi5::builder(&root).extension("bi5").build();
+    assert!(bi5.try_iter().all(|item| item.is_ok()));
+    assert_eq!(bi5.date_bounds().map(|(_, max)| max.to_string()), Some("2022-12-16 14:00:00".into()));
+}
+
+#[test]
+/// Test crossed and locked quotes
+fn test_anomaly() {
+    let tick = Tick { millisecs: 0, ask: 133153, bid: 133117, asksize: 0.0, bidsize: 0.0 };
+    assert_eq!(tick.anomaly(), None);
+    assert_eq!(Tick { bid: 133153, ..tick }.anomaly(), Some(Anomaly::Locked));
+    assert_eq!(Tick { bid: 133154, ..tick }.anomaly(), Some(Anomaly::Crossed));
+    assert!(!Tick { bid: 133154, ..tick }.is_locked());
+}
+
+#[test]
+/// Test URLs with query strings, relative paths and too few segments
+fn test_datetime_from_dukascopy_url() {
+    let t = NaiveDate::from_ymd_opt(2020, 1, 15).unwrap().and_hms_opt(13, 0, 0).unwrap();
+    for url in [
+        "https://datafeed.dukascopy.com/datafeed/EURUSD/2020/00/15/13h_ticks.bi5?x=1",
+        "/EURUSD/2020/00/15/13h_ticks.bi5",
+        "2020/00/15/13h_ticks.bi5#top",
+    ] {
+        assert_eq!(datetime_from_dukascopy_url(url), Some(t), "{}", url);
+    }
+    assert_eq!(datetime_from_dukascopy_url("https://2020/00/15/13h_ticks.bi5"), None);
+    assert_eq!(datetime_from_dukascopy_url("00/15/13h_ticks.bi5"), None);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the shift applies to the ticks and file chunks of a directory
+fn test_time_shift() {
+    let root = test_dir("shift", &["EURUSD/2022/11/16/14h_ticks.bi5"]);
+    let bi5 = Bi5::builder(&root).time_shift(Duration::hours(-1)).build();
+    assert_eq!(bi5.iter().unwrap().next().unwrap().0.to_string(), "2022-12-16 13:31:00.002");
+    let (date_time, ticks) = bi5.files().next().unwrap().unwrap();
+    assert_eq!((date_time.to_string(), ticks.len()), ("2022-12-16 13:00:00".into(), 10412));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that files outside an overnight session are pruned and ticks filtered
+fn test_iter_within_hours() {
+    let root = test_dir("session", &[
+        "EURUSD/2022/11/16/20h_ticks.bi5",
+        "EURUSD/2022/11/16/22h_ticks.bi5",
+        "EURUSD/2022/11/17/01h_ticks.bi5",
+    ]);
+    // corrupt the pruned file, reading it would end the stream
+    std::fs::write(root.join("EURUSD/2022/11/16/20h_ticks.bi5"), b"garbage").unwrap();
+    let t = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+    let ticks: Vec<_> = Bi5::new(&root, None).iter_within_hours(t(22, 45), t(1, 40)).unwrap().collect();
+    let per_file = Bi5::new("test/test.bi5", None).iter().unwrap();
+    let (late, early) = per_file.fold((0, 0), |(late, early), (t, _)| {
+        (late + (t.minute() >= 45) as usize, early + (t.minute() < 40) as usize)
+    });
+    assert_eq!(ticks.len(), late + early);
+    assert!(ticks.iter().all(|(time, _)| time.time() >= t(22, 45) || time.time() < t(1, 40)));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that errors come through the channel and a dropped receiver stops the reader
+fn test_spawn_reader() {
+    let root = test_dir("spawn", &["2022/11/16/14h_ticks.bi5", "2022/11/16/15h_ticks.bi5"]);
+    let ticks: Vec<_> = Bi5::new(&root, None).spawn_reader().iter().map(Result::unwrap).collect();
+    assert_eq!(ticks, Bi5::new(&root, None).iter().unwrap().collect::<Vec<_>>());
+    let mut rx = Bi5::new(root.join("nothing"), None).spawn_reader().into_iter();
+    assert!(matches!(rx.next(), Some(Err(Bi5Error::InvalidPath(_)))));
+    assert!(rx.next().is_none());
+    drop(Bi5::new(&root, None).spawn_reader());
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the path built by open_hour parses back to the start of the hour
+fn test_open_hour() {
+    let dt = NaiveDate::from_ymd_opt(2022, 1, 31).unwrap().and_hms_opt(9, 59, 0).unwrap();
+    assert_eq!(dukascopy_path("EURUSD", dt), PathBuf::from("EURUSD/2022/00/31/09h_ticks.bi5"));
+    let root = test_dir("open-hour", &["EURUSD/2022/00/31/09h_ticks.bi5"]);
+    assert_eq!(root.join(dukascopy_path("EURUSD", dt)).to_datetime().unwrap().to_string(), "2022-01-31 09:00:00");
+    let ticks: Vec<_> = Bi5::open_hour(&root, "EURUSD", dt).unwrap().collect();
+    assert_eq!(ticks.len(), 10412);
+    assert_eq!(ticks[0].0.to_string(), "2022-01-31 09:31:00.002");
+    assert!(Bi5::open_hour(&root, "GBPUSD", dt).is_err());
+}
+
+#[test]
+/// Test that dukascopy_path parses back for every month, day and hour boundary
+fn test_dukascopy_path_round_trip() {
+    for (m, d, h) in [(1, 1, 0), (1, 31, 23), (2, 29, 12), (12, 1, 0), (12, 31, 23)] {
+        let dt = NaiveDate::from_ymd_opt(2024, m, d).unwrap().and_hms_opt(h, 0, 0).unwrap();
+        let path = dukascopy_path("XAUUSD", dt);
+        let segments: Vec<&str> = path.iter().map(|s| s.to_str().unwrap()).collect();
+        assert_eq!(segments[2], format!("{:02}", m - 1));
+        assert_eq!(segments_datetime(&segments), Some(dt));
+    }
+}
+
+#[test]
+/// Test FixedPoint formatting with leading zeros and without decimals
+fn test_fixed_point() {
+    let tick = Tick { millisecs: 0, ask: 133153, bid: 7, asksize: 0.0, bidsize: 0.0 };
+    assert_eq!(tick.ask_decimal(3).to_string(), "133.153");
+    assert_eq!(tick.ask_decimal(0).to_string(), "133153");
+    assert_eq!(tick.bid_decimal(5).to_string(), "0.00007");
+    assert_eq!(tick.ask_decimal(5).to_f64(), tick.ask_price().as_f64(1e5));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that a directory iterator stopped by a corrupt file stays exhausted
+fn test_fused_after_corrupt_file() {
+    let root = test_dir("fused", &["2022/11/16/14h_ticks.bi5", "2022/11/16/16h_ticks.bi5"]);
+    std::fs::write(root.join("2022/11/16/15h_ticks.bi5"), b"corrupt").unwrap();
+    let mut iter = Bi5::new(&root, None).iter().unwrap();
+    assert_eq!(iter.by_ref().count(), 10412);
+    for _ in 0..3 {
+        assert!(iter.next().is_none());
+    }
+    // try_iter still reports the error and goes on with the next file
+    assert_eq!(Bi5::new(&root, None).try_iter().filter(Result::is_ok).count(), 2 * 10412);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that a file with exactly one tick yields it once, alone and in a directory
+fn test_single_tick() {
+    let mut iter = Bi5::new("test/single.bi5", None).iter().unwrap();
+    assert_eq!(iter.size_hint(), (1, Some(1)));
+    let (t, tick) = iter.next().unwrap();
+    assert_eq!((t.to_string().as_str(), tick.bid), ("0000-01-01 00:31:00.002", 133117));
+    assert_eq!(iter.size_hint(), (0, Some(0)));
+    assert!(iter.next().is_none() && iter.next().is_none());
+    assert_eq!(Bi5::new("test/single.bi5", None).iter_file().unwrap().len(), 1);
+    assert_eq!(read_bi5_file("test/single.bi5", None).unwrap(), vec![tick]);
+
+    let root = test_dir("single", &[]);
+    std::fs::create_dir_all(root.join("2022/11/16")).unwrap();
+    std::fs::copy("test/single.bi5", root.join("2022/11/16/14h_ticks.bi5")).unwrap();
+    std::fs::copy("test/single.bi5", root.join("2022/11/16/15h_ticks.bi5")).unwrap();
+    let times: Vec<String> = Bi5::new(&root, None).iter().unwrap().map(|(t, _)| t.to_string()).collect();
+    assert_eq!(times, vec!["2022-12-16 14:31:00.002", "2022-12-16 15:31:00.002"]);
+}
+
+#[test]
+/// Test that TickBuilder rejects crossed quotes and bad sizes, locked quotes pass
+fn test_tick_builder() {
+    assert!(TickBuilder::new().bid(5).ask(5).build().unwrap().is_locked());
+    for size in [-0.5, f32::NAN, f32::INFINITY] {
+        assert!(matches!(TickBuilder::new().asksize(size).build(), Err(Bi5Error::InvalidTick(_))));
+        assert!(TickBuilder::new().bidsize(size).allow_crossed(true).build().is_err());
+    }
+    let err = TickBuilder::new().bid(2).ask(1).build().unwrap_err();
+    assert_eq!(err.to_string(), "Invalid tick: bid 2 > ask 1");
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test the truncation flag below, at and beyond the total
+fn test_collect_capped() {
+    let root = test_dir("capped", &["2022/11/16/14h_ticks.bi5", "2022/11/16/15h_ticks.bi5"]);
+    let capped = |max| {
+        let (ticks, truncated) = Bi5::new(&root, None).collect_capped(max).unwrap();
+        (ticks.len(), truncated)
+    };
+    assert_eq!(capped(0), (0, true));
+    assert_eq!(capped(10412), (10412, true));
+    assert_eq!(capped(2 * 10412), (2 * 10412, false));
+    assert_eq!(capped(usize::MAX), (2 * 10412, false));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that follow yields a file completed after the first scan
+fn test_follow() {
+    let root = test_dir("follow", &["2022/11/16/14h_ticks.bi5"]);
+    let late = root.join("2022/11/16/15h_ticks.bi5");
+    let bi5 = std::fs::read("test/test.bi5").unwrap();
+    std::fs::write(&late, &bi5[..1000]).unwrap();
+    let mut follow = Bi5::new(&root, None).follow(std::time::Duration::from_millis(20)).unwrap();
+    assert_eq!(follow.by_ref().take(10412).count(), 10412);
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        std::fs::write(&late, bi5).unwrap();
+    });
+    let (t, _) = follow.next().unwrap();
+    assert_eq!(t.to_string(), "2022-12-16 15:31:00.002");
+    writer.join().unwrap();
+    assert!(Bi5::new("test/test.bi5", None).follow(std::time::Duration::ZERO).is_err());
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that a file created empty and written later is read once written,
+/// and a grown file only yields its new ticks
+fn test_follow_empty_file() {
+    let root = test_dir("follow-empty", &["2022/11/16/14h_ticks.bi5"]);
+    let late = root.join("2022/11/16/15h_ticks.bi5");
+    std::fs::write(&late, b"").unwrap();
+    let mut follow = Bi5::new(&root, None).follow(std::time::Duration::from_millis(20)).unwrap();
+    assert_eq!(follow.by_ref().take(10412).count(), 10412);
+    let ticks = read_bi5_file("test/test.bi5", None).unwrap();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        std::fs::write(&late, encode_bi5(&ticks[..10])).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        std::fs::write(&late, encode_bi5(&ticks[..20])).unwrap();
+    });
+    let hour: Vec<(NaiveDateTime, Tick)> = follow.take(20).collect();
+    writer.join().unwrap();
+    assert_eq!(hour[0].0.to_string(), "2022-12-16 15:31:00.002");
+    assert_eq!(hour.iter().map(|(_, tick)| *tick).collect::<Vec<_>>(), read_bi5_file("test/test.bi5", None).unwrap()[..20]);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that reversing a file yields the ticks backwards and meets `next` in the middle
+fn test_file_iter_rev() {
+    let forward: Vec<(NaiveDateTime, Tick)> = Bi5::new("test/test.bi5", None).iter_file().unwrap().collect();
+    let mut backward: Vec<(NaiveDateTime, Tick)> = Bi5::new("test/test.bi5", None).iter_file().unwrap().rev().collect();
+    backward.reverse();
+    assert_eq!(backward, forward);
+    let mut iter = Bi5::new("test/test.bi5", None).iter_file().unwrap().skip_ticks(10410);
+    assert_eq!(iter.next_back(), Some(forward[10411]));
+    assert_eq!(iter.len(), 1);
+    assert_eq!(iter.next(), Some(forward[10410]));
+    assert_eq!((iter.next_back(), iter.next()), (None, None));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that `last`, `nth` and `count` of a file seek, and agree with walking a directory
+fn test_file_iter_seek() {
+    let forward: Vec<(NaiveDateTime, Tick)> = Bi5::new("test/test.bi5", None).iter().unwrap().collect();
+    let bi5 = Bi5::new("test/test.bi5", None);
+    let (_, last) = bi5.iter().unwrap().last().unwrap();
+    assert_eq!((last.millisecs, last.bid, last.ask), (3599899, forward[10411].1.bid, forward[10411].1.ask));
+    let mut iter = bi5.iter().unwrap();
+    assert_eq!(iter.nth(5000), Some(forward[5000]));
+    assert_eq!(iter.progress(), Some((5001, 10412)));
+    assert_eq!(iter.nth(10412), None);
+    assert_eq!(bi5.iter().unwrap().count(), 10412);
+
+    let root = test_dir("seek", &["EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/16/15h_ticks.bi5"]);
+    let dir = Bi5::new(&root, None);
+    assert_eq!(dir.iter().unwrap().count(), 2 * 10412);
+    assert_eq!(dir.iter().unwrap().nth(10412).map(|(_, tick)| tick), Some(forward[0].1));
+    assert_eq!(dir.iter().unwrap().last().map(|(t, _)| t.to_string()).as_deref(), Some("2022-12-16 15:59:59.899"));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the summary of a directory spans its files, skipping empty ones, and matches iterating
+fn test_summary() {
+    let root = test_dir("summary", &["EURUSD/2022/11/16/15h_ticks.bi5", "EURUSD/2022/11/16/14h_ticks.bi5"]);
+    std::fs::write(root.join("EURUSD/2022/11/16/16h_ticks.bi5"), b"").unwrap();
+    let bi5 = Bi5::new(&root, None);
+    let summary = bi5.summary().unwrap();
+    let ticks: Vec<_> = bi5.iter().unwrap().collect();
+    assert_eq!(summary.tick_count, ticks.len());
+    assert_eq!((summary.first, summary.last), (ticks.first().copied(), ticks.last().copied()));
+    assert_eq!(summary.last.unwrap().0.to_string(), "2022-12-16 15:59:59.899");
+    std::fs::remove_dir_all(&root).unwrap();
+    let empty = test_dir("summary-empty", &[]);
+    std::fs::create_dir_all(&empty).unwrap();
+    assert_eq!(Bi5::new(&empty, None).summary().unwrap(), Bi5Summary::default());
+    std::fs::remove_dir_all(&empty).unwrap();
+}
+
+#[cfg(all(feature = "fs", unix))]
+#[test]
+/// Test that a FIFO is read like a file and other special files get a clear error
+fn test_special_files() {
+    let root = test_dir("fifo", &[]);
+    std::fs::create_dir_all(&root).unwrap();
+    let fifo = root.join("14h_ticks.bi5");
+    assert!(std::process::Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+    let writer = {
+        let fifo = fifo.clone();
+        std::thread::spawn(move || std::fs::write(fifo, std::fs::read("test/test.bi5").unwrap()).unwrap())
+    };
+    assert_eq!(Bi5::new(&fifo, None).iter().unwrap().count(), 10412);
+    writer.join().unwrap();
+    let err = Bi5::new("/dev/null", None).try_iter().next().unwrap().unwrap_err();
+    assert_eq!(err.to_string(), "/dev/null is a character device, not a regular file or directory");
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that setting the token ends a directory iteration mid-file
+fn test_cancel_token() {
+    let root = test_dir("cancel", &["2022/11/16/14h_ticks.bi5", "2022/11/16/15h_ticks.bi5"]);
+    let token = Arc::new(AtomicBool::new(false));
+    let bi5 = Bi5::builder(&root).cancel_token(token.clone()).build();
+    let mut iter = bi5.iter().unwrap();
+    assert_eq!(iter.by_ref().take(100).count(), 100);
+    token.store(true, Ordering::Relaxed);
+    assert!(iter.next().is_none());
+    token.store(false, Ordering::Relaxed);
+    assert!(iter.next().is_none());
+    assert!(bi5.try_iter().next().is_some());
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the audit records a corrupt file and keeps checking the others
+fn test_audit() {
+    let root = test_dir("audit", &["2022/11/16/14h_ticks.bi5", "2022/11/16/15h_ticks.bi5"]);
+    std::fs::write(root.join("2022/11/16/16h_ticks.bi5"), b"not lzma").unwrap();
+    let report = Bi5::new(&root, None).audit().unwrap();
+    assert_eq!(report.files.len(), 3);
+    assert_eq!(report.ticks(), 2 * 10412);
+    let problems: Vec<&FileAudit> = report.problems().collect();
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].path.ends_with("16h_ticks.bi5") && problems[0].error.is_some());
+    let file = &report.files[0];
+    assert!(file.monotonic && file.crossed == 0);
+    let (first, last) = report.time_range().unwrap();
+    assert_eq!((first.to_string(), last.to_string()), ("2022-12-16 14:31:00.002".to_string(), "2022-12-16 15:59:59.899".to_string()));
+    for threads in [0, 1, 2, 8] {
+        assert_eq!(Bi5::new(&root, None).audit_parallel(threads).unwrap(), report);
+    }
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that verify_length rejects partial ticks, truncated streams and directories
+fn test_verify_length() {
+    let root = test_dir("verify-length", &[]);
+    std::fs::create_dir_all(&root).unwrap();
+    let partial = root.join("partial.bi5");
+    let mut compressed = Vec::new();
+    lzma_rs::lzma_compress(&mut Cursor::new(vec![0u8; 21]), &mut compressed).unwrap();
+    std::fs::write(&partial, compressed).unwrap();
+    let bi5 = Bi5::builder(&partial).ignore_trailing_partial(true).build();
+    assert!(matches!(bi5.verify_length(), Err(Bi5Error::Length { len: 21, record: 20 })));
+    let truncated = root.join("truncated.bi5");
+    let bytes = std::fs::read("test/test.bi5").unwrap();
+    std::fs::write(&truncated, &bytes[..bytes.len() - 100]).unwrap();
+    assert!(matches!(Bi5::new(&truncated, None).verify_length(), Err(Bi5Error::Decompress(_))));
+    assert!(matches!(Bi5::new(&root, None).verify_length(), Err(Bi5Error::InvalidPath(_))));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the tolerance accepts small backward steps and ticks just past the hour
+fn test_audit_tolerance() {
+    let ticks = ticks_at(&[0, 1000, 990, HOUR_MILLISECS + 5]);
+    let path = testing::write_test_file(&test_dir("audit-tolerance", &[]), "00h_ticks.bi5", &ticks);
+    let strict = &Bi5::new(&path, None).audit().unwrap().files[0];
+    assert_eq!((strict.monotonic, strict.out_of_hour), (false, 1));
+    let lenient = &Bi5::builder(&path).tolerance(Duration::milliseconds(10)).build().audit().unwrap().files[0];
+    assert_eq!((lenient.monotonic, lenient.out_of_hour), (true, 0));
+    assert!(lenient.is_ok());
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that files below max_depth are skipped
+fn test_max_depth() {
+    let root = test_dir("max-depth", &["EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/16/old/EURUSD/2022/11/16/15h_ticks.bi5"]);
+    assert_eq!(Bi5::new(&root, None).iter().unwrap().count(), 2 * 10412);
+    assert_eq!(Bi5::builder(&root).max_depth(5).build().iter().unwrap().count(), 10412);
+    assert_eq!(Bi5::builder(&root).max_depth(4).build().iter().unwrap().count(), 0);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the columns agree with the ticks
+fn test_read_bi5_columnar() {
+    let ticks = read_bi5_file("test/test.bi5", None).unwrap();
+    let columns = read_bi5_columnar("test/test.bi5").unwrap();
+    assert_eq!(columns.len(), ticks.len());
+    for (i, tick) in ticks.iter().enumerate() {
+        let column = Tick { millisecs: columns.millisecs[i], ask: columns.ask[i], bid: columns.bid[i],
+                            asksize: columns.asksize[i], bidsize: columns.bidsize[i] };
+        assert_eq!(&column, tick);
+    }
+    assert!(read_bi5_columnar("test").is_err());
+}
+
+#[test]
+/// Test the cross-weighting of the microprice and the fallback without sizes
+fn test_microprice() {
+    let tick = Tick { millisecs: 0, ask: 110, bid: 100, asksize: 1.0, bidsize: 3.0 };
+    // more size on the bid pushes the microprice towards the ask
+    assert_eq!(tick.microprice_f64(1.0), 107.5);
+    assert_eq!(Tick { bidsize: 0.0, ..tick }.microprice_f64(10.0), 10.0);
+    assert_eq!(Tick { bidsize: 0.0, asksize: 0.0, ..tick }.microprice_f64(1.0), 105.0);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that unpadded directory names are walked in chronological, not lexical order
+fn test_walk_order() {
+    let root = test_dir("walk-order", &[
+        "EURUSD/2022/10/2/09h_ticks.bi5", "EURUSD/2022/9/10/01h_ticks.bi5",
+        "EURUSD/2022/9/2/10h_ticks.bi5", "EURUSD/2022/9/2/09h_ticks.bi5",
+    ]);
+    let starts: Vec<String> = Bi5::new(&root, None).files().map(|f| f.unwrap().0.to_string()).collect();
+    assert_eq!(starts, vec!["2022-10-02 09:00:00", "2022-10-02 10:00:00", "2022-10-10 01:00:00", "2022-11-02 09:00:00"]);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that both ends of every file are trimmed, and short files yield nothing
+fn test_trim_edges() {
+    let root = test_dir("trim-edges", &["EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/16/15h_ticks.bi5"]);
+    let ticks = read_bi5_file("test/test.bi5", None).unwrap();
+    let files: Vec<_> = Bi5::builder(&root).trim_edges(2, 3).build().files().map(|f| f.unwrap().1).collect();
+    assert_eq!(files.len(), 2);
+    assert!(files.iter().all(|file| file[..] == ticks[2..ticks.len() - 3]));
+    assert_eq!(Bi5::builder("test/test.bi5").trim_edges(0, 1).build().iter().unwrap().last().unwrap().1, ticks[10410]);
+    assert_eq!(Bi5::builder("test/test.bi5").trim_edges(10000, 500).build().iter().unwrap().count(), 0);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that a rewritten file misses the cache and the capacity evicts the oldest file
+fn test_decode_cache() {
+    let root = test_dir("decode-cache", &["EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/16/15h_ticks.bi5"]);
+    let cache = DecodeCache::new(1);
+    let file = Bi5::new(root.join("EURUSD/2022/11/16/14h_ticks.bi5"), None).with_cache(cache.clone());
+    assert_eq!(file.iter().unwrap().count(), 10412);
+    std::fs::copy("test/single.bi5", root.join("EURUSD/2022/11/16/14h_ticks.bi5")).unwrap();
+    assert_eq!(file.iter().unwrap().count(), 1);
+    assert_eq!(cache.len(), 1);
+    let dir = Bi5::new(&root, None).with_cache(cache.clone());
+    assert_eq!(dir.iter().unwrap().count(), 10413);
+    assert_eq!(dir.iter().unwrap().count(), 10413);
+    assert_eq!(cache.len(), 1);
+    cache.clear();
+    assert!(cache.is_empty());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test the symbol of the path taking precedence over the price magnitude
+fn test_infer_point_value() {
+    let root = test_dir("infer-point-value", &["USDJPY/2022/11/16/14h_ticks.bi5", "DEU.IDX/2022/11/16/14h_ticks.bi5"]);
+    assert_eq!(Bi5::new(root.join("USDJPY/2022/11/16/14h_ticks.bi5"), None).infer_point_value(10), Some(1e3));
+    assert_eq!(Bi5::new(root.join("DEU.IDX/2022/11/16/14h_ticks.bi5"), None).infer_point_value(10), Some(1e5));
+    assert_eq!(Bi5::new(&root, None).infer_point_value(0), None);
+    let jpy = Bi5::new(root.join("USDJPY/2022/11/16/14h_ticks.bi5"), None);
+    assert_eq!(jpy.resolve_point_value(Some("XAUUSD")), Some(1e3));
+    assert_eq!(jpy.resolve_point_value(Some("EURUSD")), Some(1e5));
+    assert_eq!(jpy.resolve_point_value(Some("unknown")), Some(1e3));
+    assert_eq!(Bi5::new(root.join("DEU.IDX"), None).resolve_point_value(Some("unknown")), Some(1e5));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that `.` components and trailing separators don't change directory
+/// detection, the walk or the parsed file starts
+fn test_dir_path_forms() {
+    let root = test_dir("path-forms", &["EURUSD/2022/11/16/14h_ticks.bi5"]);
+    let dir = root.to_str().unwrap();
+    for path in [format!("{}/", dir), format!("{}//", dir), format!("{}/.", dir), format!("{}/./EURUSD/2022/11/16/", dir)] {
+        let bi5 = Bi5::new(&path, None);
+        assert!(bi5.is_dir() && !bi5.is_file(), "{}", path);
+        let (t, _) = bi5.iter().unwrap().next().unwrap();
+        assert_eq!(t.to_string(), "2022-12-16 14:31:00.002", "{}", path);
+    }
+    assert_eq!(path_datetime(Path::new("EURUSD/2022/11/16/14h_ticks.bi5/")), path_datetime(Path::new("EURUSD/2022/11/16/14h_ticks.bi5")));
+    assert_eq!(symbol_from_path(Path::new("./EURUSD/2022/11/./16/14h_ticks.bi5")).as_deref(), Some("EURUSD"));
+    assert!(Bi5::new("./test", None).is_dir());
+    assert_eq!(Bi5::new("./test/", None).iter().unwrap().count(), 0);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test the file progress of a directory walk
+fn test_progress() {
+    let root = test_dir("progress", &["EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/16/15h_ticks.bi5", "README.txt"]);
+    let mut iter = Bi5::new(&root, None).iter().unwrap();
+    assert_eq!(iter.progress(), Some((1, 2)));
+    iter.nth(10412);
+    assert_eq!(iter.progress(), Some((2, 2)));
+    assert_eq!(iter.count(), 10411);
+    let mut file_iter = Bi5::new("test/test.bi5", None).iter_file().unwrap().skip_ticks(10).take_ticks(20);
+    file_iter.next_back();
+    assert_eq!(file_iter.progress(), (10, 29));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that files outside the window aren't decoded and the window is half-open
+fn test_window() {
+    let root = test_dir("window", &["EURUSD/2022/11/16/13h_ticks.bi5", "EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/16/15h_ticks.bi5"]);
+    // broken files outside the window would end the stream
+    for hour in [13, 15] {
+        std::fs::write(root.join(format!("EURUSD/2022/11/16/{}h_ticks.bi5", hour)), b"garbage").unwrap();
+    }
+    let t = |h, m, s, ms| NaiveDate::from_ymd_opt(2022, 12, 16).unwrap().and_hms_milli_opt(h, m, s, ms).unwrap();
+    let bi5 = Bi5::new(&root, None);
+    assert_eq!(bi5.window(t(14, 0, 0, 0), t(15, 0, 0, 0)).unwrap().count(), 10412);
+    let ticks: Vec<_> = bi5.window(t(14, 31, 0, 2), t(14, 31, 0, 124)).unwrap().collect();
+    assert_eq!(ticks.len(), 1);
+    assert_eq!(ticks[0].0, t(14, 31, 0, 2));
+    assert_eq!(bi5.iter_range(t(14, 30, 0, 0), t(14, 31, 0, 0)).unwrap().count(), 10412);
+    assert!(matches!(bi5.iter_range(t(14, 0, 0, 0), t(14, 0, 0, 0)).unwrap(), Bi5Iter::Empty));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+/// Test hour suffixes and that malformed components give `None` without touching the filesystem
+fn test_path_datetime() {
+    let hour = |name: &str| path_datetime(&std::path::Path::new("2022/11/16").join(name)).map(|t| t.format("%H").to_string());
+    assert_eq!(hour("14h_ticks.bi5").as_deref(), Some("14"));
+    assert_eq!(hour("EURUSD_14h_ticks.bi5").as_deref(), Some("14"));
+    assert_eq!(hour("x123h_ticks.bi5").as_deref(), Some("23"));
+    assert_eq!(hour("7h_ticks.bi5").as_deref(), Some("07"));
+    for name in ["h_ticks.bi5", "éh_ticks.bi5", "+1_ticks.bi5", "ticks.bi5", "7", ""] {
+        assert_eq!(hour(name), None, "{}", name);
+    }
+    assert_eq!(path_datetime(std::path::Path::new("2022/12/16/14h_ticks.bi5")), None);
+    assert_eq!(path_datetime(std::path::Path::new("2022/11/32/14h_ticks.bi5")), None);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that a raw file with a partial record is rejected
+fn test_read_raw_bin() {
+    let root = test_dir("read-raw-bin", &[]);
+    std::fs::create_dir_all(&root).unwrap();
+    let raw = root.join("14h_ticks.bin");
+    let mut buf = decompress_bi5(File::open("test/single.bi5").unwrap()).unwrap();
+    std::fs::write(&raw, &buf).unwrap();
+    assert_eq!(read_raw_bin(&raw).unwrap().len(), 1);
+    buf.push(0);
+    std::fs::write(&raw, &buf).unwrap();
+    assert!(read_raw_bin(&raw).is_err());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that undated files are ordered by their first tick and empty files are skipped
+fn test_iter_sort_by_content() {
+    let root = test_dir("sort-by-content", &["b.bi5"]);
+    testing::write_test_file(&root, "a.bi5", &ticks_at(&[3_000_000, 3_000_001]));
+    testing::write_test_file(&root, "c/c.bi5", &ticks_at(&[1000]));
+    testing::write_test_file(&root, "empty.bi5", &[]);
+    std::fs::write(root.join("README.txt"), "not a bi5 file").unwrap();
+    let millisecs: Vec<u32> = Bi5::new(&root, None).iter_sort_by_content().unwrap().map(|(_, tick)| tick.millisecs).collect();
+    assert_eq!(millisecs.len(), 1 + 10412 + 2);
+    assert_eq!((millisecs[0], millisecs[1], millisecs[10413]), (1000, 1860002, 3_000_000));
+    std::fs::write(root.join("broken.bi5"), b"garbage").unwrap();
+    assert!(Bi5::new(&root, None).iter_sort_by_content().is_err());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+/// Test rounding of halves and saturation of rescaled prices
+fn test_rescale() {
+    let tick = Tick { millisecs: 7, ask: 25, bid: 15, asksize: 1.0, bidsize: 2.0 };
+    let rescaled = tick.rescale(10.0, 1.0);
+    assert_eq!((rescaled.bid, rescaled.ask), (2, 3));
+    assert_eq!((rescaled.millisecs, rescaled.asksize, rescaled.bidsize), (7, 1.0, 2.0));
+    assert_eq!(tick.rescale(1.0, 1e9).ask, u32::MAX);
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that a file compressed twice fails by default and decodes with decompress_twice
+fn test_decompress_twice() {
+    let root = test_dir("decompress-twice", &[]);
+    std::fs::create_dir_all(&root).unwrap();
+    let path = root.join("14h_ticks.bi5");
+    let mut twice = Vec::new();
+    lzma_rs::lzma_compress(&mut &std::fs::read("test/test.bi5").unwrap()[..], &mut twice).unwrap();
+    std::fs::write(&path, twice).unwrap();
+    let e = Bi5::new(&path, None).iter().err().unwrap();
+    assert!(matches!(e.downcast_ref(), Some(Bi5Error::DoubleCompressed { format: "LZMA", .. })), "{}", e);
+    assert_eq!(Bi5::builder(&path).decompress_twice(true).build().iter().unwrap().count(), 10412);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the reverse order flips the files but not the ticks within a file
+fn test_with_order() {
+    let root = test_dir("order", &["EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/16/15h_ticks.bi5"]);
+    let bi5 = Bi5::new(&root, None).with_order(Order::ReverseChronological);
+    let ticks: Vec<(NaiveDateTime, Tick)> = bi5.iter().unwrap().collect();
+    assert_eq!(ticks.len(), 2 * 10412);
+    let hours: Vec<u32> = bi5.files().map(|file| file.unwrap().0.hour()).collect();
+    assert_eq!(hours, vec![15, 14]);
+    let (newest, oldest) = ticks.split_at(10412);
+    assert_eq!((newest[0].0.hour(), oldest[0].0.hour()), (15, 14));
+    for file in [newest, oldest] {
+        assert!(file.windows(2).all(|w| w[0].1.millisecs <= w[1].1.millisecs));
+    }
+    let forward: Vec<(NaiveDateTime, Tick)> = Bi5::new(&root, None).iter().unwrap().collect();
+    assert_eq!(forward[..10412], *oldest);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that par_read equals the sequential ticks in walk order, and its errors
+fn test_par_read() {
+    let root = test_dir("par-read", &[
+        "EURUSD/2022/11/16/15h_ticks.bi5", "EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/17/03h_ticks.bi5",
+        "AUDUSD/2022/11/16/16h_ticks.bi5",
+    ]);
+    for order in [Order::Chronological, Order::ReverseChronological] {
+        let bi5 = Bi5::new(&root, None).with_order(order);
+        assert_eq!(bi5.par_read(3).unwrap().ticks, bi5.iter().unwrap().collect::<Vec<_>>());
+    }
+    let corrupt = root.join("EURUSD/2022/11/16/16h_ticks.bi5");
+    std::fs::write(&corrupt, b"junk").unwrap();
+    let e = Bi5::new(&root, None).par_read(0).unwrap_err().to_string();
+    assert!(e.starts_with(&*corrupt.to_string_lossy()), "{}", e);
+    let read = Bi5::builder(&root).skip_errors(true).build().par_read(0).unwrap();
+    assert_eq!(read.ticks.len(), 4 * 10412);
+    assert_eq!(read.failed_files.len(), 1);
+    assert_eq!(read.failed_files[0].0, corrupt);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test the merge order, ties from `a` first and the exhausted side
+fn test_interleave() {
+    let root = test_dir("interleave", &["EURUSD/2022/11/16/14h_ticks.bi5", "GBPUSD/2022/11/16/14h_ticks.bi5", "GBPUSD/2022/11/16/15h_ticks.bi5"]);
+    let a = Bi5::new(root.join("EURUSD"), None);
+    let b = Bi5::new(root.join("GBPUSD"), None);
+    let merged = interleave(a, b).unwrap();
+    assert_eq!(merged.size_hint().0, 2 * 10412);
+    let merged: Vec<(NaiveDateTime, Source, Tick)> = merged.collect();
+    assert_eq!(merged.len(), 3 * 10412);
+    assert!(merged.windows(2).all(|w| w[0].0 <= w[1].0));
+    assert_eq!(merged[0].1, Source::A);
+    assert_eq!((merged[0].0, merged[0].2), (merged[1].0, merged[1].2));
+    assert_eq!(merged[1].1, Source::B);
+    assert!(merged[2 * 10412..].iter().all(|(t, source, _)| *source == Source::B && t.hour() == 15));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that a file of two concatenated hours reads as two hours with split_resets
+fn test_split_resets_option() {
+    let root = test_dir("split-resets", &[]);
+    let mut ticks = read_bi5_file("test/test.bi5", None).unwrap();
+    ticks.extend_from_within(..);
+    let path = testing::write_test_file(&root, "14h_ticks.bi5", &ticks);
+    let start = NaiveDateTime::from_timestamp_opt(1671199200, 0).unwrap();
+    let ticks: Vec<(NaiveDateTime, Tick)> = Bi5::new(&path, Some(start)).iter().unwrap().collect();
+    assert_eq!(ticks[10412].0, ticks[0].0);
+    let notices = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = notices.clone();
+    let bi5 = Bi5::builder(&path).date_time(start).split_resets(true)
+        .on_notice(move |notice| sink.lock().unwrap().push(notice))
+        .build();
+    let ticks: Vec<(NaiveDateTime, Tick)> = bi5.iter().unwrap().collect();
+    assert_eq!(ticks.len(), 2 * 10412);
+    assert!(matches!(notices.lock().unwrap()[..], [Notice::MillisecsResets { resets: 1, .. }]));
+    assert_eq!(ticks[10412].0, ticks[0].0 + Duration::hours(1));
+    assert!(ticks.windows(2).all(|w| w[0].0 <= w[1].0));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the bars of a day cover all hour files with their own times
+fn test_read_day_bars() {
+    let root = test_dir("day-bars", &["EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/16/15h_ticks.bi5"]);
+    let bars = read_day_bars(root.join("EURUSD/2022/11/16"), Duration::hours(1), 1e5).unwrap();
+    assert_eq!(bars.iter().map(|bar| bar.ticks).collect::<Vec<_>>(), vec![10412, 10412]);
+    assert_eq!(bars[1].start.to_string(), "2022-12-16 15:31:00.002");
+    assert_eq!(bars[0].open, (133117.0 + 133153.0) / 2.0 / 1e5);
+    assert!(read_day_bars(root.join("missing"), Duration::hours(1), 1e5).is_err());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[test]
+/// Test that the unsorted walk yields the ticks of all files, each file in order and with its own time
+fn test_iter_unsorted() {
+    let root = test_dir("unsorted", &["EURUSD/2022/11/16/15h_ticks.bi5", "EURUSD/2022/11/16/14h_ticks.bi5", "EURUSD/2022/11/17/00h_ticks.bi5"]);
+    let mut unsorted: Vec<(NaiveDateTime, Tick)> = Bi5::new(&root, None).iter_unsorted().unwrap().collect();
+    assert_eq!(unsorted.len(), 3 * 10412);
+    for file in unsorted.chunks(10412) {
+        assert!(file.windows(2).all(|w| w[0].0 <= w[1].0));
+    }
+    unsorted.sort_by_key(|(t, _)| *t);
+    let mut sorted: Vec<(NaiveDateTime, Tick)> = Bi5::new(&root, None).iter().unwrap().collect();
+    sorted.sort_by_key(|(t, _)| *t);
+    assert_eq!(unsorted, sorted);
+    assert_eq!(Bi5::new("test/test.bi5", None).iter_unsorted().unwrap().count(), 10412);
+    assert!(Bi5::new(root.join("missing"), None).iter_unsorted().is_err());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+/// Test that `TimedTick::to_json` parses back to the exact values
+fn test_timed_tick_json() {
+    let tick = Tick { millisecs: 3599899, ask: 133153, bid: 133117, asksize: 0.1 + 0.2, bidsize: f32::INFINITY };
+    let t = NaiveDateTime::from_timestamp_millis(1671202799899).unwrap();
+    let json = json::parse(&TimedTick::from((t, tick)).to_json()).unwrap();
+    assert_eq!(json.get("t").and_then(json::Json::as_str), Some("2022-12-16T14:59:59.899"));
+    assert_eq!(json.get("millisecs").and_then(json::Json::as_f64), Some(3599899.0));
+    assert_eq!(json.get("asksize").and_then(json::Json::as_f64).map(|x| x as f32), Some(tick.asksize));
+    assert_eq!(json.get("bidsize"), Some(&json::Json::Null));
+}
@@ -1,10 +1,8 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use bi5::*;
 use anyhow::Error;
-use chrono::{
-    naive::NaiveDateTime,
-    Duration
-};
+use std::{io::Write, path::PathBuf};
+use chrono::naive::NaiveDateTime;
 
 /// Command line arguments
 #[derive(Parser, Debug)]
@@ -13,7 +11,7 @@ use chrono::{
 #[command(about = "Dump a bi5 tick file to stdout.")]
 #[command(version, about, long_about = None)]
 struct CliArgs {
-   /// Filename
+   /// Filename, or a Dukascopy http(s) URL (feature http)
    input: String,
    /// Date in yyyy-mm-ddTHH:MM:SS format
    #[arg(short, long="date")]
@@ -23,28 +21,431 @@ struct CliArgs {
    sep: String,
    /// Count ticks
    #[arg(short, long, default_value_t=false)]
-   count: bool
+   count: bool,
+   /// Only output the first N ticks
+   #[arg(short = 'n', long, value_name = "N")]
+   limit: Option<usize>,
+   /// Timestamp format: iso, epoch_ms, epoch_ns or a chrono format string
+   #[arg(short, long, value_name = "FORMAT")]
+   time_format: Option<TimeFormat>,
+   /// Print the decompressed file as hex dump instead of ticks
+   #[arg(long, default_value_t=false)]
+   hexdump: bool,
+   /// Prepend a symbol column taken from the path, ticks of the same hour are merged in time order
+   #[arg(long, default_value_t=false)]
+   with_symbol: bool,
+   /// Write a sidecar index with the tick count next to each file, used by --count
+   #[arg(long, default_value_t=false)]
+   write_index: bool,
+   /// Only read files with this extension, e.g. bi5
+   #[arg(long, value_name = "EXT")]
+   extension: Option<String>,
+   /// Check that the file decompresses to whole ticks with non-decreasing times, prints nothing on success
+   #[arg(long, default_value_t=false)]
+   validate: bool,
+   /// Check every file, print a table of tick counts, order, crossed/locked quotes and time ranges, exits with 1 on problem files
+   #[arg(long, default_value_t=false)]
+   audit: bool,
+   /// Milliseconds of clock jitter to accept: backward steps (--validate, --audit) and ticks past the hour (--audit)
+   #[arg(long, value_name = "MS", default_value_t = 0)]
+   tolerance: u32,
+   /// Write csv files per day or hour into --out-dir instead of stdout, appending to existing files
+   #[arg(long, value_name = "PARTITION")]
+   partition_by: Option<Partition>,
+   /// Output directory of --partition-by
+   #[arg(long, value_name = "DIR", default_value = "out")]
+   out_dir: PathBuf,
+   /// Output format: csv with --sep, one JSON object per line (jsonl or ndjson), a single JSON array, or headerless csv for kdb+ `("PFFFF";",") 0:`; --sep only applies to csv, JSON has the fields of TimedTick::to_json with ISO times and unscaled prices
+   #[arg(long, value_enum, default_value_t = Format::Csv)]
+   format: Format,
+   /// Comma separated columns of the csv output, e.g. t,bid,ask [default: all]
+   #[arg(long, value_enum, value_name = "COLUMNS", value_delimiter = ',')]
+   columns: Vec<Column>,
+   /// Print a summary of a file: sizes, compression ratio, tick count, time span, point value, crossed/locked quotes
+   #[arg(long, default_value_t=false)]
+   info: bool,
+   /// Print a summary (count, time span, price range, spreads) instead of ticks
+   #[arg(long, default_value_t=false)]
+   stats: bool,
+   /// Only output ticks whose spread is at least N points, or pips with --symbol
+   #[arg(long, value_name = "N")]
+   min_spread: Option<f64>,
+   /// Instrument, e.g. EURUSD, sets the units of --stats prices and spreads, --min-spread and the --info point value
+   #[arg(long, value_name = "SYMBOL")]
+   symbol: Option<String>,
+   /// Print bid and ask divided by this point value, e.g. 100000 for EURUSD or 1000 for USDJPY
+   #[arg(long, value_name = "N")]
+   point: Option<u32>,
+   /// Decimal places of the size columns
+   #[arg(long, value_name = "N", default_value_t = 3)]
+   size_precision: usize
 }
 
+/// Output format of the ticks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Csv,
+    // one object per line, also accepted as ndjson
+    #[value(alias = "ndjson")]
+    Jsonl,
+    Json,
+    // comma separated without header, q timestamps like `2022.12.16D14:31:00.002000000`
+    Kdb,
+}
+
+/// Column of the csv output
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Column {
+    T,
+    Bid,
+    Ask,
+    Bidsize,
+    Asksize,
+}
+
+impl Column {
+    const ALL: [Column; 5] = [Column::T, Column::Bid, Column::Ask, Column::Bidsize, Column::Asksize];
+
+    fn name(self) -> &'static str {
+        match self {
+            Column::T => "t",
+            Column::Bid => "bid",
+            Column::Ask => "ask",
+            Column::Bidsize => "bidsize",
+            Column::Asksize => "asksize",
+        }
+    }
+}
+
+/// Writes the `columns` of a tick, `t` is the formatted time
+fn write_csv<W: Write + ?Sized>(out: &mut W, columns: &[Column], sep: &str, t: &str, tick: &Tick, precision: usize, point: Option<PointValue>) -> std::io::Result<()> {
+    for (i, column) in columns.iter().enumerate() {
+        if i > 0 {
+            out.write_all(sep.as_bytes())?;
+        }
+        match column {
+            Column::T => write!(out, "{}", t)?,
+            Column::Bid => write!(out, "{}", format_price(tick.bid, point))?,
+            Column::Ask => write!(out, "{}", format_price(tick.ask, point))?,
+            Column::Bidsize => write!(out, "{:.*}", precision, tick.bidsize)?,
+            Column::Asksize => write!(out, "{:.*}", precision, tick.asksize)?,
+        }
+    }
+    writeln!(out)
+}
+
+/// Classic hex dump: offset, 16 bytes in hex and as ASCII
+fn hexdump<W: Write>(buf: &[u8], out: &mut W) -> std::io::Result<()> {
+    for (i, line) in buf.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = line.iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        writeln!(out, "{:08x}  {:<47}  |{}|", i * 16, hex.join(" "), ascii)?;
+    }
+    writeln!(out, "{:08x}", buf.len())
+}
+
+/// Checks that `path` decompresses to whole ticks in time order, up to
+/// backward steps of `tolerance` milliseconds
+fn validate(path: &str, tolerance: u32) -> Result<(), Error> {
+    let buf = decompress_bi5(std::fs::File::open(path)?)?;
+    let ticks = parse_ticks(&buf)?;
+    if let Some(i) = millisecs_resets(&ticks).first() {
+        return Err(anyhow::anyhow!("millisecs reset at tick {} (byte offset {}), the file is probably concatenated from several hours", i, i * 20));
+    }
+    let mut latest = 0;
+    for (i, tick) in ticks.iter().enumerate() {
+        if (tick.millisecs as u64 + tolerance as u64) < latest as u64 {
+            return Err(anyhow::anyhow!("millisecs decrease at tick {}", i));
+        }
+        latest = latest.max(tick.millisecs);
+    }
+    Ok(())
+}
+
+/// Downloads the bi5 file at `url` with curl and decodes the stream, the
+/// file start is taken from `date_time` or else the URL path
+#[cfg(feature = "http")]
+fn url_ticks(url: &str, date_time: Option<NaiveDateTime>) -> Result<Vec<(NaiveDateTime, Tick)>, Error> {
+    use std::process::{Command, Stdio};
+    let mut child = Command::new("curl").args(["-sSfL", url])
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("running curl: {}", e))?;
+    let zero = chrono::NaiveDate::from_ymd_opt(0, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let date_time = date_time.or_else(|| datetime_from_dukascopy_url(url)).unwrap_or(zero);
+    let ticks = read_bi5_reader(child.stdout.take().expect("piped"), date_time);
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("downloading {} failed ({})", url, status));
+    }
+    ticks
+}
+
+#[cfg(not(feature = "http"))]
+fn url_ticks(_url: &str, _date_time: Option<NaiveDateTime>) -> Result<Vec<(NaiveDateTime, Tick)>, Error> {
+    Err(anyhow::anyhow!("reading URLs needs catbi5 built with the http feature"))
+}
+
+/// Prints the `--stats` summary, with `decimals` prices are scaled and
+/// spreads given in pips (10 points)
+fn print_stats<I>(ticks: I, decimals: Option<u32>, time_format: &Option<TimeFormat>) -> Result<(), Error>
+where I: Iterator<Item = (NaiveDateTime, Tick)>
+{
+    let (mut count, mut spread_sum, mut spread_max) = (0u64, 0i64, i64::MIN);
+    let (mut first, mut last, mut low, mut high) = (None, None, u32::MAX, 0);
+    for (t, tick) in ticks {
+        let spread = tick.spread();
+        count += 1;
+        spread_sum += spread;
+        spread_max = spread_max.max(spread);
+        first.get_or_insert(t);
+        last = Some(t);
+        low = low.min(tick.bid);
+        high = high.max(tick.ask);
+    }
+    println!("ticks: {}", count);
+    let (first, last) = match (first, last) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Ok(()),
+    };
+    let time = |t| time_format.as_ref().map_or_else(|| NaiveDateTime::to_string(&t), |f| f.format(t));
+    let price = |raw: u32| match decimals {
+        Some(decimals) => FixedPoint { mantissa: raw, scale: decimals }.to_string(),
+        None => raw.to_string(),
+    };
+    let spread = |raw: f64| match decimals {
+        Some(_) => format!("{:.1} pips", raw / 10.0),
+        None => format!("{:.1}", raw),
+    };
+    println!("first: {}", time(first));
+    println!("last: {}", time(last));
+    println!("bid low: {}", price(low));
+    println!("ask high: {}", price(high));
+    println!("mean spread: {}", spread(spread_sum as f64 / count as f64));
+    println!("max spread: {}", spread(spread_max as f64));
+    Ok(())
+}
+
+/// Prints the `--info` summary of the file `path`, `symbol` is the `--symbol`
+fn print_info(bi5: &Bi5, path: &str, symbol: Option<&str>, time_format: &Option<TimeFormat>) -> Result<(), Error> {
+    if !bi5.is_file() {
+        return Err(anyhow::anyhow!("--info needs a file, {} isn't one", path));
+    }
+    let compressed = std::fs::metadata(path)?.len();
+    let decompressed = decompress_bi5(std::fs::File::open(path)?)?.len();
+    let summary = bi5.summary()?;
+    let time = |t| time_format.as_ref().map_or_else(|| NaiveDateTime::to_string(&t), |f| f.format(t));
+    println!("compressed size: {}", compressed);
+    println!("decompressed size: {}", decompressed);
+    if compressed > 0 {
+        println!("compression ratio: {:.2}", decompressed as f64 / compressed as f64);
+    }
+    println!("ticks: {}", summary.tick_count);
+    if let (Some((first, _)), Some((last, _))) = (summary.first, summary.last) {
+        println!("first: {}", time(first));
+        println!("last: {}", time(last));
+    }
+    if let Some(point_value) = bi5.resolve_point_value(symbol) {
+        println!("point value: {}", point_value);
+    }
+    let (crossed, locked) = bi5.iter()?.fold((0, 0), |(crossed, locked), (_, tick)| {
+        (crossed + tick.is_crossed() as usize, locked + tick.is_locked() as usize)
+    });
+    println!("crossed: {}", crossed);
+    println!("locked: {}", locked);
+    Ok(())
+}
+
+/// Prints the `--audit` table and summary, returns true if all files are ok
+fn print_audit(report: &AuditReport, sep: &str, time_format: &Option<TimeFormat>) -> bool {
+    let time = |t| time_format.as_ref().map_or_else(|| NaiveDateTime::to_string(&t), |f| f.format(t));
+    println!("path{}ticks{}monotonic{}out_of_hour{}crossed{}locked{}first{}last{}status", sep, sep, sep, sep, sep, sep, sep, sep);
+    for file in &report.files {
+        let (first, last) = file.time_range.map_or((String::new(), String::new()), |(first, last)| (time(first), time(last)));
+        let status = match (&file.error, file.monotonic) {
+            (Some(error), _) => error.clone(),
+            (None, false) => "out of order".to_string(),
+            (None, true) if file.out_of_hour > 0 => "out of hour".to_string(),
+            (None, true) => "ok".to_string(),
+        };
+        println!("{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}", file.path.to_string_lossy(), sep, file.ticks, sep, file.monotonic,
+            sep, file.out_of_hour, sep, file.crossed, sep, file.locked, sep, first, sep, last, sep, status);
+    }
+    let problems = report.problems().count();
+    println!("{} files, {} ticks, {} problem files", report.files.len(), report.ticks(), problems);
+    problems == 0
+}
+
+/// Ticks of all files tagged with their symbol, the files of each hour are
+/// merged into one time-ordered stream
+fn with_symbol(bi5: &Bi5) -> Result<impl Iterator<Item = (String, NaiveDateTime, Tick)>, Error> {
+    let scan = bi5.scan()?;
+    let mut hours: Vec<Vec<(PathBuf, NaiveDateTime)>> = Vec::new();
+    for file in scan.files() {
+        match hours.last_mut() {
+            Some(hour) if hour[0].1 == file.1 => hour.push(file.clone()),
+            _ => hours.push(vec![file.clone()]),
+        }
+    }
+    Ok(hours.into_iter().flat_map(|hour| {
+        let mut ticks: Vec<(String, NaiveDateTime, Tick)> = Vec::new();
+        for (path, date_time) in hour {
+            let symbol = symbol_from_path(&path).unwrap_or_default();
+            let iter = Bi5::new(&path, Some(date_time)).iter().into_iter().flatten();
+            ticks.extend(iter.map(|(t, tick)| (symbol.clone(), t, tick)));
+        }
+        ticks.sort_by_key(|(_, t, _)| *t);
+        ticks
+    }))
+}
 
 fn main() -> Result<(), Error> 
 {
     let args: CliArgs = CliArgs::parse();
 
-    let bi5 = Bi5::new(&args.input, args.date_time);
+    let mut builder = Bi5::builder(&args.input);
+    if let Some(extension) = &args.extension {
+        builder = builder.extension(extension);
+    }
+    if let Some(date_time) = args.date_time {
+        builder = builder.date_time(date_time);
+    }
+    let bi5 = builder.tolerance(chrono::Duration::milliseconds(args.tolerance as i64)).build();
+    // take() stops pulling ticks, so further files of a directory aren't decompressed
+    let limit = args.limit.unwrap_or(usize::MAX);
+
+    if args.validate {
+        if let Err(err) = validate(&args.input, args.tolerance) {
+            eprintln!("{}: {}", args.input, err);
+            std::process::exit(1);
+        }
+        return Ok(())
+    }
+
+    if args.audit {
+        if !print_audit(&bi5.audit_parallel(0)?, &args.sep, &args.time_format) {
+            std::process::exit(1);
+        }
+        return Ok(())
+    }
+
+    if args.info {
+        return print_info(&bi5, &args.input, args.symbol.as_deref(), &args.time_format);
+    }
+
+    if args.hexdump {
+        let buf = decompress_bi5(std::fs::File::open(&args.input)?)?;
+        hexdump(&buf, &mut std::io::stdout().lock())?;
+        return Ok(())
+    }
+
+    if args.write_index {
+        println!("{}:{} files indexed", args.input, bi5.write_index()?);
+        return Ok(())
+    }
 
     if args.count {
-        println!("{}:{}", args.input, bi5.iter()?.count());
+        // sidecar indexes only help for whole counts
+        let count = match args.limit {
+            Some(limit) => bi5.iter()?.take(limit).count(),
+            None => bi5.count()?,
+        };
+        println!("{}:{}", args.input, count);
         return Ok(())
     }
 
     let sep = &args.sep;
-    println!("t{}bid{}ask{}bidsize{}asksize",sep,sep,sep,sep);
-    for (date_time, tick) in bi5.iter()? {
-        let t: NaiveDateTime = date_time + Duration::milliseconds(tick.millisecs as i64);
-        println!("{}{}{}{}{}{}{}{}{}", 
-                  t, sep, tick.bid, sep, tick.ask, sep, tick.bidsize, sep, tick.asksize
-                );
+    let p = args.size_precision;
+    let point = args.point.map(PointValue::new).transpose()?;
+    let columns = if args.columns.is_empty() { &Column::ALL[..] } else { &args.columns[..] };
+    let mut header = columns.iter().map(|column| column.name()).collect::<Vec<_>>().join(sep);
+    let is_url = args.input.starts_with("http://") || args.input.starts_with("https://");
+    let ticks: Box<dyn Iterator<Item = (String, NaiveDateTime, Tick)>> = if is_url {
+        let ticks = url_ticks(&args.input, args.date_time)?;
+        Box::new(ticks.into_iter().map(|(t, tick)| (String::new(), t, tick)))
+    } else if args.with_symbol {
+        header = format!("symbol{}{}", sep, header);
+        Box::new(with_symbol(&bi5)?)
+    } else {
+        Box::new(bi5.iter()?.map(|(t, tick)| (String::new(), t, tick)))
+    };
+    let decimals = match &args.symbol {
+        Some(symbol) => Some(PointTable::builtin().decimals(symbol)
+            .ok_or_else(|| anyhow::anyhow!("unknown point value of {}", symbol))?),
+        None => None,
+    };
+    let ticks: Box<dyn Iterator<Item = (String, NaiveDateTime, Tick)>> = match args.min_spread {
+        Some(min_spread) => {
+            // a pip is 10 points
+            let min_spread = if decimals.is_some() { min_spread * 10.0 } else { min_spread };
+            Box::new(ticks.filter(move |(_, _, tick)| tick.spread() as f64 >= min_spread))
+        }
+        None => ticks,
+    };
+    if args.stats {
+        return print_stats(ticks.take(limit).map(|(_, t, tick)| (t, tick)), decimals, &args.time_format);
+    }
+
+    if args.partition_by.is_some() && args.format != Format::Csv {
+        return Err(anyhow::anyhow!("--partition-by only writes csv"));
+    }
+    if !args.columns.is_empty() && args.format != Format::Csv {
+        return Err(anyhow::anyhow!("--columns only applies to csv"));
+    }
+    let mut partitions = match args.partition_by {
+        Some(partition) => Some(PartitionWriter::new(&args.out_dir, partition, &header)?),
+        None => {
+            if args.format == Format::Csv {
+                println!("{}", header);
+            }
+            None
+        }
+    };
+    let stdout = std::io::stdout();
+    let mut stdout = std::io::BufWriter::new(stdout.lock());
+    if args.format == Format::Json {
+        write!(stdout, "[")?;
+    }
+    let mut empty = true;
+    for (symbol, date_time, tick) in ticks.take(limit) {
+        let t = match &args.time_format {
+            Some(time_format) => time_format.format(date_time),
+            None => date_time.to_string(),
+        };
+        let symbol = if args.with_symbol { Some(symbol.as_str()) } else { None };
+        match args.format {
+            Format::Csv => {
+                let out: &mut dyn Write = match partitions.as_mut() {
+                    Some(partitions) => partitions.writer(date_time)?,
+                    None => &mut stdout,
+                };
+                if let Some(symbol) = symbol {
+                    write!(out, "{}{}", symbol, sep)?;
+                }
+                write_csv(out, columns, sep, &t, &tick, p, point)?;
+            }
+            Format::Kdb => {
+                // a symbol column makes it ("SPFFFF";",")
+                if let Some(symbol) = symbol {
+                    write!(stdout, "{},", symbol)?;
+                }
+                writeln!(stdout, "{},{},{},{:.*},{:.*}", date_time.format("%Y.%m.%dD%H:%M:%S%.9f"),
+                         format_price(tick.bid, point), format_price(tick.ask, point), p, tick.bidsize, p, tick.asksize)?;
+            }
+            Format::Jsonl => writeln!(stdout, "{}", TimedTick::from((date_time, tick)).to_json_with_symbol(symbol))?,
+            // the separator goes before each object, so there is no trailing comma
+            Format::Json => write!(stdout, "{}\n{}", if empty { "" } else { "," }, TimedTick::from((date_time, tick)).to_json_with_symbol(symbol))?,
+        }
+        empty = false;
+    }
+    if args.format == Format::Json {
+        writeln!(stdout, "{}]", if empty { "" } else { "\n" })?;
+    }
+    if let Some(partitions) = partitions.as_mut() {
+        partitions.flush()?;
     }
+    stdout.flush()?;
     Ok(())
 }
@@ -0,0 +1,132 @@
+//! Writing ticks to one file per day or hour, see `PartitionWriter`.
+
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use chrono::NaiveDateTime;
+
+/// Granularity of `PartitionWriter` files, parses from `day` and `hour`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Partition {
+    /// `YYYY-MM-DD`
+    Day,
+    /// `YYYY-MM-DDTHH`
+    Hour,
+}
+
+impl Partition {
+    /// Key of the partition containing `t`, used as file stem
+    pub fn key(&self, t: NaiveDateTime) -> String {
+        match self {
+            Partition::Day => t.format("%Y-%m-%d").to_string(),
+            Partition::Hour => t.format("%Y-%m-%dT%H").to_string(),
+        }
+    }
+}
+
+impl FromStr for Partition {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(Partition::Day),
+            "hour" => Ok(Partition::Hour),
+            _ => Err(format!("invalid partition {:?}, expected day or hour", s)),
+        }
+    }
+}
+
+impl fmt::Display for Partition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Partition::Day => write!(f, "day"),
+            Partition::Hour => write!(f, "hour"),
+        }
+    }
+}
+
+/// Routes output to `<dir>/<key>.csv` by the partition of each tick time
+///
+/// Files are appended to, so several runs can fill the same partitions.
+/// `header` is written to files that are new or empty. One file is open at a
+/// time, which suits time-sorted input.
+/// ```
+/// use std::io::Write;
+/// use bi5::*;
+/// let dir = std::env::temp_dir().join(format!("bi5-doc-partition-{}", std::process::id()));
+/// let mut writer = PartitionWriter::new(&dir, Partition::Day, "t,bid,ask").unwrap();
+/// for (t, tick) in Bi5::new("test/test.bi5", None).iter().unwrap() {
+///     writeln!(writer.writer(t).unwrap(), "{},{},{}", t, tick.bid, tick.ask).unwrap();
+/// }
+/// writer.flush().unwrap();
+/// assert!(dir.join("0000-01-01.csv").is_file());
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub struct PartitionWriter {
+    dir: PathBuf,
+    partition: Partition,
+    header: String,
+    current: Option<(String, BufWriter<File>)>,
+}
+
+impl PartitionWriter {
+    /// Writer into `dir`, which is created if missing
+    pub fn new<P: AsRef<Path>>(dir: P, partition: Partition, header: &str) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(PartitionWriter { dir: dir.as_ref().to_path_buf(), partition, header: header.to_string(), current: None })
+    }
+
+    /// Path of the file for the partition `key`
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.csv", key))
+    }
+
+    /// The file of the partition containing `t`, opened (and the previous one
+    /// flushed) when the partition changes
+    pub fn writer(&mut self, t: NaiveDateTime) -> io::Result<&mut BufWriter<File>> {
+        let key = self.partition.key(t);
+        if self.current.as_ref().is_none_or(|(current, _)| *current != key) {
+            self.flush()?;
+            let file = OpenOptions::new().create(true).append(true).open(self.path(&key))?;
+            let is_empty = file.metadata()?.len() == 0;
+            let mut writer = BufWriter::new(file);
+            if is_empty {
+                writeln!(writer, "{}", self.header)?;
+            }
+            self.current = Some((key, writer));
+        }
+        Ok(&mut self.current.as_mut().expect("opened above").1)
+    }
+
+    /// Flush the open file
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.current.as_mut() {
+            Some((_, writer)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[test]
+/// Test that ticks land in their day, the header is written once across runs
+fn test_partition_writer() {
+    let dir = std::env::temp_dir().join(format!("bi5-partition-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let t = |d, h| chrono::NaiveDate::from_ymd_opt(2022, 12, d).unwrap().and_hms_opt(h, 0, 0).unwrap();
+    for _ in 0..2 {
+        let mut writer = PartitionWriter::new(&dir, Partition::Day, "t").unwrap();
+        for time in [t(16, 23), t(17, 0), t(17, 1)] {
+            writeln!(writer.writer(time).unwrap(), "{}", time).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+    let day = |d| fs::read_to_string(dir.join(format!("2022-12-{}.csv", d))).unwrap();
+    assert_eq!(day(16), "t\n2022-12-16 23:00:00\n2022-12-16 23:00:00\n");
+    assert_eq!(day(17).lines().filter(|line| *line == "t").count(), 1);
+    assert_eq!(day(17).lines().count(), 5);
+    assert_eq!(Partition::Hour.key(t(17, 1)), "2022-12-17T01");
+    fs::remove_dir_all(&dir).unwrap();
+}
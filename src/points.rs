@@ -0,0 +1,191 @@
+//! Point values of instruments, see `PointTable`.
+
+use std::collections::HashMap;
+use crate::{json::{self, Json}, Bi5Error};
+
+/// Number of decimals of the raw prices of each instrument
+///
+/// A raw price `133117` with 5 decimals is `1.33117`. Symbols not in the
+/// table fall back to the FX convention: 6-letter currency pairs have 5
+/// decimals, or 3 if the quote currency is JPY. A pip is 10 points.
+/// ```
+/// use bi5::PointTable;
+/// let table = PointTable::builtin();
+/// assert_eq!(table.decimals("EURUSD"), Some(5));
+/// assert_eq!(table.decimals("usdjpy"), Some(3));
+/// assert_eq!(table.decimals("DEU.IDX/EUR"), None);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PointTable {
+    decimals: HashMap<String, u32>,
+}
+
+/// Instruments that don't follow the FX convention
+const BUILTIN: [(&str, u32); 2] = [("XAUUSD", 3), ("XAGUSD", 3)];
+
+impl PointTable {
+    /// Table with the built-in entries
+    pub fn builtin() -> Self {
+        let mut table = PointTable::default();
+        for (symbol, decimals) in BUILTIN {
+            table.insert(symbol, decimals);
+        }
+        table
+    }
+
+    /// Built-in table extended with the instruments of Dukascopy's instrument
+    /// metadata JSON
+    ///
+    /// The JSON has an `instruments` object whose entries name the symbol in
+    /// `historical_filename`, as used in the download paths, and the pip size
+    /// in `pipValue`, e.g. `0.0001`. With a pip of 10 points that is 5
+    /// decimals. Entries without these fields are skipped, symbols not in the
+    /// JSON keep the built-in entries and the FX convention. A JSONP wrapper
+    /// like `callback({...});` is stripped.
+    /// ```
+    /// use bi5::PointTable;
+    /// let json = r#"{"instruments": {"DEU.IDX/EUR": {"historical_filename": "DEUIDXEUR", "pipValue": 1}}}"#;
+    /// let table = PointTable::from_dukascopy_json_str(json).unwrap();
+    /// assert_eq!(table.decimals("DEUIDXEUR"), Some(1));
+    /// assert_eq!(table.decimals("XAUUSD"), Some(3));
+    /// ```
+    pub fn from_dukascopy_json_str(s: &str) -> Result<Self, Bi5Error> {
+        let s = s.trim();
+        let s = match (s.starts_with('{'), s.find('{'), s.rfind('}')) {
+            (false, Some(start), Some(end)) if start < end => &s[start..=end],
+            _ => s,
+        };
+        let instruments = match json::parse(s)?.get("instruments") {
+            Some(Json::Object(instruments)) => instruments.clone(),
+            _ => return Err(Bi5Error::Parse("no \"instruments\" object in the instrument metadata".to_string())),
+        };
+        let mut table = PointTable::builtin();
+        for (_, instrument) in &instruments {
+            let symbol = instrument.get("historical_filename").and_then(Json::as_str);
+            let pip = instrument.get("pipValue").and_then(Json::as_f64).filter(|pip| *pip > 0.0 && pip.is_finite());
+            if let (Some(symbol), Some(pip)) = (symbol, pip) {
+                // a point is a tenth of a pip
+                let decimals = (1.0 - pip.log10()).round().max(0.0) as u32;
+                table.insert(symbol, decimals);
+            }
+        }
+        Ok(table)
+    }
+
+    /// `from_dukascopy_json_str` of the file at `path`
+    #[cfg(feature = "fs")]
+    pub fn from_dukascopy_json<P: AsRef<std::path::Path>>(path: P) -> Result<Self, anyhow::Error> {
+        Ok(Self::from_dukascopy_json_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Add or replace the entry of `symbol`
+    pub fn insert(&mut self, symbol: &str, decimals: u32) {
+        self.decimals.insert(symbol.to_ascii_uppercase(), decimals);
+    }
+
+    /// Decimals of `symbol` (case-insensitive), `None` if unknown
+    pub fn decimals(&self, symbol: &str) -> Option<u32> {
+        let symbol = symbol.to_ascii_uppercase();
+        if let Some(&decimals) = self.decimals.get(&symbol) {
+            return Some(decimals);
+        }
+        if symbol.len() == 6 && symbol.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return Some(if symbol.ends_with("JPY") { 3 } else { 5 });
+        }
+        None
+    }
+
+    /// `1 / price unit` of `symbol`, the `point_value` of `Price::as_f64`
+    pub fn point_value(&self, symbol: &str) -> Option<f64> {
+        Some(10f64.powi(self.decimals(symbol)? as i32))
+    }
+}
+
+/// Number of raw price units per 1.0 of an instrument, never zero
+///
+/// `PointValue::FX` (`100000`) fits most FX pairs, `PointValue::JPY` (`1000`)
+/// JPY pairs and metals.
+/// ```
+/// use bi5::{PointValue, Tick};
+/// let tick = Tick { millisecs: 0, ask: 133153, bid: 133117, asksize: 0.015, bidsize: 0.02 };
+/// assert_eq!(tick.to_prices(PointValue::FX.get()).unwrap(), (1.33117, 1.33153));
+/// assert_eq!(PointValue::for_symbol("USDJPY"), Some(PointValue::JPY));
+/// assert!(PointValue::new(0).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PointValue(u32);
+
+impl PointValue {
+    /// 5 decimals, most FX pairs
+    pub const FX: PointValue = PointValue(100_000);
+    /// 3 decimals, JPY pairs and metals
+    pub const JPY: PointValue = PointValue(1_000);
+
+    /// `Bi5Error::ZeroPointValue` for 0
+    pub fn new(point_value: u32) -> Result<Self, Bi5Error> {
+        match point_value {
+            0 => Err(Bi5Error::ZeroPointValue),
+            n => Ok(PointValue(n)),
+        }
+    }
+
+    /// Point value of `symbol` in `PointTable::builtin`, `None` if unknown
+    /// or beyond `u32`
+    pub fn for_symbol(symbol: &str) -> Option<Self> {
+        PointValue::new(10u32.checked_pow(PointTable::builtin().decimals(symbol)?)?).ok()
+    }
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Number of decimals if the point value is a power of ten
+    pub fn decimals(self) -> Option<u32> {
+        let decimals = self.0.ilog10();
+        (10u32.pow(decimals) == self.0).then_some(decimals)
+    }
+}
+
+#[test]
+/// Test that entries override the FX convention
+fn test_point_table() {
+    let mut table = PointTable::builtin();
+    assert_eq!(table.decimals("XAUUSD"), Some(3));
+    assert_eq!(table.point_value("GBPUSD"), Some(1e5));
+    table.insert("eurusd", 4);
+    assert_eq!(table.decimals("EURUSD"), Some(4));
+    assert_eq!(table.decimals("EURUS1"), None);
+}
+
+#[test]
+/// Test the decimals from pip sizes, skipped entries, fallbacks and a JSONP wrapper
+fn test_from_dukascopy_json() {
+    let json = r#"jsonp({"instruments": {
+        "EUR/USD": {"title": "EUR/USD", "historical_filename": "EURUSD", "pipValue": 0.0001, "tag_list": ["CFD"]},
+        "USD/JPY": {"historical_filename": "USDJPY", "pipValue": "0.01"},
+        "BTC/USD": {"historical_filename": "BTCUSD", "pipValue": 1},
+        "XAU/USD": {"historical_filename": "XAUUSD", "pipValue": 0.01},
+        "BAD": {"historical_filename": "BAD", "pipValue": 0},
+        "NONAME": {"pipValue": 0.1}
+    }, "groups": {}});"#;
+    let table = PointTable::from_dukascopy_json_str(json).unwrap();
+    assert_eq!(table.decimals("EURUSD"), Some(5));
+    assert_eq!(table.decimals("usdjpy"), Some(3));
+    assert_eq!(table.decimals("BTCUSD"), Some(1));
+    assert_eq!(table.decimals("XAUUSD"), Some(3));
+    assert_eq!(table.decimals("BAD"), None);
+    assert_eq!(table.decimals("GBPUSD"), Some(5));
+    assert!(PointTable::from_dukascopy_json_str("{}").is_err());
+    assert!(PointTable::from_dukascopy_json_str("{\"instruments\": ").is_err());
+}
+
+#[test]
+/// Test the decimals of powers of ten only and the symbol lookup
+fn test_point_value() {
+    assert_eq!(PointValue::FX.decimals(), Some(5));
+    assert_eq!(PointValue::new(1).unwrap().decimals(), Some(0));
+    assert_eq!(PointValue::new(250).unwrap().decimals(), None);
+    assert_eq!(PointValue::for_symbol("EURUSD"), Some(PointValue::FX));
+    assert_eq!(PointValue::for_symbol("DEU.IDX"), None);
+    assert!(matches!(PointValue::new(0), Err(Bi5Error::ZeroPointValue)));
+}
@@ -0,0 +1,159 @@
+//! Protobuf encoding of ticks, behind the `proto` feature, see `TickProto`.
+//!
+//! The messages follow `TICK_PROTO_SCHEMA` and are encoded with the plain
+//! protobuf wire format, so any protobuf implementation can decode them.
+
+use chrono::NaiveDateTime;
+use crate::{Bi5Error, Tick};
+
+/// The `.proto` schema of `TickProto`
+pub const TICK_PROTO_SCHEMA: &str = r#"syntax = "proto3";
+
+package bi5;
+
+// One tick of a bi5 file, prices in points
+message Tick {
+  // milliseconds since the Unix epoch
+  int64 time = 1;
+  uint32 bid = 2;
+  uint32 ask = 3;
+  float bidsize = 4;
+  float asksize = 5;
+}
+"#;
+
+/// A tick with its absolute time, the `bi5.Tick` message of `TICK_PROTO_SCHEMA`
+/// ```
+/// use bi5::*;
+/// let start = chrono::NaiveDateTime::from_timestamp_opt(1671199200, 0).unwrap();
+/// let tick = from_compressed_bytes(&std::fs::read("test/test.bi5").unwrap()).unwrap()[0];
+/// let proto = tick_to_proto(&tick, start);
+/// assert_eq!(proto.time, 1671201060002);
+/// assert_eq!(TickProto::decode(&proto.encode_to_vec()).unwrap(), proto);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TickProto {
+    /// Milliseconds since the Unix epoch
+    pub time: i64,
+    /// Bid price
+    pub bid: u32,
+    /// Ask price
+    pub ask: u32,
+    /// Bid size
+    pub bidsize: f32,
+    /// Ask size
+    pub asksize: f32,
+}
+
+/// The message of `tick` in a file starting at `file_start`
+pub fn tick_to_proto(tick: &Tick, file_start: NaiveDateTime) -> TickProto {
+    let t = tick.time(file_start);
+    TickProto { time: t.timestamp_millis(), bid: tick.bid, ask: tick.ask, bidsize: tick.bidsize, asksize: tick.asksize }
+}
+
+/// Wire types of the fields
+const VARINT: u8 = 0;
+const I64: u8 = 1;
+const LEN: u8 = 2;
+const I32: u8 = 5;
+
+fn push_varint(buf: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        buf.push(x as u8 | 0x80);
+        x >>= 7;
+    }
+    buf.push(x as u8);
+}
+
+fn malformed(msg: &str) -> Bi5Error {
+    Bi5Error::Parse(format!("malformed Tick message: {}", msg))
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> Result<u64, Bi5Error> {
+    let mut x = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *buf.get(*pos).ok_or_else(|| malformed("truncated varint"))?;
+        *pos += 1;
+        x |= ((b & 0x7f) as u64) << shift;
+        if b < 0x80 {
+            return Ok(x);
+        }
+    }
+    Err(malformed("varint too long"))
+}
+
+fn read_bytes<'a>(buf: &'a [u8], pos: &mut usize, n: usize) -> Result<&'a [u8], Bi5Error> {
+    let bytes = buf.get(*pos..pos.saturating_add(n)).ok_or_else(|| malformed("truncated field"))?;
+    *pos += n;
+    Ok(bytes)
+}
+
+impl TickProto {
+    /// Encode as protobuf message, fields with default values are omitted
+    /// like proto3 does
+    pub fn encode_to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(32);
+        if self.time != 0 {
+            buf.push(1 << 3 | VARINT);
+            push_varint(&mut buf, self.time as u64);
+        }
+        for (field, x) in [(2, self.bid), (3, self.ask)] {
+            if x != 0 {
+                buf.push(field << 3 | VARINT);
+                push_varint(&mut buf, x as u64);
+            }
+        }
+        for (field, x) in [(4, self.bidsize), (5, self.asksize)] {
+            if x.to_bits() != 0 {
+                buf.push(field << 3 | I32);
+                buf.extend_from_slice(&x.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    /// Decode a protobuf message, unknown fields are skipped
+    pub fn decode(buf: &[u8]) -> Result<Self, Bi5Error> {
+        let mut proto = TickProto::default();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let key = read_varint(buf, &mut pos)?;
+            match (key >> 3, (key & 7) as u8) {
+                (1, VARINT) => proto.time = read_varint(buf, &mut pos)? as i64,
+                // uint32 fields keep the low 32 bits like other implementations
+                (2, VARINT) => proto.bid = read_varint(buf, &mut pos)? as u32,
+                (3, VARINT) => proto.ask = read_varint(buf, &mut pos)? as u32,
+                (field @ (4 | 5), I32) => {
+                    let b = read_bytes(buf, &mut pos, 4)?;
+                    let x = f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+                    if field == 4 { proto.bidsize = x } else { proto.asksize = x }
+                }
+                (_, VARINT) => { read_varint(buf, &mut pos)?; }
+                (_, I64) => { read_bytes(buf, &mut pos, 8)?; }
+                (_, LEN) => {
+                    let n = read_varint(buf, &mut pos)?;
+                    read_bytes(buf, &mut pos, usize::try_from(n).unwrap_or(usize::MAX))?;
+                }
+                (_, I32) => { read_bytes(buf, &mut pos, 4)?; }
+                (field, wire_type) => return Err(malformed(&format!("field {} has wire type {}", field, wire_type))),
+            }
+        }
+        Ok(proto)
+    }
+}
+
+#[test]
+/// Test the wire bytes of a known message, negative times and unknown fields
+fn test_tick_proto() {
+    let proto = TickProto { time: 150, bid: 1, ask: 0, bidsize: 1.0, asksize: 0.0 };
+    assert_eq!(proto.encode_to_vec(), vec![0x08, 0x96, 0x01, 0x10, 0x01, 0x25, 0x00, 0x00, 0x80, 0x3f]);
+    let negative = TickProto { time: -1, ..proto };
+    assert_eq!(negative.encode_to_vec().len(), 1 + 10 + 2 + 5);
+    assert_eq!(TickProto::decode(&negative.encode_to_vec()).unwrap(), negative);
+    let mut unknown = vec![0x30, 0x05, 0x3a, 0x02, b'h', b'i'];
+    unknown.extend(proto.encode_to_vec());
+    assert_eq!(TickProto::decode(&unknown).unwrap(), proto);
+    assert!(TickProto::decode(&[0x08]).is_err());
+    assert!(TickProto::decode(&[0x25, 0x00]).is_err());
+    assert_eq!(TickProto::decode(&[]).unwrap(), TickProto::default());
+}
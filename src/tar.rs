@@ -0,0 +1,334 @@
+//! Reading bi5 files out of a gzipped tar archive.
+//!
+//! The archive is inflated while it is read, nothing is extracted to disk.
+//! gzip and tar are handled here without further dependencies, entries are
+//! read in archive order. Regular files, GNU long names and the pax `path`
+//! record are supported. Each tar header checksum is verified, the gzip CRC-32
+//! and size once the end of the archive is reached.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+use chrono::NaiveDateTime;
+use crate::{Bi5Error, FileIter, Tick, path_datetime};
+use crate::decode::{check_len, decompress};
+use crate::inflate::Inflate;
+
+/// Iterator over the ticks of all bi5 entries of a `.tar.gz`, see `Bi5::from_tar_gz`
+///
+/// The first error is yielded as `Err` and ends the iteration, as the rest of
+/// the stream can't be located. A gzip trailer that doesn't match is an `Err`
+/// after the last tick.
+pub struct TarGzIter {
+    reader: Inflate<BufReader<File>>,
+    file_iter: Option<FileIter>,
+    /// set after the end of the archive or the first error
+    done: bool,
+}
+
+fn bad(msg: &str) -> Bi5Error {
+    Bi5Error::Decompress(format!("tar.gz: {}", msg))
+}
+
+impl TarGzIter {
+    pub(crate) fn open(path: &Path) -> Result<Self, Bi5Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+        skip_gzip_header(&mut reader)?;
+        Ok(TarGzIter { reader: Inflate::new(reader), file_iter: None, done: false })
+    }
+
+    /// Reads up to the next bi5 entry and decodes it
+    fn open_next(&mut self) -> Result<Option<FileIter>, Bi5Error> {
+        let mut long_name = None;
+        loop {
+            let mut header = [0u8; 512];
+            self.reader.read_exact(&mut header)?;
+            if header.iter().all(|&b| b == 0) {
+                check_gzip_trailer(&mut self.reader)?;
+                return Ok(None);
+            }
+            check_header_checksum(&header)?;
+            let size = entry_size(&header)?;
+            let mut data = Vec::new();
+            (&mut self.reader).take(size).read_to_end(&mut data)?;
+            if (data.len() as u64) < size {
+                return Err(bad("truncated entry"));
+            }
+            let padding = (512 - size % 512) % 512;
+            io::copy(&mut (&mut self.reader).take(padding), &mut io::sink())?;
+            let name = long_name.take().unwrap_or_else(|| header_name(&header));
+            match header[156] {
+                b'L' => long_name = Some(c_str(&data)),
+                b'x' => long_name = pax_path(&data),
+                b'0' | 0 => {
+                    let path = Path::new(&name);
+                    // sidecar index files share the hour prefix of their bi5 file
+                    if path.extension().is_some_and(|ext| ext == "idx") {
+                        continue;
+                    }
+                    if let Some(date_time) = path_datetime(path) {
+                        let buf = decompress(&data[..])?;
+                        check_len(&buf)?;
+                        return Ok(Some(FileIter::new(buf, date_time)));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Iterator for TarGzIter {
+    type Item = Result<(NaiveDateTime, Tick), Bi5Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.file_iter.as_mut().and_then(|it| it.next()) {
+                return Some(Ok(item));
+            }
+            if self.done {
+                return None;
+            }
+            match self.open_next() {
+                Ok(Some(file_iter)) => self.file_iter = Some(file_iter),
+                Ok(None) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    self.file_iter = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+// ---- tar ----
+
+/// The bytes up to the first NUL as string
+fn c_str(buf: &[u8]) -> String {
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).into_owned()
+}
+
+/// Entry name, with the ustar prefix if present
+fn header_name(header: &[u8; 512]) -> String {
+    let name = c_str(&header[0..100]);
+    let prefix = if &header[257..262] == b"ustar" { c_str(&header[345..500]) } else { String::new() };
+    if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) }
+}
+
+/// Compares the checksum field with the sum of the header bytes, the field
+/// itself counted as spaces. Some old tars summed signed bytes.
+fn check_header_checksum(header: &[u8; 512]) -> Result<(), Bi5Error> {
+    let field = c_str(&header[148..156]);
+    let expected = u32::from_str_radix(field.trim(), 8).map_err(|_| bad("bad header checksum"))?;
+    let unsigned: u32 = header.iter().enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u32 } else { b as u32 })
+        .sum();
+    let signed: i32 = header.iter().enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as i32 } else { b as i8 as i32 })
+        .sum();
+    if expected != unsigned && expected as i32 != signed {
+        return Err(bad("bad header checksum"));
+    }
+    Ok(())
+}
+
+/// Entry size, octal or GNU base-256
+fn entry_size(header: &[u8; 512]) -> Result<u64, Bi5Error> {
+    let field = &header[124..136];
+    if field[0] & 0x80 != 0 {
+        return Ok(field[4..].iter().fold(0, |size, &b| size << 8 | b as u64));
+    }
+    let digits = c_str(field);
+    let digits = digits.trim();
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8).map_err(|_| bad("bad entry size"))
+}
+
+/// The `path` of a pax extended header, records are `<len> <key>=<value>\n`
+fn pax_path(data: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(data).lines()
+        .filter_map(|record| record.split_once(' ')?.1.strip_prefix("path="))
+        .next_back()
+        .map(str::to_string)
+}
+
+// ---- gzip ----
+
+/// Compares the CRC-32 and size (mod 2^32) of the trailer with the inflated
+/// output, reading the deflate stream to its end
+fn check_gzip_trailer<R: Read>(inflate: &mut Inflate<R>) -> Result<(), Bi5Error> {
+    let (crc, size, reader) = inflate.finish()?;
+    let mut trailer = [0u8; 8];
+    reader.read_exact(&mut trailer).map_err(|_| bad("truncated gzip trailer"))?;
+    if trailer[0..4] != crc.to_le_bytes() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "tar.gz: gzip CRC-32 mismatch").into());
+    }
+    if trailer[4..8] != (size as u32).to_le_bytes() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "tar.gz: gzip size mismatch").into());
+    }
+    Ok(())
+}
+
+fn skip_gzip_header<R: Read>(reader: &mut R) -> Result<(), Bi5Error> {
+    let mut header = [0u8; 10];
+    reader.read_exact(&mut header)?;
+    if header[0..3] != [0x1f, 0x8b, 8] {
+        return Err(bad("not a gzip file"));
+    }
+    let flags = header[3];
+    let mut byte = [0u8; 1];
+    if flags & 4 != 0 {
+        let mut len = [0u8; 2];
+        reader.read_exact(&mut len)?;
+        io::copy(&mut reader.take(u16::from_le_bytes(len) as u64), &mut io::sink())?;
+    }
+    // file name and comment are NUL terminated
+    for flag in [8, 16] {
+        if flags & flag != 0 {
+            loop {
+                reader.read_exact(&mut byte)?;
+                if byte[0] == 0 { break; }
+            }
+        }
+    }
+    if flags & 2 != 0 {
+        reader.read_exact(&mut [0u8; 2])?;
+    }
+    Ok(())
+}
+
+/// gzip with stored deflate blocks
+#[cfg(test)]
+fn write_stored_gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    out.extend_from_slice(&crate::inflate::write_stored_deflate(data));
+    out.extend_from_slice(&crate::inflate::crc32(0, data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// tar with ustar headers, `(name, typeflag, data)`
+#[cfg(test)]
+fn write_tar(entries: &[(&str, u8, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (name, typeflag, data) in entries {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+        header[156] = *typeflag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[148..156].fill(b' ');
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+        out.extend_from_slice(&header);
+        out.extend_from_slice(data);
+        out.resize(out.len().div_ceil(512) * 512, 0);
+    }
+    out.resize(out.len() + 1024, 0);
+    out
+}
+
+#[cfg(test)]
+fn inflate(gz: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let mut reader = io::Cursor::new(gz);
+    skip_gzip_header(&mut reader).unwrap();
+    let mut out = Vec::new();
+    Inflate::new(reader).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+#[test]
+/// Stored, fixed and dynamic Huffman blocks, gzip from zlib
+fn test_inflate() {
+    let data: String = (0..12).map(|i| format!("{},{},{}\n", i * 37, 133117 + i * i % 91, 133153 + i % 17)).collect();
+    const FIXED: [u8; 136] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x33, 0xd0, 0x31, 0x34, 0x36,
+        0x36, 0x34, 0x34, 0x07, 0x53, 0xa6, 0xc6, 0x5c, 0xc6, 0x10, 0x96, 0xa1, 0x05, 0x44, 0xc0,
+        0x84, 0xcb, 0xdc, 0x04, 0xcc, 0x32, 0x32, 0x84, 0x08, 0x98, 0x72, 0x19, 0x1a, 0x42, 0x98,
+        0x46, 0x66, 0x10, 0x11, 0x33, 0x2e, 0x43, 0x13, 0x88, 0x6a, 0x63, 0x63, 0x88, 0x88, 0x39,
+        0x97, 0xa1, 0x85, 0x29, 0x98, 0x69, 0x62, 0x04, 0x11, 0xb1, 0xe0, 0x32, 0x32, 0x82, 0x32,
+        0xa1, 0x6a, 0x2c, 0xb9, 0x8c, 0x4c, 0x2d, 0xc1, 0x4c, 0x33, 0x88, 0x39, 0x66, 0x06, 0x5c,
+        0x46, 0x96, 0x10, 0xa6, 0x05, 0xc4, 0x02, 0x33, 0x43, 0x2e, 0x63, 0xa8, 0x91, 0x96, 0x10,
+        0x0b, 0xcc, 0x8c, 0x80, 0xee, 0x33, 0x40, 0xb6, 0xdd, 0xcc, 0x98, 0xcb, 0xc4, 0x00, 0xe2,
+        0x64, 0x13, 0x08, 0x65, 0x66, 0xc2, 0x05, 0x00, 0x3d, 0x2e, 0x0b, 0x94, 0xd4, 0x00, 0x00,
+        0x00,
+    ];
+    const DYNAMIC: [u8; 107] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x4d, 0x8e, 0xb9, 0x0d, 0xc0,
+        0x30, 0x0c, 0x03, 0x7b, 0xce, 0x92, 0xc2, 0xd4, 0x67, 0x69, 0xff, 0xc5, 0x12, 0x98, 0x2e,
+        0x52, 0xf1, 0x70, 0x10, 0x44, 0xae, 0x87, 0xee, 0xe4, 0x3e, 0x91, 0x0e, 0x17, 0xb1, 0x25,
+        0x02, 0x3b, 0x0e, 0x19, 0x25, 0x12, 0xa4, 0xd0, 0x4a, 0xa6, 0xc0, 0xd0, 0xb5, 0xbb, 0xcc,
+        0x06, 0x3b, 0x0f, 0x86, 0xc9, 0x34, 0xcc, 0x2e, 0xde, 0x9b, 0x81, 0xe5, 0x1c, 0x2c, 0xfd,
+        0xa9, 0x05, 0x1b, 0x61, 0xab, 0xa0, 0x08, 0xbf, 0x2f, 0x47, 0x05, 0x65, 0xdf, 0xbe, 0xf5,
+        0x6f, 0x2f, 0x47, 0x2c, 0x4d, 0x0e, 0x45, 0x05, 0x5e, 0x3d, 0x2e, 0x0b, 0x94, 0xd4, 0x00,
+        0x00, 0x00,
+    ];
+    assert_eq!(inflate(&FIXED).unwrap(), data.as_bytes());
+    assert_eq!(inflate(&DYNAMIC).unwrap(), data.as_bytes());
+    for gz in [&FIXED[..], &DYNAMIC[..]] {
+        let mut reader = io::Cursor::new(gz);
+        skip_gzip_header(&mut reader).unwrap();
+        check_gzip_trailer(&mut Inflate::new(reader)).unwrap();
+    }
+    let big: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    assert_eq!(inflate(&write_stored_gzip(&big)).unwrap(), big);
+    assert!(inflate(&DYNAMIC[..60]).is_err());
+}
+
+#[test]
+/// Entries are read in archive order, long names are followed, others skipped
+fn test_from_tar_gz() {
+    let bi5 = std::fs::read("test/test.bi5").unwrap();
+    let long = format!("{}/EURUSD/2022/11/16/14h_ticks.bi5", "x".repeat(100));
+    let tar = write_tar(&[
+        ("EURUSD/", b'5', b""),
+        ("EURUSD/2022/11/16/15h_ticks.bi5", b'0', &bi5),
+        ("EURUSD/2022/11/16/15h_ticks.bi5.idx", b'0', b"10412 36827\n"),
+        ("README.txt", b'0', b"hello"),
+        ("././@LongLink", b'L', long.as_bytes()),
+        (&long[..100], b'0', &bi5),
+    ]);
+    let path = std::env::temp_dir().join(format!("bi5-tar-{}.tar.gz", std::process::id()));
+    std::fs::write(&path, write_stored_gzip(&tar)).unwrap();
+    let ticks: Vec<(NaiveDateTime, Tick)> = crate::Bi5::from_tar_gz(&path).unwrap().collect::<Result<_, _>>().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(ticks.len(), 2 * 10412);
+    assert_eq!(ticks[0].0.to_string(), "2022-12-16 15:31:00.002");
+    assert_eq!(ticks[10412].0.to_string(), "2022-12-16 14:31:00.002");
+}
+
+#[test]
+/// A corrupted gzip trailer is an `Err` after the last tick, a corrupted tar
+/// header an `Err` before the entry
+fn test_from_tar_gz_corrupt() {
+    let bi5 = std::fs::read("test/test.bi5").unwrap();
+    let gz = write_stored_gzip(&write_tar(&[("EURUSD/2022/11/16/14h_ticks.bi5", b'0', &bi5)]));
+    let path = std::env::temp_dir().join(format!("bi5-tar-corrupt-{}.tar.gz", std::process::id()));
+    let read = |gz: &[u8]| -> Vec<Result<(NaiveDateTime, Tick), Bi5Error>> {
+        std::fs::write(&path, gz).unwrap();
+        crate::Bi5::from_tar_gz(&path).unwrap().collect()
+    };
+    assert!(read(&gz).iter().all(|item| item.is_ok()));
+    let mut bad_crc = gz.clone();
+    let n = bad_crc.len();
+    bad_crc[n - 8] ^= 0xff;
+    let items = read(&bad_crc);
+    assert_eq!(items.len(), 10412 + 1);
+    let e = items.last().unwrap().as_ref().unwrap_err();
+    assert!(matches!(e, Bi5Error::Io(e) if e.kind() == io::ErrorKind::InvalidData), "{}", e);
+    let mut bad_size = gz.clone();
+    bad_size[n - 1] ^= 0xff;
+    assert!(read(&bad_size).last().unwrap().is_err());
+    // the first byte of the entry name, after the gzip and stored block headers
+    let mut bad_header = gz.clone();
+    bad_header[10 + 5] ^= 0x01;
+    let items = read(&bad_header);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(items.len(), 1);
+    assert!(items[0].is_err());
+}
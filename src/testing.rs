@@ -0,0 +1,53 @@
+//! Fixture helpers for downstream tests, behind the `testing` feature.
+//!
+//! ```
+//! use bi5::{from_compressed_bytes, testing::*};
+//! let ticks = make_test_ticks(100);
+//! assert_eq!(from_compressed_bytes(&encode_test_file(&ticks)).unwrap(), ticks);
+//! ```
+
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+use crate::Tick;
+
+/// `n` deterministic ticks spread over the hour with varying prices and sizes
+pub fn make_test_ticks(n: usize) -> Vec<Tick> {
+    let step = (crate::HOUR_MILLISECS as usize).checked_div(n).unwrap_or(0);
+    (0..n).map(|i| {
+        let bid = 133_000 + (i * 7 % 101) as u32;
+        Tick {
+            millisecs: (i * step) as u32,
+            ask: bid + 1 + (i % 5) as u32,
+            bid,
+            asksize: 0.5 + (i % 4) as f32 * 0.25,
+            bidsize: 1.0 + (i % 3) as f32 * 0.5,
+        }
+    }).collect()
+}
+
+/// Encode `ticks` as an LZMA compressed bi5 file
+pub fn encode_test_file(ticks: &[Tick]) -> Vec<u8> {
+    crate::encode_bi5(ticks)
+}
+
+/// Write `ticks` as the bi5 file `name` below `root`, creating the directories
+///
+/// Returns the path of the file.
+#[cfg(feature = "fs")]
+pub fn write_test_file(root: &Path, name: &str, ticks: &[Tick]) -> PathBuf {
+    let path = root.join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, encode_test_file(ticks)).unwrap();
+    path
+}
+
+#[test]
+/// Test that ticks survive the round trip and stay within the hour
+fn test_round_trip() {
+    for n in [0, 1, 10412] {
+        let ticks = make_test_ticks(n);
+        assert_eq!(ticks.len(), n);
+        assert!(ticks.iter().all(|t| !t.is_out_of_hour() && t.ask > t.bid));
+        assert_eq!(crate::from_compressed_bytes(&encode_test_file(&ticks)).unwrap(), ticks);
+    }
+}
@@ -0,0 +1,76 @@
+//! Timestamp formatting for tick output.
+
+use std::{fmt, str::FromStr};
+use chrono::{NaiveDateTime, format::{Item, StrftimeItems}};
+
+/// Output format of tick timestamps
+///
+/// Parses from `iso`, `epoch_ms`, `epoch_ns`, anything else is taken as a
+/// chrono format string (e.g. `%Y%m%d %H:%M:%S%.f`).
+/// ```
+/// use bi5::TimeFormat;
+/// let t = chrono::NaiveDate::from_ymd_opt(2022, 12, 16).unwrap().and_hms_milli_opt(14, 31, 0, 2).unwrap();
+/// assert_eq!(TimeFormat::Iso.format(t), "2022-12-16T14:31:00.002");
+/// assert_eq!("epoch_ns".parse::<TimeFormat>().unwrap().format(t), "1671201060002000000");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// ISO 8601 with milliseconds, `2022-12-16T14:31:00.002`
+    Iso,
+    /// Milliseconds since the Unix epoch
+    EpochMs,
+    /// Nanoseconds since the Unix epoch
+    EpochNs,
+    /// chrono `strftime` format string
+    Custom(String),
+}
+
+impl TimeFormat {
+    /// Format `t`
+    pub fn format(&self, t: NaiveDateTime) -> String {
+        match self {
+            TimeFormat::Iso => t.format("%Y-%m-%dT%H:%M:%S%.3f").to_string(),
+            TimeFormat::EpochMs => t.timestamp_millis().to_string(),
+            // i64 nanoseconds overflow after 2262, bi5 has millisecond resolution anyway
+            TimeFormat::EpochNs => format!("{}000000", t.timestamp_millis()),
+            TimeFormat::Custom(fmt) => t.format(fmt).to_string(),
+        }
+    }
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "iso" => Ok(TimeFormat::Iso),
+            "epoch_ms" => Ok(TimeFormat::EpochMs),
+            "epoch_ns" => Ok(TimeFormat::EpochNs),
+            // invalid specifiers would only fail (panic) when formatting
+            _ if StrftimeItems::new(s).any(|item| item == Item::Error) => {
+                Err(format!("invalid time format {:?}", s))
+            }
+            _ => Ok(TimeFormat::Custom(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for TimeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeFormat::Iso => write!(f, "iso"),
+            TimeFormat::EpochMs => write!(f, "epoch_ms"),
+            TimeFormat::EpochNs => write!(f, "epoch_ns"),
+            TimeFormat::Custom(fmt) => write!(f, "{}", fmt),
+        }
+    }
+}
+
+#[test]
+/// Custom formats and invalid specifiers
+fn test_time_format() {
+    let t = chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_milli_opt(0, 0, 1, 500).unwrap();
+    assert_eq!(TimeFormat::EpochMs.format(t), "1500");
+    assert_eq!(TimeFormat::EpochNs.format(t), "1500000000");
+    assert_eq!("%H%M%S%.6f".parse::<TimeFormat>().unwrap().format(t), "000001.500000");
+    assert!("%Q".parse::<TimeFormat>().is_err());
+}
@@ -0,0 +1,240 @@
+//! Reading bi5 files out of a zip archive.
+//!
+//! Bi5 payloads are already LZMA compressed, so archives of them are often
+//! written with the `stored` method, but `zip -r` deflates them anyway. Stored
+//! and deflated entries are supported, there is no zip64 and no CRC check.
+
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+use chrono::NaiveDateTime;
+use crate::{Bi5Error, FileIter, Tick, path_datetime};
+use crate::decode::{check_len, decompress};
+use crate::inflate::Inflate;
+
+const EOCD_SIG: u32 = 0x06054b50;
+const CENTRAL_SIG: u32 = 0x02014b50;
+const LOCAL_SIG: u32 = 0x04034b50;
+/// End of central directory record without comment
+const EOCD_LEN: usize = 22;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// Iterator over the ticks of all bi5 entries of a zip archive, see `Bi5::from_zip`
+///
+/// An entry that fails to decode is yielded as `Err`, the iteration then
+/// continues with the next entry.
+pub struct ZipIter {
+    reader: BufReader<File>,
+    /// remaining entries in reverse name order
+    entries: Vec<Entry>,
+    file_iter: Option<FileIter>,
+}
+
+struct Entry {
+    date_time: NaiveDateTime,
+    method: u16,
+    size: u32,
+    offset: u32,
+}
+
+fn u16_at(buf: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([buf[i], buf[i + 1]])
+}
+
+fn u32_at(buf: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]])
+}
+
+fn bad(msg: &str) -> Bi5Error {
+    Bi5Error::Decompress(format!("zip: {}", msg))
+}
+
+impl ZipIter {
+    pub(crate) fn open(path: &Path) -> Result<Self, Bi5Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut entries = read_central_directory(&mut reader)?;
+        if let Some((name, entry)) = entries.iter().find(|(_, e)| e.method != STORED && e.method != DEFLATED) {
+            return Err(bad(&format!("{}: unsupported compression method {}", name, entry.method)));
+        }
+        entries.sort_by(|a, b| b.0.cmp(&a.0));
+        let entries = entries.into_iter().map(|(_, e)| e).collect();
+        Ok(ZipIter { reader, entries, file_iter: None })
+    }
+
+    fn open_next(&mut self) -> Result<Option<FileIter>, Bi5Error> {
+        let entry = match self.entries.pop() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let mut header = [0u8; 30];
+        self.reader.seek(SeekFrom::Start(entry.offset as u64))?;
+        self.reader.read_exact(&mut header)?;
+        if u32_at(&header, 0) != LOCAL_SIG {
+            return Err(bad("bad local file header"));
+        }
+        let skip = u16_at(&header, 26) as i64 + u16_at(&header, 28) as i64;
+        self.reader.seek(SeekFrom::Current(skip))?;
+        let data = (&mut self.reader).take(entry.size as u64);
+        let buf = match entry.method {
+            DEFLATED => decompress(Inflate::new(data))?,
+            _ => decompress(data)?,
+        };
+        check_len(&buf)?;
+        Ok(Some(FileIter::new(buf, entry.date_time)))
+    }
+}
+
+/// Reads the central directory, returning the bi5 entries with their names
+fn read_central_directory<R: Read + Seek>(reader: &mut R) -> Result<Vec<(String, Entry)>, Bi5Error> {
+    // the EOCD record is followed by a comment of at most u16::MAX bytes
+    let len = reader.seek(SeekFrom::End(0))?;
+    let tail_len = len.min((EOCD_LEN + u16::MAX as usize) as u64);
+    reader.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = Vec::with_capacity(tail_len as usize);
+    reader.read_to_end(&mut tail)?;
+    let eocd = (0..(tail.len() + 1).saturating_sub(EOCD_LEN))
+        .rev()
+        .find(|&i| u32_at(&tail, i) == EOCD_SIG)
+        .ok_or_else(|| bad("end of central directory not found"))?;
+    let count = u16_at(&tail, eocd + 10) as usize;
+    let cd_len = u32_at(&tail, eocd + 12) as usize;
+    let cd_offset = u32_at(&tail, eocd + 16) as u64;
+
+    let mut cd = vec![0u8; cd_len];
+    reader.seek(SeekFrom::Start(cd_offset))?;
+    reader.read_exact(&mut cd)?;
+    let mut entries = Vec::with_capacity(count);
+    let mut i = 0;
+    for _ in 0..count {
+        if i + 46 > cd.len() || u32_at(&cd, i) != CENTRAL_SIG {
+            return Err(bad("bad central directory header"));
+        }
+        let name_len = u16_at(&cd, i + 28) as usize;
+        let next = i + 46 + name_len + u16_at(&cd, i + 30) as usize + u16_at(&cd, i + 32) as usize;
+        let name = cd.get(i + 46..i + 46 + name_len).ok_or_else(|| bad("truncated central directory"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        // directories, sidecar index files and files without a Dukascopy
+        // path are skipped
+        let path = Path::new(&name);
+        if path.extension().is_some_and(|ext| ext == "idx") {
+            i = next;
+            continue;
+        }
+        if let Some(date_time) = path_datetime(path) {
+            entries.push((name, Entry {
+                date_time,
+                method: u16_at(&cd, i + 10),
+                size: u32_at(&cd, i + 20),
+                offset: u32_at(&cd, i + 42),
+            }));
+        }
+        i = next;
+    }
+    Ok(entries)
+}
+
+impl Iterator for ZipIter {
+    type Item = Result<(NaiveDateTime, Tick), Bi5Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.file_iter.as_mut().and_then(|it| it.next()) {
+                return Some(Ok(item));
+            }
+            match self.open_next() {
+                Ok(file_iter) => self.file_iter = Some(file_iter?),
+                Err(e) => {
+                    self.file_iter = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Writes a zip archive, `(name, method, data)`, deflated entries as stored
+/// deflate blocks
+#[cfg(test)]
+fn write_zip(files: &[(&str, u16, &[u8])]) -> Vec<u8> {
+    let (mut out, mut cd) = (Vec::new(), Vec::new());
+    for (name, method, raw) in files {
+        let deflated;
+        let data = if *method == DEFLATED {
+            deflated = crate::inflate::write_stored_deflate(raw);
+            &deflated[..]
+        } else {
+            raw
+        };
+        let offset = out.len() as u32;
+        let mut common = Vec::new();
+        common.extend_from_slice(&[20, 0, 0, 0]); // version, flags
+        common.extend_from_slice(&method.to_le_bytes());
+        common.extend_from_slice(&[0, 0, 0, 0]); // time, date
+        common.extend_from_slice(&0u32.to_le_bytes()); // crc, unchecked
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(&LOCAL_SIG.to_le_bytes());
+        out.extend_from_slice(&common);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+        cd.extend_from_slice(&CENTRAL_SIG.to_le_bytes());
+        cd.extend_from_slice(&[20, 0]);
+        cd.extend_from_slice(&common);
+        cd.extend_from_slice(&[0; 10]); // comment len, disk, attributes
+        cd.extend_from_slice(&offset.to_le_bytes());
+        cd.extend_from_slice(name.as_bytes());
+    }
+    let cd_offset = out.len() as u32;
+    out.extend_from_slice(&cd);
+    out.extend_from_slice(&EOCD_SIG.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(cd.len() as u32).to_le_bytes());
+    out.extend_from_slice(&cd_offset.to_le_bytes());
+    out.extend_from_slice(&[0; 2]);
+    out
+}
+
+#[test]
+/// Entries are read in name order, non-bi5 and index entries are skipped,
+/// stored and deflated entries are decoded
+fn test_from_zip() {
+    let bi5 = std::fs::read("test/test.bi5").unwrap();
+    let zip = write_zip(&[
+        ("EURUSD/2022/11/16/15h_ticks.bi5", DEFLATED, &bi5),
+        ("README.txt", STORED, b"hello"),
+        ("EURUSD/2022/11/16/14h_ticks.bi5", STORED, &bi5),
+        ("EURUSD/2022/11/16/14h_ticks.bi5.idx", STORED, b"10412 36827\n"),
+    ]);
+    let path = std::env::temp_dir().join(format!("bi5-zip-{}.zip", std::process::id()));
+    std::fs::write(&path, zip).unwrap();
+    let ticks: Vec<(NaiveDateTime, Tick)> = crate::Bi5::from_zip(&path).unwrap().collect::<Result<_, _>>().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(ticks.len(), 2 * 10412);
+    assert_eq!(ticks[0].0.to_string(), "2022-12-16 14:31:00.002");
+    assert_eq!(ticks[10412].0.to_string(), "2022-12-16 15:31:00.002");
+}
+
+#[test]
+/// Unsupported methods fail `from_zip`, an entry that fails to decode is an
+/// `Err` item followed by the next entry
+fn test_from_zip_errors() {
+    let bi5 = std::fs::read("test/test.bi5").unwrap();
+    let path = std::env::temp_dir().join(format!("bi5-zip-errors-{}.zip", std::process::id()));
+    std::fs::write(&path, write_zip(&[("EURUSD/2022/11/16/14h_ticks.bi5", 14, &bi5)])).unwrap();
+    assert!(crate::Bi5::from_zip(&path).is_err());
+    std::fs::write(&path, write_zip(&[
+        ("EURUSD/2022/11/16/14h_ticks.bi5", STORED, &bi5[..100]),
+        ("EURUSD/2022/11/16/15h_ticks.bi5", STORED, &bi5),
+    ])).unwrap();
+    let items: Vec<_> = crate::Bi5::from_zip(&path).unwrap().collect();
+    std::fs::remove_file(&path).unwrap();
+    assert!(items[0].is_err());
+    assert_eq!(items.len(), 1 + 10412);
+    assert_eq!(items[1].as_ref().unwrap().0.to_string(), "2022-12-16 15:31:00.002");
+}